@@ -0,0 +1,610 @@
+use ansi_term::Color;
+
+use chrono::prelude::*;
+use chrono::Duration;
+
+use core::str::FromStr;
+
+use super::format_duration;
+use super::parse_duration;
+use super::profile::is_valid_profile_name;
+use super::profile::DEFAULT_PROFILE;
+use super::GoalPeriod;
+
+use std::env;
+use std::fmt::Display;
+use std::fmt::Error as FormatError;
+use std::fmt::Formatter;
+use std::fs;
+use std::io;
+use std::io::IsTerminal;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+//==============================================================================
+//
+//                              Type Definitions
+//
+//==============================================================================
+
+/// The settings of the config.toml file. Settings that aren't in the file have default values.
+#[derive(Default)]
+pub struct Config {
+    pub data_dir: Option<PathBuf>,
+    pub week_start: Option<Weekday>,
+    /// The day of the month periods start on, from 1 to 28.
+    pub month_start: Option<u32>,
+    /// The time days start at, so the time before it belongs to the previous day.
+    pub day_start: Option<NaiveTime>,
+    pub date_order: Option<DateOrder>,
+    pub forecast_limit: Option<Duration>,
+    pub color: Option<ColorMode>,
+    pub default_task: Option<String>,
+    /// The profile used when none is given in the command line.
+    pub profile: Option<String>,
+}
+
+/// The order of the day, month and year in abbreviated dates, e.g. `25/01/2019` for
+/// `DayMonthYear`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum DateOrder {
+    #[default]
+    DayMonthYear,
+    MonthDayYear,
+    YearMonthDay,
+}
+
+/// When to color the output.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ColorMode {
+    /// Only when writing to a terminal, unless the `NO_COLOR` environment variable is set.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// The names of the settings, in the order they're listed.
+pub const KEYS: [&str; 9] = [
+    "data_dir",
+    "week_start",
+    "month_start",
+    "day_start",
+    "date_order",
+    "forecast_limit",
+    "color",
+    "default_task",
+    "profile",
+];
+
+/// Overrides the path of the config.toml file.
+pub const CONFIG_VAR: &str = "TIMELOG_CONFIG";
+
+/// Overrides the data directory set in the config.toml file.
+pub const DATA_DIR_VAR: &str = "TIMELOG_DATA_DIR";
+
+static CURRENT: OnceLock<Config> = OnceLock::new();
+
+//==============================================================================
+//
+//                                Error Types
+//
+//==============================================================================
+
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Serialize(toml::ser::Error),
+    NoConfigDir,
+    UnknownKey(String),
+    InvalidValue(&'static str, String),
+}
+
+//==============================================================================
+//
+//                              Settings Access
+//
+//==============================================================================
+
+/// Makes the configuration available to the whole program. Only the first call has an effect.
+pub fn install(config: Config) {
+    CURRENT.set(config).ok();
+}
+
+/// The configuration installed when the program started, or the default one.
+pub fn current() -> &'static Config {
+    CURRENT.get_or_init(Config::default)
+}
+
+/// The path of the config.toml file: `$TIMELOG_CONFIG`, or `timelog/config.toml` inside the
+/// user's configuration directory, e.g. `$XDG_CONFIG_HOME` or `~/.config` on Linux.
+pub fn path() -> Result<PathBuf, ConfigError> {
+    match env::var_os(CONFIG_VAR).filter(|path| !path.is_empty()) {
+        Some(path) => Ok(PathBuf::from(path)),
+        None => dirs::config_dir()
+            .map(|dir| dir.join("timelog").join("config.toml"))
+            .ok_or(ConfigError::NoConfigDir),
+    }
+}
+
+impl Config {
+    /// Reads the config.toml file. A missing file means the default configuration.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Config, ConfigError> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => {
+                return Ok(Config::default())
+            }
+            Err(error) => return Err(error.into()),
+        };
+
+        let table: toml::value::Table = toml::from_str(&text)?;
+        let mut config = Config::default();
+
+        for (key, value) in &table {
+            let value = match value {
+                toml::Value::String(value) => value.clone(),
+                toml::Value::Integer(value) => value.to_string(),
+                other => other.to_string(),
+            };
+
+            config.set(key, Some(&value))?;
+        }
+
+        Ok(config)
+    }
+
+    /// Writes the settings that have a value to the config.toml file, creating its directory
+    /// if needed.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ConfigError> {
+        let mut table = toml::value::Table::new();
+
+        for key in KEYS.iter() {
+            if let Some(value) = self.get(key)? {
+                table.insert(key.to_string(), toml::Value::String(value));
+            }
+        }
+
+        if let Some(dir) = path
+            .as_ref()
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+        {
+            fs::create_dir_all(dir)?;
+        }
+
+        fs::write(path, toml::to_string(&table)?)?;
+        Ok(())
+    }
+
+    /// The value of a setting as written in the file, if it's set.
+    pub fn get(&self, key: &str) -> Result<Option<String>, ConfigError> {
+        Ok(match key {
+            "data_dir" => self
+                .data_dir
+                .as_ref()
+                .map(|dir| dir.to_string_lossy().into_owned()),
+            "week_start" => self
+                .week_start
+                .map(|weekday| GoalPeriod::Weekday(weekday).to_string()),
+            "month_start" => self.month_start.map(|day| day.to_string()),
+            "day_start" => self.day_start.map(|time| time.format("%H:%M").to_string()),
+            "date_order" => self.date_order.map(|order| order.to_string()),
+            "forecast_limit" => self.forecast_limit.map(format_duration),
+            "color" => self.color.map(|color| color.to_string()),
+            "default_task" => self.default_task.clone(),
+            "profile" => self.profile.clone(),
+            _ => return Err(ConfigError::UnknownKey(key.to_string())),
+        })
+    }
+
+    /// The value of a setting that isn't set, if it has one.
+    pub fn default_value(key: &str) -> Result<Option<String>, ConfigError> {
+        Ok(match key {
+            "data_dir" => default_data_dir().map(|dir| dir.to_string_lossy().into_owned()),
+            "week_start" => Some(GoalPeriod::Weekday(Weekday::Mon).to_string()),
+            "month_start" => Some(String::from("1")),
+            "day_start" => Some(String::from("00:00")),
+            "date_order" => Some(DateOrder::default().to_string()),
+            "forecast_limit" => Some(format_duration(Duration::hours(12))),
+            "color" => Some(ColorMode::default().to_string()),
+            "default_task" => None,
+            "profile" => Some(DEFAULT_PROFILE.to_string()),
+            _ => return Err(ConfigError::UnknownKey(key.to_string())),
+        })
+    }
+
+    /// Changes a setting, or erases it if there's no value.
+    pub fn set(&mut self, key: &str, value: Option<&str>) -> Result<(), ConfigError> {
+        let invalid = |key| ConfigError::InvalidValue(key, value.unwrap_or_default().to_string());
+
+        match key {
+            "data_dir" => self.data_dir = value.map(PathBuf::from),
+            "week_start" => {
+                self.week_start = value
+                    .map(|value| match GoalPeriod::from_str(&value.to_lowercase()) {
+                        Ok(GoalPeriod::Weekday(weekday)) => Ok(weekday),
+                        _ => Err(()),
+                    })
+                    .transpose()
+                    .map_err(|_| invalid("week_start"))?
+            }
+            "month_start" => {
+                self.month_start = value
+                    .map(|value| match value.parse::<u32>() {
+                        Ok(day) if (1..=28).contains(&day) => Ok(day),
+                        _ => Err(()),
+                    })
+                    .transpose()
+                    .map_err(|_| invalid("month_start"))?
+            }
+            "day_start" => {
+                self.day_start = value
+                    .map(|value| NaiveTime::parse_from_str(value, "%H:%M"))
+                    .transpose()
+                    .map_err(|_| invalid("day_start"))?
+            }
+            "date_order" => {
+                self.date_order = value
+                    .map(DateOrder::from_str)
+                    .transpose()
+                    .map_err(|_| invalid("date_order"))?
+            }
+            "forecast_limit" => {
+                self.forecast_limit = value
+                    .map(parse_duration)
+                    .transpose()
+                    .map_err(|_| invalid("forecast_limit"))?
+            }
+            "color" => {
+                self.color = value
+                    .map(ColorMode::from_str)
+                    .transpose()
+                    .map_err(|_| invalid("color"))?
+            }
+            "default_task" => {
+                self.default_task = value
+                    .map(|value| {
+                        if super::is_valid_mnemonic(value) {
+                            Ok(value.to_string())
+                        } else {
+                            Err(())
+                        }
+                    })
+                    .transpose()
+                    .map_err(|_| invalid("default_task"))?
+            }
+            "profile" => {
+                self.profile = value
+                    .map(|value| {
+                        if is_valid_profile_name(value) {
+                            Ok(value.to_string())
+                        } else {
+                            Err(())
+                        }
+                    })
+                    .transpose()
+                    .map_err(|_| invalid("profile"))?
+            }
+            _ => return Err(ConfigError::UnknownKey(key.to_string())),
+        }
+
+        Ok(())
+    }
+
+    /// The directory of the history.log and data.json files: the one given in the command line,
+    /// `$TIMELOG_DATA_DIR`, the configured one or `timelog` inside the user's data directory,
+    /// e.g. `$XDG_DATA_HOME` or `~/.local/share` on Linux, in that order.
+    pub fn data_dir(&self, arg: Option<&str>) -> Option<PathBuf> {
+        arg.map(PathBuf::from)
+            .or_else(|| {
+                env::var_os(DATA_DIR_VAR)
+                    .filter(|dir| !dir.is_empty())
+                    .map(PathBuf::from)
+            })
+            .or_else(|| self.data_dir.clone())
+            .or_else(default_data_dir)
+    }
+
+    /// The profile the commands operate on: the one given in the command line, the configured
+    /// one or the default one, in that order.
+    pub fn profile<'a>(&'a self, arg: Option<&'a str>) -> &'a str {
+        arg.or(self.profile.as_deref()).unwrap_or(DEFAULT_PROFILE)
+    }
+
+    /// The first day of the week periods. Monday by default.
+    pub fn week_start(&self) -> Weekday {
+        self.week_start.unwrap_or(Weekday::Mon)
+    }
+
+    /// The day of the month periods start on. The 1st by default.
+    pub fn month_start(&self) -> u32 {
+        self.month_start.unwrap_or(1)
+    }
+
+    /// The time days start at. Midnight by default.
+    pub fn day_start(&self) -> NaiveTime {
+        self.day_start
+            .unwrap_or_else(|| NaiveTime::from_hms(0, 0, 0))
+    }
+
+    pub fn date_order(&self) -> DateOrder {
+        self.date_order.unwrap_or_default()
+    }
+
+    /// How far ahead the end of a goal can be forecast to be displayed. 12 hours by default.
+    pub fn forecast_limit(&self) -> Duration {
+        self.forecast_limit.unwrap_or_else(|| Duration::hours(12))
+    }
+
+    /// Colors the text, unless the output shouldn't be colored.
+    pub fn paint(&self, color: Color, text: &str) -> String {
+        let colored = match self.color.unwrap_or_default() {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            ColorMode::Auto => env::var_os("NO_COLOR").is_none() && io::stdout().is_terminal(),
+        };
+
+        if colored {
+            color.paint(text).to_string()
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+fn default_data_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("timelog"))
+}
+
+//==============================================================================
+//
+//                              Type Conversions
+//
+//==============================================================================
+
+impl FromStr for DateOrder {
+    type Err = ();
+
+    fn from_str(string: &str) -> Result<Self, ()> {
+        match string {
+            "dmy" => Ok(DateOrder::DayMonthYear),
+            "mdy" => Ok(DateOrder::MonthDayYear),
+            "ymd" => Ok(DateOrder::YearMonthDay),
+            _ => Err(()),
+        }
+    }
+}
+
+impl FromStr for ColorMode {
+    type Err = ();
+
+    fn from_str(string: &str) -> Result<Self, ()> {
+        match string {
+            "auto" => Ok(ColorMode::Auto),
+            "always" => Ok(ColorMode::Always),
+            "never" => Ok(ColorMode::Never),
+            _ => Err(()),
+        }
+    }
+}
+
+//==============================================================================
+//
+//                         Conversions Between Error Types
+//
+//==============================================================================
+
+impl From<io::Error> for ConfigError {
+    fn from(error: io::Error) -> ConfigError {
+        ConfigError::Io(error)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(error: toml::de::Error) -> ConfigError {
+        ConfigError::Parse(error)
+    }
+}
+
+impl From<toml::ser::Error> for ConfigError {
+    fn from(error: toml::ser::Error) -> ConfigError {
+        ConfigError::Serialize(error)
+    }
+}
+
+//==============================================================================
+//
+//                           Display Implementations
+//
+//==============================================================================
+
+impl Display for DateOrder {
+    fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FormatError> {
+        write!(
+            f,
+            "{}",
+            match self {
+                DateOrder::DayMonthYear => "dmy",
+                DateOrder::MonthDayYear => "mdy",
+                DateOrder::YearMonthDay => "ymd",
+            }
+        )
+    }
+}
+
+impl Display for ColorMode {
+    fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FormatError> {
+        write!(
+            f,
+            "{}",
+            match self {
+                ColorMode::Auto => "auto",
+                ColorMode::Always => "always",
+                ColorMode::Never => "never",
+            }
+        )
+    }
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FormatError> {
+        match self {
+            ConfigError::Io(error) => write!(f, "{}", error),
+            ConfigError::Parse(error) => write!(f, "invalid configuration file: {}", error),
+            ConfigError::Serialize(error) => write!(f, "{}", error),
+            ConfigError::NoConfigDir => write!(
+                f,
+                "the configuration directory is unknown, set {} to the path of the file.",
+                CONFIG_VAR
+            ),
+            ConfigError::UnknownKey(key) => write!(
+                f,
+                "unknown setting {}, valid settings: {}.",
+                key,
+                KEYS.join(", ")
+            ),
+            ConfigError::InvalidValue(key, value) => {
+                write!(f, "invalid value \"{}\" for {}: ", value, key)?;
+
+                match *key {
+                    "week_start" => write!(f, "expected a day of the week, e.g. sunday."),
+                    "month_start" => write!(f, "expected a day from 1 to 28."),
+                    "day_start" => write!(f, "expected a time, e.g. 04:00."),
+                    "date_order" => write!(f, "valid values: dmy, mdy, ymd."),
+                    "forecast_limit" => write!(f, "expected a time span, e.g. 12h."),
+                    "color" => write!(f, "valid values: auto, always, never."),
+                    "default_task" => write!(f, "expected a task mnemonic."),
+                    _ => write!(f, "unexpected value."),
+                }
+            }
+        }
+    }
+}
+
+//==============================================================================
+//
+//                                   Tests
+//
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::temp_dir;
+
+    #[test]
+    fn settings_are_set_and_unset() {
+        let mut config = Config::default();
+
+        config.set("week_start", Some("Sunday")).ok().unwrap();
+        config.set("forecast_limit", Some("8h30m")).ok().unwrap();
+        config.set("date_order", Some("ymd")).ok().unwrap();
+
+        assert_eq!(config.week_start(), Weekday::Sun);
+        assert_eq!(config.date_order(), DateOrder::YearMonthDay);
+        assert_eq!(
+            config.get("week_start").ok().unwrap().as_deref(),
+            Some("sunday")
+        );
+        assert_eq!(
+            config.get("forecast_limit").ok().unwrap().as_deref(),
+            Some("8h 30m")
+        );
+
+        config.set("week_start", None).ok().unwrap();
+        assert_eq!(config.get("week_start").ok().unwrap(), None);
+        assert_eq!(config.week_start(), Weekday::Mon);
+        assert_eq!(
+            Config::default_value("forecast_limit")
+                .ok()
+                .unwrap()
+                .as_deref(),
+            Some("12h")
+        );
+    }
+
+    #[test]
+    fn settings_are_validated() {
+        let mut config = Config::default();
+
+        let invalid = |result: Result<(), ConfigError>, expected: &str| match result {
+            Err(ConfigError::InvalidValue(key, _)) => key == expected,
+            _ => false,
+        };
+
+        assert!(invalid(
+            config.set("week_start", Some("week")),
+            "week_start"
+        ));
+        assert!(invalid(config.set("date_order", Some("dym")), "date_order"));
+        assert!(invalid(
+            config.set("forecast_limit", Some("soon")),
+            "forecast_limit"
+        ));
+        assert!(invalid(config.set("color", Some("red")), "color"));
+        assert!(invalid(
+            config.set("default_task", Some("two words")),
+            "default_task"
+        ));
+        assert!(matches!(
+            config.set("colour", Some("never")),
+            Err(ConfigError::UnknownKey(_))
+        ));
+        assert!(matches!(
+            config.get("colour"),
+            Err(ConfigError::UnknownKey(_))
+        ));
+
+        // Invalid values leave the settings as they were.
+        assert_eq!(config.get("week_start").ok().unwrap(), None);
+    }
+
+    #[test]
+    fn settings_are_saved_and_loaded() {
+        let path = temp_dir("config").join("timelog").join("config.toml");
+
+        assert!(Config::load(&path)
+            .ok()
+            .unwrap()
+            .get("color")
+            .ok()
+            .unwrap()
+            .is_none());
+
+        let mut config = Config::default();
+        config.set("color", Some("never")).ok().unwrap();
+        config.set("default_task", Some("dev")).ok().unwrap();
+        config.save(&path).ok().unwrap();
+
+        let loaded = Config::load(&path).ok().unwrap();
+
+        for key in KEYS.iter() {
+            assert_eq!(loaded.get(key).ok().unwrap(), config.get(key).ok().unwrap());
+        }
+
+        fs::write(&path, "color = \"sometimes\"\n").unwrap();
+        assert!(matches!(
+            Config::load(&path),
+            Err(ConfigError::InvalidValue("color", _))
+        ));
+    }
+
+    #[test]
+    fn data_dir_precedence() {
+        let mut config = Config::default();
+        config.set("data_dir", Some("/configured")).ok().unwrap();
+
+        env::set_var(DATA_DIR_VAR, "/from-env");
+        let with_env = (config.data_dir(Some("/from-arg")), config.data_dir(None));
+        env::remove_var(DATA_DIR_VAR);
+
+        assert_eq!(with_env.0, Some(PathBuf::from("/from-arg")));
+        assert_eq!(with_env.1, Some(PathBuf::from("/from-env")));
+        assert_eq!(config.data_dir(None), Some(PathBuf::from("/configured")));
+
+        config.set("data_dir", None).ok().unwrap();
+        assert_eq!(config.data_dir(None), default_data_dir());
+    }
+}
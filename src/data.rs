@@ -0,0 +1,586 @@
+use chrono::Duration;
+use chrono::NaiveDate;
+use chrono::NaiveTime;
+
+use core::str::FromStr;
+
+use indexmap::IndexMap;
+
+use serde::de::Error as DeserializeError;
+use serde::ser::SerializeMap;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+
+use super::format_duration;
+use super::parse_duration;
+use super::wal;
+use super::wal::Rewrite;
+use super::AbsenceKind;
+use super::GoalPeriod;
+use super::Money;
+use super::Rounding;
+
+use std::fmt::Display;
+use std::fmt::Error as FormatError;
+use std::fmt::Formatter;
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
+use std::path::Path;
+use std::path::PathBuf;
+
+//==============================================================================
+//
+//                              Type Definitions
+//
+//==============================================================================
+
+/// Contents of the data.json file.
+///
+/// The field order matters: the "general" object is always the last one of the root, and the
+/// active tasks are always stored after the inactive ones. See docs/files.md.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Data {
+    #[serde(default)]
+    pub tasks: IndexMap<String, Task>,
+    #[serde(default)]
+    pub general: General,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct Task {
+    pub active: bool,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
+    /// Hourly rate, instead of the one of the client or the default one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate: Option<Money>,
+    #[serde(default, skip_serializing_if = "Goals::is_empty")]
+    pub goals: Goals,
+    /// Rounding of the time logged by commits, instead of the general one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rounding: Option<Rounding>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct General {
+    #[serde(default, skip_serializing_if = "Goals::is_empty")]
+    pub goals: Goals,
+    /// Rounding of the time logged by commits of tasks without a policy of their own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rounding: Option<Rounding>,
+    #[serde(default, skip_serializing_if = "PlannedBreaks::is_empty")]
+    pub breaks: PlannedBreaks,
+    #[serde(default, skip_serializing_if = "Rates::is_empty")]
+    pub rates: Rates,
+    /// Number of the last invoice written.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub last_invoice: u32,
+    /// First day counted by the flex-time balance.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub balance_start: Option<NaiveDate>,
+    /// Days away from work, in order of start.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub absences: Vec<Absence>,
+    /// Tasks suspended by interruptions, the most recent last.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub interruptions: Vec<Interruption>,
+    /// Task stopped by the current break, to be started again when it's resumed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paused: Option<String>,
+}
+
+/// A task suspended by another one, to be started again when the other one stops.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct Interruption {
+    pub suspended: String,
+    pub by: String,
+}
+
+/// Hourly rates of the tasks without a rate of their own.
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct Rates {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<Money>,
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub clients: IndexMap<String, Money>,
+}
+
+/// Days away from work, from `start` to `end` inclusive. Only half of each day is expected to
+/// be worked if `half` is set.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct Absence {
+    #[serde(rename = "type")]
+    pub kind: AbsenceKind,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub half: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+/// Time goals of a task or of the work in general, stored as `"period": "duration"` pairs.
+#[derive(Default, Clone, PartialEq)]
+pub struct Goals(Vec<(GoalPeriod, Duration)>);
+
+/// Breaks planned for every day, stored as `"start time": "duration"` pairs in order of start.
+#[derive(Default, Clone, PartialEq)]
+pub struct PlannedBreaks(Vec<(NaiveTime, Duration)>);
+
+/// Provides access to the data.json file.
+pub struct DataFile {
+    path: PathBuf,
+}
+
+//==============================================================================
+//
+//                                Error Types
+//
+//==============================================================================
+
+pub enum DataError {
+    Io(io::Error),
+    Json(serde_json::Error),
+}
+
+//==============================================================================
+//
+//                               Data Handling
+//
+//==============================================================================
+
+impl Data {
+    pub fn task(&self, mnemonic: &str) -> Option<&Task> {
+        self.tasks.get(mnemonic)
+    }
+
+    pub fn task_mut(&mut self, mnemonic: &str) -> Option<&mut Task> {
+        self.tasks.get_mut(mnemonic)
+    }
+
+    /// Adds a new active task after all the other ones.
+    pub fn add_task(&mut self, mnemonic: &str, task: Task) {
+        self.tasks.insert(mnemonic.to_string(), task);
+        self.sort_task(mnemonic);
+    }
+
+    /// Removes a task, along with the interruptions it's involved in and the break it was
+    /// stopped by.
+    pub fn remove_task(&mut self, mnemonic: &str) -> Option<Task> {
+        self.general.interruptions.retain(|interruption| {
+            interruption.suspended != mnemonic && interruption.by != mnemonic
+        });
+
+        if self.general.paused.as_deref() == Some(mnemonic) {
+            self.general.paused = None;
+        }
+
+        self.tasks.shift_remove(mnemonic)
+    }
+
+    /// Changes the mnemonic of a task, keeping its position.
+    pub fn rename_task(&mut self, old: &str, new: &str) {
+        if let Some((index, _, task)) = self.tasks.get_full(old) {
+            let task = task.clone();
+            self.place_task(old, None, None);
+            self.place_task(new, Some(task), Some(index));
+            self.retarget_interruptions(old, new);
+        }
+    }
+
+    /// Changes the interruptions a task is involved in, and the break it was stopped by, to
+    /// refer to another task. Interruptions of a task by itself are dropped.
+    pub fn retarget_interruptions(&mut self, from: &str, into: &str) {
+        if self.general.paused.as_deref() == Some(from) {
+            self.general.paused = Some(into.to_string());
+        }
+
+        for interruption in &mut self.general.interruptions {
+            if interruption.suspended == from {
+                interruption.suspended = into.to_string();
+            }
+
+            if interruption.by == from {
+                interruption.by = into.to_string();
+            }
+        }
+
+        self.general
+            .interruptions
+            .retain(|interruption| interruption.suspended != interruption.by);
+    }
+
+    /// Activates or deactivates a task, moving it to the appropriate position:
+    /// active tasks go to the end, and recently inactivated tasks go right before the active ones.
+    pub fn set_active(&mut self, mnemonic: &str, active: bool) {
+        if let Some(task) = self.tasks.get_mut(mnemonic) {
+            if task.active != active {
+                task.active = active;
+                self.sort_task(mnemonic);
+            }
+        }
+    }
+
+    /// Places a task at the given position, replacing any other version of it.
+    /// If no task is provided, it's just removed. If no position is provided, it goes to the end.
+    pub fn place_task(&mut self, mnemonic: &str, task: Option<Task>, index: Option<usize>) {
+        self.tasks.shift_remove(mnemonic);
+
+        if let Some(task) = task {
+            let tasks = std::mem::replace(&mut self.tasks, IndexMap::new());
+            let mut tasks = tasks.into_iter();
+            let index = index.unwrap_or(usize::MAX);
+
+            self.tasks.extend(tasks.by_ref().take(index));
+            self.tasks.insert(mnemonic.to_string(), task);
+            self.tasks.extend(tasks);
+        }
+    }
+
+    /// The hourly rate of a task: its own rate, the rate of its client or the default rate.
+    pub fn rate(&self, mnemonic: &str) -> Option<&Money> {
+        let task = self.task(mnemonic);
+
+        task.and_then(|task| task.rate.as_ref())
+            .or_else(|| {
+                task.and_then(|task| task.client.as_ref())
+                    .and_then(|client| self.general.rates.clients.get(client))
+            })
+            .or(self.general.rates.default.as_ref())
+    }
+
+    /// The rounding policy that applies to the commits of a task.
+    pub fn rounding(&self, mnemonic: &str) -> Option<Rounding> {
+        self.task(mnemonic)
+            .and_then(|task| task.rounding)
+            .or(self.general.rounding)
+    }
+
+    /// Goals of the given task, or of the work in general if no task is provided.
+    pub fn goals(&self, mnemonic: Option<&str>) -> Option<&Goals> {
+        match mnemonic {
+            Some(mnemonic) => self.task(mnemonic).map(|task| &task.goals),
+            None => Some(&self.general.goals),
+        }
+    }
+
+    pub fn goals_mut(&mut self, mnemonic: Option<&str>) -> Option<&mut Goals> {
+        match mnemonic {
+            Some(mnemonic) => self.task_mut(mnemonic).map(|task| &mut task.goals),
+            None => Some(&mut self.general.goals),
+        }
+    }
+
+    /// Moves a task to the last position of its group (inactive or active tasks).
+    fn sort_task(&mut self, mnemonic: &str) {
+        let (mnemonic, task) = match self.tasks.shift_remove_entry(mnemonic) {
+            Some(entry) => entry,
+            None => return,
+        };
+
+        let position = if task.active {
+            self.tasks.len()
+        } else {
+            self.tasks
+                .values()
+                .position(|other| other.active)
+                .unwrap_or_else(|| self.tasks.len())
+        };
+
+        self.place_task(&mnemonic, Some(task), Some(position));
+    }
+}
+
+impl General {
+    /// The absence covering the given date, if any.
+    pub fn absence_on(&self, date: NaiveDate) -> Option<&Absence> {
+        self.absences
+            .iter()
+            .find(|absence| absence.start <= date && date <= absence.end)
+    }
+
+    /// Adds an absence, keeping them in order of start. Returns the absence it overlaps with
+    /// instead, if any.
+    pub fn add_absence(&mut self, absence: Absence) -> Result<(), &Absence> {
+        if let Some(index) = self
+            .absences
+            .iter()
+            .position(|other| other.start <= absence.end && absence.start <= other.end)
+        {
+            return Err(&self.absences[index]);
+        }
+
+        let index = self
+            .absences
+            .iter()
+            .position(|other| other.start > absence.start)
+            .unwrap_or(self.absences.len());
+
+        self.absences.insert(index, absence);
+        Ok(())
+    }
+
+    /// Removes the absence covering the given date, returning it if it existed.
+    pub fn erase_absence(&mut self, date: NaiveDate) -> Option<Absence> {
+        let index = self
+            .absences
+            .iter()
+            .position(|absence| absence.start <= date && date <= absence.end)?;
+
+        Some(self.absences.remove(index))
+    }
+}
+
+impl Task {
+    pub fn new(title: String, code: Option<String>) -> Task {
+        Task {
+            active: true,
+            title,
+            code,
+            client: None,
+            rate: None,
+            goals: Goals::default(),
+            rounding: None,
+        }
+    }
+}
+
+impl Rates {
+    pub fn is_empty(&self) -> bool {
+        self.default.is_none() && self.clients.is_empty()
+    }
+}
+
+impl Goals {
+    pub fn get(&self, period: GoalPeriod) -> Option<Duration> {
+        self.0
+            .iter()
+            .find(|(other, _)| *other == period)
+            .map(|(_, duration)| *duration)
+    }
+
+    pub fn set(&mut self, period: GoalPeriod, duration: Duration) {
+        match self.0.iter_mut().find(|(other, _)| *other == period) {
+            Some(goal) => goal.1 = duration,
+            None => self.0.push((period, duration)),
+        }
+    }
+
+    /// Removes the goal for the given period, returning whether it existed.
+    pub fn erase(&mut self, period: GoalPeriod) -> bool {
+        let len = self.0.len();
+        self.0.retain(|(other, _)| *other != period);
+        self.0.len() != len
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(GoalPeriod, Duration)> {
+        self.0.iter()
+    }
+}
+
+impl PlannedBreaks {
+    pub fn set(&mut self, start: NaiveTime, duration: Duration) {
+        match self.0.iter_mut().find(|(other, _)| *other == start) {
+            Some(planned) => planned.1 = duration,
+            None => {
+                self.0.push((start, duration));
+                self.0.sort_by_key(|(start, _)| *start);
+            }
+        }
+    }
+
+    /// Removes the break starting at the given time, returning whether it existed.
+    pub fn erase(&mut self, start: NaiveTime) -> bool {
+        let len = self.0.len();
+        self.0.retain(|(other, _)| *other != start);
+        self.0.len() != len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(NaiveTime, Duration)> {
+        self.0.iter()
+    }
+}
+
+//==============================================================================
+//
+//                                File Handling
+//
+//==============================================================================
+
+impl DataFile {
+    pub fn new<P: AsRef<Path>>(path: P) -> DataFile {
+        DataFile {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Loads the file contents. A missing file is the same as an empty one.
+    pub fn load(&self) -> Result<Data, DataError> {
+        match File::open(&self.path) {
+            Ok(file) => Ok(serde_json::from_reader(BufReader::new(file))?),
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => Ok(Data::default()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Rewrites the file through the write-ahead log, so it's never left incomplete.
+    pub fn save(&self, data: &Data) -> Result<(), DataError> {
+        wal::rewrite_tails(&[self.rewrite(data)?])?;
+        Ok(())
+    }
+
+    /// The rewrite saving the data, to be applied along with other ones.
+    pub fn rewrite(&self, data: &Data) -> Result<Rewrite, DataError> {
+        let mut contents = serde_json::to_vec_pretty(data)?;
+        contents.push(b'\n');
+
+        Ok(Rewrite {
+            path: self.path.clone(),
+            offset: 0,
+            tail: contents,
+        })
+    }
+
+    /// See `wal::recover`.
+    pub fn recover(&self) -> Result<bool, DataError> {
+        Ok(!wal::recover(&self.path)?.is_empty())
+    }
+
+    pub fn is_pending(&self) -> bool {
+        wal::is_pending(&self.path)
+    }
+}
+
+//==============================================================================
+//
+//                            Serde Implementations
+//
+//==============================================================================
+
+impl Serialize for Goals {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+
+        for (period, duration) in &self.0 {
+            map.serialize_entry(&period.to_string(), &format_duration(*duration))?;
+        }
+
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Goals {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Goals, D::Error> {
+        IndexMap::<String, String>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(period, duration)| {
+                Ok((
+                    GoalPeriod::from_str(&period).map_err(D::Error::custom)?,
+                    parse_duration(&duration).map_err(D::Error::custom)?,
+                ))
+            })
+            .collect::<Result<_, _>>()
+            .map(Goals)
+    }
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Money, D::Error> {
+        Money::from_str(&String::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
+
+fn is_zero(number: &u32) -> bool {
+    *number == 0
+}
+
+impl Serialize for PlannedBreaks {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+
+        for (start, duration) in &self.0 {
+            map.serialize_entry(
+                &start.format("%H:%M").to_string(),
+                &format_duration(*duration),
+            )?;
+        }
+
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for PlannedBreaks {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<PlannedBreaks, D::Error> {
+        let mut breaks = PlannedBreaks::default();
+
+        for (start, duration) in IndexMap::<String, String>::deserialize(deserializer)? {
+            breaks.set(
+                NaiveTime::parse_from_str(&start, "%H:%M").map_err(D::Error::custom)?,
+                parse_duration(&duration).map_err(D::Error::custom)?,
+            );
+        }
+
+        Ok(breaks)
+    }
+}
+
+//==============================================================================
+//
+//                         Conversions Between Error Types
+//
+//==============================================================================
+
+impl From<io::Error> for DataError {
+    fn from(error: io::Error) -> DataError {
+        DataError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for DataError {
+    fn from(error: serde_json::Error) -> DataError {
+        DataError::Json(error)
+    }
+}
+
+//==============================================================================
+//
+//                           Display Implementations
+//
+//==============================================================================
+
+impl Display for DataError {
+    fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FormatError> {
+        match self {
+            DataError::Io(error) => write!(f, "{}", error),
+            DataError::Json(error) => write!(f, "invalid data file: {}", error),
+        }
+    }
+}
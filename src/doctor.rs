@@ -0,0 +1,749 @@
+use chrono::Duration;
+
+use core::str::FromStr;
+
+use super::history::entry_ids;
+use super::history::HistoryEntry;
+use super::history::HistoryLine;
+use super::history::Sequence;
+use super::parse_duration;
+use super::DateTime;
+use super::GoalPeriod;
+
+use std::collections::HashMap;
+
+//==============================================================================
+//
+//                              Type Definitions
+//
+//==============================================================================
+
+/// A problem found in the history.log or data.json file.
+pub struct Finding {
+    pub file: &'static str,
+    /// Line number, starting at 1.
+    pub line: usize,
+    pub problem: String,
+    pub fix: String,
+}
+
+/// A session with the line numbers of its entries.
+struct NumberedSession<'a> {
+    mnemonic: &'a str,
+    start: DateTime,
+    stop: Option<DateTime>,
+    line: usize,
+}
+
+const HISTORY: &str = "history.log";
+const DATA: &str = "data.json";
+
+//==============================================================================
+//
+//                                 Data File
+//
+//==============================================================================
+
+/// Checks the contents of the data.json file. Returns the findings and the mnemonics of the
+/// tasks, unless the file can't be parsed at all.
+pub fn check_data(text: &str) -> (Vec<Finding>, Option<Vec<String>>) {
+    let mut findings = Vec::new();
+
+    if text.trim().is_empty() {
+        return (findings, Some(Vec::new()));
+    }
+
+    let root: serde_json::Value = match serde_json::from_str(text) {
+        Ok(root) => root,
+        Err(error) => {
+            findings.push(Finding {
+                file: DATA,
+                line: error.line(),
+                problem: format!("invalid JSON: {}", error),
+                fix: String::from("correct the file by hand, or restore it from a backup"),
+            });
+
+            return (findings, None);
+        }
+    };
+
+    let tasks = root.get("tasks").and_then(serde_json::Value::as_object);
+
+    for (mnemonic, task) in tasks.into_iter().flatten() {
+        if let Some(goals) = task.get("goals") {
+            let owner = format!("\"{}\": {{", mnemonic);
+            check_goals(
+                text,
+                &owner,
+                &format!("task {}", mnemonic),
+                goals,
+                &mut findings,
+            );
+        }
+    }
+
+    if let Some(goals) = root.get("general").and_then(|general| general.get("goals")) {
+        check_goals(
+            text,
+            "\"general\": {",
+            "work in general",
+            goals,
+            &mut findings,
+        );
+    }
+
+    let mnemonics = tasks.map_or_else(Vec::new, |tasks| tasks.keys().cloned().collect());
+    findings.sort_by_key(|finding| finding.line);
+    (findings, Some(mnemonics))
+}
+
+/// Goals must be `"period": "duration"` pairs, e.g. `"week": "40h"`.
+fn check_goals(
+    text: &str,
+    owner: &str,
+    description: &str,
+    goals: &serde_json::Value,
+    findings: &mut Vec<Finding>,
+) {
+    let goals = match goals.as_object() {
+        Some(goals) => goals,
+        None => {
+            findings.push(Finding {
+                file: DATA,
+                line: line_of(text, &[owner, "\"goals\":"]),
+                problem: format!("the goals of the {} aren't an object", description),
+                fix: String::from(
+                    "replace them by \"period\": \"duration\" pairs, e.g. \"week\": \"40h\"",
+                ),
+            });
+
+            return;
+        }
+    };
+
+    for (period, duration) in goals {
+        let key = format!("\"{}\":", period);
+        let line = line_of(text, &[owner, "\"goals\":", &key]);
+
+        if GoalPeriod::from_str(period).is_err() {
+            findings.push(Finding {
+                file: DATA,
+                line,
+                problem: format!("invalid goal period of the {}: {}", description, period),
+                fix: String::from("use day, week, month or a weekday, e.g. monday"),
+            });
+        }
+
+        if duration
+            .as_str()
+            .is_none_or(|duration| parse_duration(duration).is_err())
+        {
+            findings.push(Finding {
+                file: DATA,
+                line,
+                problem: format!("invalid goal duration of the {}: {}", description, duration),
+                fix: String::from("write the duration as a string, e.g. \"8h\" or \"7h 30m\""),
+            });
+        }
+    }
+}
+
+/// The number of the line containing the last pattern, searching for each pattern after the
+/// line of the previous one. The data.json file is pretty-printed, so keys are on their own
+/// lines.
+fn line_of(text: &str, patterns: &[&str]) -> usize {
+    let mut line = 0;
+
+    for pattern in patterns {
+        match text
+            .lines()
+            .enumerate()
+            .skip(line)
+            .find(|(_, text)| text.contains(pattern))
+        {
+            Some((index, _)) => line = index,
+            None => break,
+        }
+    }
+
+    line + 1
+}
+
+//==============================================================================
+//
+//                                History File
+//
+//==============================================================================
+
+/// Checks the contents of the history.log file. Tasks are only checked for existence if the
+/// mnemonics are known. Entries marked as uncertain are reported once they're older than
+/// `max_age`. Blank lines are skipped, but counted, so the line numbers match the file.
+pub fn check_history(
+    text: &str,
+    mnemonics: Option<&[String]>,
+    max_age: Duration,
+    now: DateTime,
+) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut lines = Vec::new();
+    let mut numbers = Vec::new();
+    let mut offset = 0;
+
+    for (index, line) in text.split_inclusive('\n').enumerate() {
+        let trimmed = line.trim();
+
+        if !trimmed.is_empty() {
+            match HistoryEntry::from_str(trimmed) {
+                Ok(entry) => {
+                    lines.push(HistoryLine {
+                        offset,
+                        text: line.trim_end_matches('\n').to_string(),
+                        entry,
+                    });
+                    numbers.push(index + 1);
+                }
+                Err(error) => findings.push(Finding {
+                    file: HISTORY,
+                    line: index + 1,
+                    problem: format!("unreadable entry: {}", error),
+                    fix: String::from("correct the line or delete it"),
+                }),
+            }
+        }
+
+        offset += line.len() as u64;
+    }
+
+    let entries: Vec<HistoryEntry> = lines.iter().map(|line| line.entry.clone()).collect();
+
+    check_order(&entries, &numbers, &mut findings);
+    check_matching(&entries, &numbers, &mut findings);
+
+    if let Some(mnemonics) = mnemonics {
+        check_tasks(&entries, &numbers, mnemonics, &mut findings);
+    }
+
+    let sessions = numbered_sessions(&entries, &numbers);
+    check_overlaps(&sessions, &mut findings);
+    check_presence(&entries, &sessions, now, &mut findings);
+    check_uncertain(&lines, &numbers, max_age, now, &mut findings);
+
+    findings.sort_by_key(|finding| finding.line);
+    findings
+}
+
+/// Entries must be in chronological order. Commits have no date/time of their own.
+fn check_order(entries: &[HistoryEntry], numbers: &[usize], findings: &mut Vec<Finding>) {
+    let mut latest: Option<(DateTime, usize)> = None;
+
+    for (index, entry) in entries.iter().enumerate() {
+        let datetime = match entry.datetime() {
+            Some(datetime) => datetime,
+            None => continue,
+        };
+
+        match latest {
+            Some((previous, line)) if datetime < previous => {
+                let place = entries
+                    .iter()
+                    .zip(numbers)
+                    .find(|(other, _)| other.datetime().is_some_and(|other| other > datetime))
+                    .map_or(numbers[index], |(_, &line)| line);
+
+                findings.push(Finding {
+                    file: HISTORY,
+                    line: numbers[index],
+                    problem: format!("entry is earlier than the one at line {}", line),
+                    fix: format!(
+                        "move it right before line {}, or amend its date/time",
+                        place
+                    ),
+                });
+            }
+            _ => latest = Some((datetime, numbers[index])),
+        }
+    }
+}
+
+/// `enter`/`exit`, `pause`/`resume` and `start`/`stop` entries must alternate. The last period
+/// of each sequence may still be open.
+fn check_matching(entries: &[HistoryEntry], numbers: &[usize], findings: &mut Vec<Finding>) {
+    let mut mnemonics: Vec<&str> = entries.iter().filter_map(HistoryEntry::mnemonic).collect();
+    mnemonics.sort_unstable();
+    mnemonics.dedup();
+
+    let sequences = vec![Sequence::Presence, Sequence::Break]
+        .into_iter()
+        .chain(mnemonics.into_iter().map(Sequence::Task));
+
+    for sequence in sequences {
+        for index in sequence.unmatched(entries) {
+            let entry = &entries[index];
+            let (_, counterpart) = entry_kinds(entry);
+
+            let (problem, fix) = if sequence.opens(entry) == Some(true) {
+                (
+                    format!("{} while the previous one is still open", describe(entry)),
+                    format!(
+                        "add the missing {} entry before this line, or delete this line",
+                        counterpart
+                    ),
+                )
+            } else {
+                (
+                    format!("{} without a matching {}", describe(entry), counterpart),
+                    format!(
+                        "add the missing {} entry before this line, or delete this line",
+                        counterpart
+                    ),
+                )
+            };
+
+            findings.push(Finding {
+                file: HISTORY,
+                line: numbers[index],
+                problem,
+                fix,
+            });
+        }
+    }
+}
+
+/// Tasks referred to by the history must exist in the data file. Each task is reported once,
+/// at its first entry.
+fn check_tasks(
+    entries: &[HistoryEntry],
+    numbers: &[usize],
+    mnemonics: &[String],
+    findings: &mut Vec<Finding>,
+) {
+    let mut unknown: Vec<(&str, usize, usize)> = Vec::new();
+
+    for (index, entry) in entries.iter().enumerate() {
+        let mnemonic = match entry.mnemonic() {
+            Some(mnemonic) if !mnemonics.iter().any(|other| other == mnemonic) => mnemonic,
+            _ => continue,
+        };
+
+        match unknown.iter_mut().find(|(other, _, _)| *other == mnemonic) {
+            Some((_, _, count)) => *count += 1,
+            None => unknown.push((mnemonic, numbers[index], 1)),
+        }
+    }
+
+    for (mnemonic, line, count) in unknown {
+        findings.push(Finding {
+            file: HISTORY,
+            line,
+            problem: format!(
+                "unknown task: {} ({} {})",
+                mnemonic,
+                count,
+                if count == 1 { "entry" } else { "entries" }
+            ),
+            fix: format!("create the task with: timelog create {}", mnemonic),
+        });
+    }
+}
+
+/// Groups the sessions like `history::sessions`, keeping the line of their start.
+fn numbered_sessions<'a>(
+    entries: &'a [HistoryEntry],
+    numbers: &[usize],
+) -> Vec<NumberedSession<'a>> {
+    let mut sessions: Vec<NumberedSession> = Vec::new();
+
+    for (index, entry) in entries.iter().enumerate() {
+        match entry {
+            HistoryEntry::Start {
+                mnemonic, datetime, ..
+            } => sessions.push(NumberedSession {
+                mnemonic,
+                start: datetime.datetime,
+                stop: None,
+                line: numbers[index],
+            }),
+            HistoryEntry::Stop {
+                mnemonic, datetime, ..
+            } => {
+                if let Some(session) = sessions
+                    .iter_mut()
+                    .rev()
+                    .find(|session| session.mnemonic == mnemonic)
+                {
+                    if session.stop.is_none() {
+                        session.stop = Some(datetime.datetime);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    sessions
+}
+
+/// Sessions of the same task can't overlap, or the time would be counted twice. Open sessions
+/// only conflict with the ones starting after them.
+fn check_overlaps(sessions: &[NumberedSession], findings: &mut Vec<Finding>) {
+    let mut by_task: HashMap<&str, Vec<&NumberedSession>> = HashMap::new();
+
+    for session in sessions {
+        by_task.entry(session.mnemonic).or_default().push(session);
+    }
+
+    for sessions in by_task.values_mut() {
+        sessions.sort_by_key(|session| session.start);
+
+        for pair in sessions.windows(2) {
+            let (first, second) = (pair[0], pair[1]);
+
+            if first.stop.is_none_or(|stop| second.start < stop) {
+                findings.push(Finding {
+                    file: HISTORY,
+                    line: second.line,
+                    problem: format!(
+                        "session of {} overlaps the one starting at line {}",
+                        second.mnemonic, first.line
+                    ),
+                    fix: String::from("amend the date/time of the entries so they don't overlap"),
+                });
+            }
+        }
+    }
+}
+
+/// Sessions must be within periods at the workplace, unless the history has no `enter` or
+/// `exit` entries at all. Open periods last until `now`.
+fn check_presence(
+    entries: &[HistoryEntry],
+    sessions: &[NumberedSession],
+    now: DateTime,
+    findings: &mut Vec<Finding>,
+) {
+    let mut presences: Vec<(DateTime, Option<DateTime>)> = Vec::new();
+
+    for entry in entries {
+        match entry {
+            HistoryEntry::Enter { datetime } => presences.push((datetime.datetime, None)),
+            HistoryEntry::Exit { datetime } => {
+                if let Some((_, exit @ None)) = presences.last_mut() {
+                    *exit = Some(datetime.datetime);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if presences.is_empty() {
+        return;
+    }
+
+    for session in sessions {
+        let stop = session.stop.unwrap_or(now);
+
+        let is_inside = presences
+            .iter()
+            .any(|&(enter, exit)| enter <= session.start && exit.is_none_or(|exit| stop <= exit));
+
+        if !is_inside {
+            findings.push(Finding {
+                file: HISTORY,
+                line: session.line,
+                problem: format!(
+                    "session of {} from {} to {} is outside the presence time",
+                    session.mnemonic,
+                    session.start.format("%Y-%m-%d %H:%M"),
+                    match session.stop {
+                        Some(stop) => stop.format("%Y-%m-%d %H:%M").to_string(),
+                        None => String::from("now"),
+                    }
+                ),
+                fix: String::from(
+                    "add enter/exit entries around it, or amend the date/time of the session",
+                ),
+            });
+        }
+    }
+}
+
+/// Entries marked as uncertain should get an estimated date/time eventually.
+fn check_uncertain(
+    lines: &[HistoryLine],
+    numbers: &[usize],
+    max_age: Duration,
+    now: DateTime,
+    findings: &mut Vec<Finding>,
+) {
+    let ids = entry_ids(lines);
+
+    for (index, line) in lines.iter().enumerate() {
+        if let Some(datetime) = line.entry.forgettable_datetime() {
+            if datetime.forgotten && now - datetime.datetime > max_age {
+                findings.push(Finding {
+                    file: HISTORY,
+                    line: numbers[index],
+                    problem: format!("uncertain date/time older than {} days", max_age.num_days()),
+                    fix: format!(
+                        "estimate it with: timelog amend {} --datetime=<date/time>",
+                        ids[index]
+                    ),
+                });
+            }
+        }
+    }
+}
+
+/// The name of the entry type and of the one that matches it, e.g. `start` and `stop`.
+fn entry_kinds(entry: &HistoryEntry) -> (&'static str, &'static str) {
+    match entry {
+        HistoryEntry::Enter { .. } => ("enter", "exit"),
+        HistoryEntry::Exit { .. } => ("exit", "enter"),
+        HistoryEntry::Pause { .. } => ("pause", "resume"),
+        HistoryEntry::Resume { .. } => ("resume", "pause"),
+        HistoryEntry::Start { .. } => ("start", "stop"),
+        HistoryEntry::Stop { .. } => ("stop", "start"),
+        HistoryEntry::Commit { .. } => ("commit", "stop"),
+    }
+}
+
+/// The entry type, followed by the task for task entries, e.g. `stop of project/feature`.
+fn describe(entry: &HistoryEntry) -> String {
+    let (kind, _) = entry_kinds(entry);
+
+    match entry.mnemonic() {
+        Some(mnemonic) => format!("{} of {}", kind, mnemonic),
+        None => kind.to_string(),
+    }
+}
+
+//==============================================================================
+//
+//                                   Tests
+//
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::use_berlin_time;
+
+    fn at(datetime: &str) -> DateTime {
+        HistoryEntry::from_str(&format!("enter {}", datetime))
+            .ok()
+            .and_then(|entry| entry.datetime())
+            .unwrap()
+    }
+
+    /// The line and problem of each finding of the history.
+    fn history_problems(lines: &[&str], mnemonics: &[&str]) -> Vec<(usize, String)> {
+        let text: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+        let mnemonics: Vec<String> = mnemonics.iter().map(|m| m.to_string()).collect();
+
+        check_history(
+            &text,
+            Some(&mnemonics),
+            Duration::days(7),
+            at("2026-06-30 12:00 +02:00"),
+        )
+        .into_iter()
+        .map(|finding| (finding.line, finding.problem))
+        .collect()
+    }
+
+    fn problem(line: usize, problem: &str) -> (usize, String) {
+        (line, problem.to_string())
+    }
+
+    #[test]
+    fn a_consistent_history_has_no_findings() {
+        use_berlin_time();
+
+        assert!(history_problems(
+            &[
+                "enter 2026-06-10 8:00 +02:00",
+                "start dev 2026-06-10 9:00 +02:00",
+                "",
+                "pause 2026-06-10 12:00 +02:00",
+                "stop dev 2026-06-10 12:00 +02:00",
+                "commit dev",
+                "resume 2026-06-10 13:00 +02:00",
+                "exit 2026-06-10 17:00 +02:00",
+                "enter 2026-06-29 8:00 +02:00?",
+            ],
+            &["dev"],
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn entries_out_of_order_are_found() {
+        use_berlin_time();
+
+        let findings = check_history(
+            "enter 2026-06-10 8:00 +02:00\n\
+             start dev 2026-06-10 10:00 +02:00\n\
+             stop dev 2026-06-10 9:00 +02:00\n\
+             exit 2026-06-10 17:00 +02:00\n",
+            None,
+            Duration::days(7),
+            at("2026-06-30 12:00 +02:00"),
+        );
+
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].line, 3);
+        assert_eq!(
+            findings[0].problem,
+            "entry is earlier than the one at line 2"
+        );
+        assert_eq!(
+            findings[0].fix,
+            "move it right before line 2, or amend its date/time"
+        );
+    }
+
+    #[test]
+    fn unmatched_and_overlapping_entries_are_found() {
+        use_berlin_time();
+
+        assert_eq!(
+            history_problems(
+                &[
+                    "start dev 2026-06-10 9:00 +02:00",
+                    "start dev 2026-06-10 10:00 +02:00",
+                    "stop dev 2026-06-10 11:00 +02:00",
+                    "stop dev 2026-06-10 12:00 +02:00",
+                    "resume 2026-06-10 13:00 +02:00",
+                ],
+                &["dev"],
+            ),
+            vec![
+                problem(2, "start of dev while the previous one is still open"),
+                problem(2, "session of dev overlaps the one starting at line 1"),
+                problem(4, "stop of dev without a matching start"),
+                problem(5, "resume without a matching pause"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_tasks_are_found_once() {
+        use_berlin_time();
+
+        assert_eq!(
+            history_problems(
+                &[
+                    "start dev 2026-06-10 9:00 +02:00",
+                    "stop dev 2026-06-10 10:00 +02:00",
+                    "start ops 2026-06-10 10:00 +02:00",
+                    "stop ops 2026-06-10 11:00 +02:00",
+                ],
+                &["dev"],
+            ),
+            vec![problem(3, "unknown task: ops (2 entries)")]
+        );
+    }
+
+    #[test]
+    fn work_outside_the_presence_is_found() {
+        use_berlin_time();
+
+        assert_eq!(
+            history_problems(
+                &[
+                    "enter 2026-06-10 9:00 +02:00",
+                    "start dev 2026-06-10 11:00 +02:00",
+                    "exit 2026-06-10 12:00 +02:00",
+                    "stop dev 2026-06-10 13:00 +02:00",
+                    "start dev 2026-06-11 9:00 +02:00",
+                ],
+                &["dev"],
+            ),
+            vec![
+                problem(
+                    2,
+                    "session of dev from 2026-06-10 11:00 to 2026-06-10 13:00 is outside the \
+                     presence time"
+                ),
+                problem(
+                    5,
+                    "session of dev from 2026-06-11 09:00 to now is outside the presence time"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn old_uncertain_entries_are_found() {
+        use_berlin_time();
+
+        let findings = check_history(
+            "enter 2026-06-10 8:00 +02:00?\nexit 2026-06-29 17:00 +02:00?\n",
+            None,
+            Duration::days(7),
+            at("2026-06-30 12:00 +02:00"),
+        );
+
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].line, 1);
+        assert_eq!(findings[0].problem, "uncertain date/time older than 7 days");
+        assert!(findings[0]
+            .fix
+            .starts_with("estimate it with: timelog amend "));
+    }
+
+    #[test]
+    fn unreadable_entries_are_found() {
+        use_berlin_time();
+
+        assert_eq!(
+            history_problems(
+                &["enter 2026-06-10 8:00 +02:00", "begin 2026-06-10 9:00"],
+                &[]
+            )
+            .into_iter()
+            .map(|(line, _)| line)
+            .collect::<Vec<usize>>(),
+            vec![2]
+        );
+    }
+
+    #[test]
+    fn malformed_goals_are_found() {
+        let text = r#"{
+  "tasks": {
+    "dev": {
+      "active": true,
+      "title": "Dev",
+      "goals": {
+        "week": "40h",
+        "fortnight": "80h",
+        "day": 8
+      }
+    }
+  },
+  "general": {
+    "goals": []
+  }
+}"#;
+
+        let (findings, mnemonics) = check_data(text);
+
+        assert_eq!(mnemonics, Some(vec![String::from("dev")]));
+        assert_eq!(
+            findings
+                .into_iter()
+                .map(|finding| (finding.line, finding.problem))
+                .collect::<Vec<_>>(),
+            vec![
+                problem(8, "invalid goal period of the task dev: fortnight"),
+                problem(9, "invalid goal duration of the task dev: 8"),
+                problem(14, "the goals of the work in general aren't an object"),
+            ]
+        );
+
+        let (findings, mnemonics) = check_data("{\n  \"tasks\": {\n}\n");
+        assert!(mnemonics.is_none());
+        assert!(findings[0].problem.starts_with("invalid JSON"));
+    }
+}
@@ -0,0 +1,4596 @@
+use ansi_term::Color;
+
+use chrono::prelude::*;
+use chrono::Duration;
+
+use super::config;
+use super::config::Config;
+use super::config::ConfigError;
+use super::data::Absence;
+use super::data::Data;
+use super::data::DataError;
+use super::data::DataFile;
+use super::data::Goals;
+use super::data::Interruption;
+use super::data::Task;
+use super::doctor;
+use super::format_duration;
+use super::history::breaks;
+use super::history::chronological_position;
+use super::history::entry_ids;
+use super::history::sessions;
+use super::history::HistoryEntry;
+use super::history::HistoryError;
+use super::history::HistoryFile;
+use super::history::HistoryLine;
+use super::history::Insertion;
+use super::history::Replacement;
+use super::history::Sequence;
+use super::history::Session;
+use super::ical;
+use super::ical::IcalParseError;
+use super::index::Totals;
+use super::input::parse_datetime;
+use super::invoice;
+use super::invoice::Invoice;
+use super::is_valid_mnemonic;
+use super::is_within;
+use super::journal::data_changes;
+use super::journal::general_fields;
+use super::journal::Change;
+use super::journal::Journal;
+use super::journal::JournalError;
+use super::org;
+use super::org::OrgParseError;
+use super::profile::is_valid_profile_name;
+use super::profile::Profiles;
+use super::profile::DEFAULT_PROFILE;
+use super::prompt;
+use super::repair::unified_diff;
+use super::report;
+use super::report::break_time;
+use super::report::forecast;
+use super::report::general_goal_for;
+use super::report::goal_for;
+use super::report::workday;
+use super::report::BalanceWeek;
+use super::report::Period;
+use super::wal;
+use super::wal::Rewrite;
+use super::AbsenceKind;
+use super::AmendAction;
+use super::Command;
+use super::ConfigAction;
+use super::DateTime;
+use super::DeleteMode;
+use super::EntrySelector;
+use super::ExchangeFormat;
+use super::ForgetableDateTime;
+use super::GoalAction;
+use super::GoalPeriod;
+use super::InvoiceFormat;
+use super::Money;
+use super::ProfileAction;
+use super::ProfileSelection;
+use super::RateAction;
+use super::RateTarget;
+use super::RoundingAction;
+use super::SessionFilter;
+
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::fmt::Error as FormatError;
+use std::fmt::Formatter;
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::fs::TryLockError;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration as StdDuration;
+use std::time::Instant;
+use std::time::SystemTime;
+
+//==============================================================================
+//
+//                              Type Definitions
+//
+//==============================================================================
+
+/// The files a command operates on.
+/// Changes should be made through `Files` methods, so they're recorded in the undo journal.
+pub struct Files {
+    pub history: HistoryFile,
+    pub data: DataFile,
+    pub journal: Journal,
+    lock: PathBuf,
+    /// The lock held while a command is executed, and whether it's exclusive.
+    held: RefCell<Option<(File, bool)>>,
+}
+
+/// A replacement of history lines: its offset, then the previous and the current lines.
+/// See `history::Replacement`.
+pub type LineReplacement = (u64, Vec<String>, Vec<String>);
+
+/// How long to wait for another instance to release the lock on the files.
+const LOCK_TIMEOUT: StdDuration = StdDuration::from_secs(5);
+
+//==============================================================================
+//
+//                                Error Types
+//
+//==============================================================================
+
+pub enum ExecutionError {
+    Io(io::Error),
+    History(HistoryError),
+    Data(DataError),
+    Journal(JournalError),
+    Config(ConfigError),
+    OrgParseError(OrgParseError),
+    IcalParseError(IcalParseError),
+    InvalidMnemonic(String),
+    UnknownTask(String),
+    UnknownClient(String),
+    InvalidProfileName(String),
+    UnknownProfile(String),
+    ProfileAlreadyExists(String),
+    TaskAlreadyExists(String),
+    TaskHasHistory(String),
+    TaskIsOpen(String),
+    NoOpenTask,
+    UnknownEntry(String),
+    NotApplicable(&'static str),
+    UnmatchedEntry(String),
+    ConcurrentChange,
+    Locked(PathBuf),
+    UndoConflict(String),
+}
+
+//==============================================================================
+//
+//                              Command Execution
+//
+//==============================================================================
+
+impl Files {
+    /// The history.log and data.json files inside the given directory.
+    pub fn new<P: AsRef<Path>>(dir: P) -> Files {
+        Files {
+            history: HistoryFile::new(dir.as_ref().join("history.log")),
+            data: DataFile::new(dir.as_ref().join("data.json")),
+            journal: Journal::new(dir.as_ref().join("undo.json")),
+            lock: dir.as_ref().join("timelog.lock"),
+            held: RefCell::new(None),
+        }
+    }
+
+    /// Locks the files against other instances: shared for reading them, exclusive for
+    /// changing them. If another instance holds a conflicting lock, waits up to `LOCK_TIMEOUT`
+    /// for it to be released. The lock lasts until the returned file is dropped.
+    pub fn lock(&self, exclusive: bool) -> Result<File, ExecutionError> {
+        self.lock_within(exclusive, LOCK_TIMEOUT)
+    }
+
+    fn lock_within(&self, exclusive: bool, timeout: StdDuration) -> Result<File, ExecutionError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.lock)?;
+
+        let start = Instant::now();
+
+        loop {
+            let result = if exclusive {
+                file.try_lock()
+            } else {
+                file.try_lock_shared()
+            };
+
+            match result {
+                Ok(()) => return Ok(file),
+                Err(TryLockError::WouldBlock) => {
+                    if start.elapsed() >= timeout {
+                        return Err(ExecutionError::Locked(self.lock.clone()));
+                    }
+
+                    thread::sleep(StdDuration::from_millis(50));
+                }
+                Err(TryLockError::Error(error)) => return Err(error.into()),
+            }
+        }
+    }
+
+    /// Locks the files until `release` is called. See `lock`.
+    pub fn hold(&self, exclusive: bool) -> Result<(), ExecutionError> {
+        let lock = self.lock(exclusive)?;
+        *self.held.borrow_mut() = Some((lock, exclusive));
+        Ok(())
+    }
+
+    pub fn release(&self) {
+        self.held.borrow_mut().take();
+    }
+
+    /// Asks the user to type a line of text. See `unlocked`.
+    pub fn ask(&self, question: &str) -> Result<String, ExecutionError> {
+        self.unlocked(|| prompt::ask(question))
+    }
+
+    /// Asks the user a yes/no question. See `unlocked`.
+    pub fn confirm(&self, question: &str) -> Result<bool, ExecutionError> {
+        self.unlocked(|| prompt::confirm(question))
+    }
+
+    /// Releases the held lock while waiting for the user, so other instances, such as a shell
+    /// prompt displaying the status, aren't kept waiting on the answer. Since what was read
+    /// from the files may be outdated afterwards, fails if they were changed meanwhile.
+    fn unlocked<T, F>(&self, prompt: F) -> Result<T, ExecutionError>
+    where
+        F: FnOnce() -> io::Result<T>,
+    {
+        let (lock, exclusive) = match self.held.borrow_mut().take() {
+            Some(held) => held,
+            None => return Ok(prompt()?),
+        };
+
+        let before = self.versions()?;
+        drop(lock);
+
+        let answer = prompt();
+        self.hold(exclusive)?;
+        let answer = answer?;
+
+        if self.versions()? != before || self.history.is_pending() || self.data.is_pending() {
+            return Err(ExecutionError::ConcurrentChange);
+        }
+
+        Ok(answer)
+    }
+
+    /// The size and the modification time of the history and data files, which change
+    /// whenever they're written.
+    fn versions(&self) -> Result<Vec<Option<(u64, SystemTime)>>, ExecutionError> {
+        let mut versions = Vec::new();
+
+        for path in &[self.history.path(), self.data.path()] {
+            versions.push(match fs::metadata(path) {
+                Ok(metadata) => Some((metadata.len(), metadata.modified()?)),
+                Err(ref error) if error.kind() == io::ErrorKind::NotFound => None,
+                Err(error) => return Err(error.into()),
+            });
+        }
+
+        Ok(versions)
+    }
+
+    /// Completes the changes to the history, data and undo files interrupted by a crash,
+    /// holding an exclusive lock meanwhile. Returns whether there were any.
+    pub fn recover(&self) -> Result<bool, ExecutionError> {
+        if !self.history.is_pending() && !self.data.is_pending() && !self.journal.is_pending() {
+            return Ok(false);
+        }
+
+        let _lock = self.lock(true)?;
+        let history = self.history.recover()?;
+        let data = self.data.recover()?;
+        let journal = self.journal.recover()?;
+
+        Ok(history || data || journal)
+    }
+
+    /// See `HistoryFile::insert`. The change is recorded in the undo journal as part of the
+    /// same write, as are the changes of the other methods.
+    pub fn insert(&self, entry: &HistoryEntry) -> Result<Insertion, ExecutionError> {
+        self.history
+            .insert_along(entry, |insertion| self.record_insertion(insertion, entry))
+    }
+
+    /// See `HistoryFile::insert_at`.
+    pub fn insert_at(
+        &self,
+        offset: u64,
+        entry: &HistoryEntry,
+    ) -> Result<Insertion, ExecutionError> {
+        self.history.insert_at_along(offset, entry, |insertion| {
+            self.record_insertion(insertion, entry)
+        })
+    }
+
+    /// Saves the data file, recording the tasks that changed since it was loaded.
+    pub fn save(&self, data: &Data) -> Result<(), ExecutionError> {
+        let changes = data_changes(&self.data.load()?, data);
+
+        wal::rewrite_tails(&[self.data.rewrite(data)?, self.journal.rewrite(changes)?])?;
+        Ok(())
+    }
+
+    /// See `HistoryFile::replace`. Each replacement is given by its offset, the previous and
+    /// the current version of its lines.
+    pub fn replace(&self, replacements: Vec<LineReplacement>) -> Result<(), ExecutionError> {
+        self.replace_along(replacements, None)
+    }
+
+    /// Replaces history lines and saves the data file at once, so a crash can't leave one of
+    /// them changed without the other. See `replace` and `save`.
+    pub fn replace_and_save(
+        &self,
+        replacements: Vec<LineReplacement>,
+        data: &Data,
+    ) -> Result<(), ExecutionError> {
+        self.replace_along(replacements, Some(data))
+    }
+
+    fn replace_along(
+        &self,
+        replacements: Vec<LineReplacement>,
+        data: Option<&Data>,
+    ) -> Result<(), ExecutionError> {
+        let mut others = Vec::new();
+        let mut changes: Vec<Change> = replacements
+            .iter()
+            .map(|(offset, previous, current)| Change::HistoryReplace {
+                offset: *offset,
+                previous: previous.clone(),
+                current: current.clone(),
+            })
+            .collect();
+
+        if let Some(data) = data {
+            others.push(self.data.rewrite(data)?);
+            changes.extend(data_changes(&self.data.load()?, data));
+        }
+
+        others.push(self.journal.rewrite(changes)?);
+
+        let replacements: Vec<Replacement> = replacements
+            .iter()
+            .map(|(offset, previous, current)| Replacement {
+                offset: *offset,
+                previous,
+                current,
+            })
+            .collect();
+
+        if !self.history.replace_along(&replacements, others)? {
+            return Err(ExecutionError::ConcurrentChange);
+        }
+
+        Ok(())
+    }
+
+    /// The rewrite of the undo journal recording an insertion.
+    fn record_insertion(
+        &self,
+        insertion: &Insertion,
+        entry: &HistoryEntry,
+    ) -> Result<Vec<Rewrite>, ExecutionError> {
+        let change = Change::HistoryInsert {
+            offset: insertion.offset,
+            line: entry.to_string(),
+        };
+
+        Ok(vec![self.journal.rewrite(Some(change))?])
+    }
+}
+
+impl<'a> Command<'a> {
+    /// Executes the command on the files of the current profile. Only the commands about the
+    /// configuration and the profiles can be used when the current profile doesn't exist.
+    pub fn execute(self, files: &Files, profiles: &Profiles) -> Result<(), ExecutionError> {
+        let is_setup = matches!(self, Command::Config { .. } | Command::Profile { .. });
+
+        if !is_setup && !profiles.exists(profiles.current()) {
+            return Err(ExecutionError::UnknownProfile(
+                profiles.current().to_string(),
+            ));
+        }
+
+        if !is_setup && files.recover()? {
+            println!("Completed the changes interrupted by the last crash.");
+        }
+
+        if !is_setup {
+            files.hold(!self.is_read_only())?;
+        }
+
+        let result = match self {
+            Command::Enter { datetime } => enter(files, datetime),
+            Command::Exit { datetime } => exit(files, datetime),
+            Command::Pause { datetime } => pause(files, datetime),
+            Command::Resume { datetime } => resume(files, datetime),
+            Command::Break { start, duration } => plan_break(files, start, duration),
+            Command::Breaks => planned_breaks(files),
+            Command::Absence { date, absence } => add_or_erase_absence(files, date, absence),
+            Command::Absences => absences(files),
+            Command::ImportHolidays { path } => import_holidays(files, path),
+            Command::Create { mnemonic, code } => create(files, mnemonic, code),
+            Command::Edit { mnemonic, code } => edit(files, mnemonic, code),
+            Command::Start {
+                mnemonic,
+                datetime,
+                note,
+            } => start(files, mnemonic, datetime, note),
+            Command::Stop {
+                mnemonic,
+                datetime,
+                note,
+                commit: then_commit,
+            } => stop_or_end_interruption(files, mnemonic, datetime, note, then_commit),
+            Command::Interrupt {
+                mnemonic,
+                datetime,
+                note,
+            } => interrupt(files, mnemonic, datetime, note),
+            Command::Switch {
+                mnemonic,
+                datetime,
+                note,
+                commit: then_commit,
+            } => switch(files, mnemonic, datetime, note, then_commit),
+            Command::Commit {
+                mnemonic,
+                datetime,
+                note,
+            } => commit(files, mnemonic, datetime, note),
+            Command::Goal { action, mnemonic } => goal(files, action, mnemonic),
+            Command::Goals { mnemonic } => goals(files, mnemonic),
+            Command::Rounding { mnemonic, action } => rounding(files, mnemonic, action),
+            Command::Rate { target, action } => rate(files, target, action),
+            Command::Client { mnemonic, client } => assign_client(files, mnemonic, client),
+            Command::Invoice {
+                first,
+                last,
+                format,
+                client,
+                sessions,
+                preview,
+            } => write_invoice(files, first, last, format, client, sessions, preview),
+            Command::Export { format, mnemonic } => export(files, format, mnemonic),
+            Command::Import { format, path } => import(files, format, path),
+            Command::Undo => undo(files),
+            Command::Log { mnemonic, count } => log(files, mnemonic, count),
+            Command::Amend { entry, action } => amend(files, entry, action),
+            Command::Resolve { mnemonic, all } => resolve(files, mnemonic, all),
+            Command::Delete { mnemonic, mode } => delete(files, mnemonic, mode),
+            Command::Rename { old, new } => rename(files, old, new),
+            Command::Merge { from, into } => merge(files, from, into),
+            Command::Status { mnemonic } => status(files, mnemonic),
+            Command::Report {
+                period,
+                datetime,
+                mnemonic,
+                profiles: selection,
+            } => report(files, profiles, period, datetime, mnemonic, selection),
+            Command::Balance { since } => balance(files, since),
+            Command::Config { action } => configure(action),
+            Command::Doctor { days } => doctor(files, days),
+            Command::Repair => repair(files),
+            Command::Profile { action } => profile(profiles, action),
+        };
+
+        files.release();
+        result
+    }
+
+    /// Whether the command only reads the files, so other instances can read them meanwhile.
+    fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            Command::Breaks
+                | Command::Absences
+                | Command::Goals { .. }
+                | Command::Rounding {
+                    action: RoundingAction::Show,
+                    ..
+                }
+                | Command::Rate {
+                    action: RateAction::Show,
+                    ..
+                }
+                | Command::Invoice { preview: true, .. }
+                | Command::Export { .. }
+                | Command::Log { .. }
+                | Command::Status { .. }
+                | Command::Report { .. }
+                | Command::Balance { since: None }
+                | Command::Doctor { .. }
+        )
+    }
+}
+
+fn enter(files: &Files, datetime: ForgetableDateTime) -> Result<(), ExecutionError> {
+    let last = last_entry_before(files, datetime.datetime, |entry| {
+        matches!(
+            entry,
+            HistoryEntry::Enter { .. } | HistoryEntry::Exit { .. }
+        )
+    })?;
+
+    if let Some(last @ HistoryEntry::Enter { .. }) = last {
+        if !confirm_after(files, &last)? {
+            return Ok(());
+        }
+    }
+
+    files.insert(&HistoryEntry::Enter { datetime })?;
+    Ok(())
+}
+
+fn exit(files: &Files, datetime: ForgetableDateTime) -> Result<(), ExecutionError> {
+    let last = last_entry_before(files, datetime.datetime, |entry| {
+        matches!(
+            entry,
+            HistoryEntry::Enter { .. } | HistoryEntry::Exit { .. }
+        )
+    })?;
+
+    if let Some(last @ HistoryEntry::Exit { .. }) = last {
+        if !confirm_after(files, &last)? {
+            return Ok(());
+        }
+    }
+
+    files.insert(&HistoryEntry::Exit { datetime })?;
+    Ok(())
+}
+
+/// Starts a break, stopping the task being worked on at that moment, if any.
+fn pause(files: &Files, datetime: ForgetableDateTime) -> Result<(), ExecutionError> {
+    let last = last_entry_before(files, datetime.datetime, |entry| {
+        matches!(
+            entry,
+            HistoryEntry::Pause { .. } | HistoryEntry::Resume { .. }
+        )
+    })?;
+
+    if let Some(last @ HistoryEntry::Pause { .. }) = last {
+        if !confirm_after(files, &last)? {
+            return Ok(());
+        }
+    }
+
+    let mut data = files.data.load()?;
+    let lines = files.history.read()?;
+    let mut entries: Vec<HistoryEntry> = lines.iter().map(|line| line.entry.clone()).collect();
+
+    let stopped = open_task_at(&entries, datetime.datetime);
+
+    if let Some(mnemonic) = &stopped {
+        insert_entry(
+            &mut entries,
+            HistoryEntry::Stop {
+                mnemonic: mnemonic.clone(),
+                datetime,
+                note: None,
+            },
+        );
+    }
+
+    insert_entry(&mut entries, HistoryEntry::Pause { datetime });
+
+    // The task is recorded, so `resume` doesn't mistake a task stopped on purpose for it.
+    if data.general.paused != stopped {
+        data.general.paused = stopped.clone();
+        replace_entries_and_save(files, &lines, &entries, &data)?;
+    } else {
+        replace_entries(files, &lines, &entries)?;
+    }
+
+    if let Some(mnemonic) = stopped {
+        println!("Task stopped: {}", mnemonic);
+    }
+
+    Ok(())
+}
+
+/// Ends a break, starting the task stopped by it again, if any.
+fn resume(files: &Files, datetime: ForgetableDateTime) -> Result<(), ExecutionError> {
+    let mut data = files.data.load()?;
+    let lines = files.history.read()?;
+    let mut entries: Vec<HistoryEntry> = lines.iter().map(|line| line.entry.clone()).collect();
+
+    let last = entries[..chronological_position(&entries, datetime.datetime)]
+        .iter()
+        .rev()
+        .find(|entry| {
+            matches!(
+                entry,
+                HistoryEntry::Pause { .. } | HistoryEntry::Resume { .. }
+            )
+        });
+
+    let pause = match last {
+        Some(HistoryEntry::Pause { datetime: pause }) => *pause,
+        _ => {
+            println!("There's no break to be resumed.");
+            return Ok(());
+        }
+    };
+
+    let stopped = data.general.paused.take();
+
+    insert_entry(&mut entries, HistoryEntry::Resume { datetime });
+
+    if let Some(mnemonic) = &stopped {
+        insert_entry(
+            &mut entries,
+            HistoryEntry::Start {
+                mnemonic: mnemonic.clone(),
+                datetime,
+                note: None,
+            },
+        );
+    }
+
+    if stopped.is_some() {
+        replace_entries_and_save(files, &lines, &entries, &data)?;
+    } else {
+        replace_entries(files, &lines, &entries)?;
+    }
+
+    println!(
+        "Break duration: {}",
+        format_duration(datetime.datetime - pause.datetime)
+    );
+
+    if let Some(mnemonic) = stopped {
+        println!("Task started: {}", mnemonic);
+    }
+
+    Ok(())
+}
+
+fn plan_break(
+    files: &Files,
+    start: NaiveTime,
+    duration: Option<Duration>,
+) -> Result<(), ExecutionError> {
+    let mut data = files.data.load()?;
+
+    match duration {
+        Some(duration) => data.general.breaks.set(start, duration),
+        None => {
+            if !data.general.breaks.erase(start) {
+                println!("There's no break planned at {}.", start.format("%H:%M"));
+                return Ok(());
+            }
+        }
+    }
+
+    files.save(&data)?;
+    Ok(())
+}
+
+fn planned_breaks(files: &Files) -> Result<(), ExecutionError> {
+    let data = files.data.load()?;
+
+    if data.general.breaks.is_empty() {
+        println!("There are no planned breaks.");
+    }
+
+    for (start, duration) in data.general.breaks.iter() {
+        println!("{}: {}", start.format("%H:%M"), format_duration(*duration));
+    }
+
+    Ok(())
+}
+
+fn add_or_erase_absence(
+    files: &Files,
+    date: NaiveDate,
+    absence: Option<Absence>,
+) -> Result<(), ExecutionError> {
+    let mut data = files.data.load()?;
+
+    match absence {
+        Some(absence) => {
+            if let Err(other) = data.general.add_absence(absence) {
+                println!(
+                    "The absence overlaps with another one: {}",
+                    describe_absence(other)
+                );
+                return Ok(());
+            }
+        }
+        None => {
+            if data.general.erase_absence(date).is_none() {
+                println!("There's no absence on {}.", date.format("%Y-%m-%d"));
+                return Ok(());
+            }
+        }
+    }
+
+    files.save(&data)?;
+    Ok(())
+}
+
+fn absences(files: &Files) -> Result<(), ExecutionError> {
+    let data = files.data.load()?;
+
+    if data.general.absences.is_empty() {
+        println!("There are no absences.");
+    }
+
+    for absence in &data.general.absences {
+        println!("{}", describe_absence(absence));
+    }
+
+    Ok(())
+}
+
+/// Registers the all-day events of an iCalendar file as holiday absences, named after the
+/// events. Events overlapping other absences are skipped.
+fn import_holidays(files: &Files, path: &str) -> Result<(), ExecutionError> {
+    let events = ical::parse(&std::fs::read_to_string(path)?)?;
+    let mut data = files.data.load()?;
+    let (mut imported, mut skipped) = (0, 0);
+
+    for event in events {
+        let absence = Absence {
+            kind: AbsenceKind::Holiday,
+            start: event.start,
+            end: event.end,
+            half: false,
+            name: event.summary,
+        };
+
+        match data.general.add_absence(absence) {
+            Ok(()) => imported += 1,
+            Err(_) => skipped += 1,
+        }
+    }
+
+    files.save(&data)?;
+    println!("Imported holidays: {}", imported);
+
+    if skipped > 0 {
+        println!("Holidays overlapping other absences: {}", skipped);
+    }
+
+    Ok(())
+}
+
+fn create(files: &Files, mnemonic: &str, code: Option<&str>) -> Result<(), ExecutionError> {
+    if !is_valid_mnemonic(mnemonic) {
+        return Err(ExecutionError::InvalidMnemonic(mnemonic.to_string()));
+    }
+
+    let mut data = files.data.load()?;
+
+    if data.task(mnemonic).is_some() {
+        return Err(ExecutionError::TaskAlreadyExists(mnemonic.to_string()));
+    }
+
+    let title = files.ask("Task name:")?;
+    data.add_task(mnemonic, Task::new(title, code.map(str::to_string)));
+    files.save(&data)?;
+
+    Ok(())
+}
+
+fn edit(files: &Files, mnemonic: &str, code: Option<&str>) -> Result<(), ExecutionError> {
+    let mut data = files.data.load()?;
+
+    let task = data
+        .task_mut(mnemonic)
+        .ok_or_else(|| ExecutionError::UnknownTask(mnemonic.to_string()))?;
+
+    let title = files.ask(&format!("Task name [{}]:", task.title))?;
+
+    if !title.trim().is_empty() {
+        task.title = title;
+    }
+
+    if let Some(code) = code {
+        task.code = Some(code.to_string());
+    }
+
+    files.save(&data)?;
+    Ok(())
+}
+
+/// Deletes a task. A task with history can only be archived, i.e. deactivated, or purged,
+/// in which case its history entries are removed after confirmation and a backup.
+fn delete(files: &Files, mnemonic: &str, mode: DeleteMode) -> Result<(), ExecutionError> {
+    let mut data = files.data.load()?;
+
+    if data.task(mnemonic).is_none() {
+        return Err(ExecutionError::UnknownTask(mnemonic.to_string()));
+    }
+
+    let lines = files.history.read()?;
+    let count = lines
+        .iter()
+        .filter(|line| line.entry.is_about(mnemonic))
+        .count();
+
+    match mode {
+        DeleteMode::Default if count > 0 => {
+            return Err(ExecutionError::TaskHasHistory(mnemonic.to_string()));
+        }
+        DeleteMode::Archive => {
+            if current_task(files)?.as_deref() == Some(mnemonic) {
+                return Err(ExecutionError::TaskIsOpen(mnemonic.to_string()));
+            }
+
+            data.set_active(mnemonic, false);
+            files.save(&data)?;
+            return Ok(());
+        }
+        DeleteMode::Purge if count > 0 => {
+            let total = worked_time(lines.iter().map(|line| &line.entry), mnemonic, Local::now());
+
+            println!(
+                "{} history entries about {} will be removed, with {} worked in total.",
+                count,
+                mnemonic,
+                format_duration(total)
+            );
+
+            if !files.confirm("Proceed?")? {
+                return Ok(());
+            }
+
+            for path in &[files.history.path(), files.data.path()] {
+                if path.exists() {
+                    let backup = backup(path)?;
+                    println!("Backed up {} to {}", path.display(), backup.display());
+                }
+            }
+
+            let entries: Vec<HistoryEntry> = lines
+                .iter()
+                .filter(|line| !line.entry.is_about(mnemonic))
+                .map(|line| line.entry.clone())
+                .collect();
+
+            data.remove_task(mnemonic);
+            return replace_entries_and_save(files, &lines, &entries, &data);
+        }
+        DeleteMode::Default | DeleteMode::Purge => {}
+    }
+
+    data.remove_task(mnemonic);
+    files.save(&data)?;
+    Ok(())
+}
+
+/// Changes the mnemonic of a task, rewriting its history entries accordingly.
+fn rename(files: &Files, old: &str, new: &str) -> Result<(), ExecutionError> {
+    if !is_valid_mnemonic(new) {
+        return Err(ExecutionError::InvalidMnemonic(new.to_string()));
+    }
+
+    let mut data = files.data.load()?;
+
+    if data.task(old).is_none() {
+        return Err(ExecutionError::UnknownTask(old.to_string()));
+    }
+
+    if data.task(new).is_some() {
+        return Err(ExecutionError::TaskAlreadyExists(new.to_string()));
+    }
+
+    let lines = files.history.read()?;
+    let (entries, count) = retarget_entries(&lines, old, new);
+
+    data.rename_task(old, new);
+    replace_entries_and_save(files, &lines, &entries, &data)?;
+
+    println!("{} history entries rewritten.", count);
+    Ok(())
+}
+
+/// Merges a task into another one, rewriting its history entries to refer to the other task.
+/// The code and goals are carried over where the remaining task has none, and the user is
+/// asked which ones to keep when both tasks have different values.
+fn merge(files: &Files, from: &str, into: &str) -> Result<(), ExecutionError> {
+    if from == into {
+        return Err(ExecutionError::NotApplicable(
+            "a task can't be merged into itself",
+        ));
+    }
+
+    let mut data = files.data.load()?;
+
+    let source = data
+        .task(from)
+        .cloned()
+        .ok_or_else(|| ExecutionError::UnknownTask(from.to_string()))?;
+
+    let lines = files.history.read()?;
+    let previous: Vec<HistoryEntry> = lines.iter().map(|line| line.entry.clone()).collect();
+    let (entries, count) = retarget_entries(&lines, from, into);
+
+    // Overlapping sessions of both tasks can't be combined.
+    if let Some(unmatched) = unmatched_entry(&Sequence::Task(into), &previous, &entries) {
+        return Err(ExecutionError::UnmatchedEntry(unmatched));
+    }
+
+    let target = data
+        .task_mut(into)
+        .ok_or_else(|| ExecutionError::UnknownTask(into.to_string()))?;
+
+    match (&target.code, &source.code) {
+        (None, Some(code)) => target.code = Some(code.clone()),
+        (Some(current), Some(code)) if current != code => {
+            let question = format!(
+                "Keep the code of {} ({}) instead of {}?",
+                from, code, current
+            );
+
+            if files.confirm(&question)? {
+                target.code = Some(code.clone());
+            }
+        }
+        _ => {}
+    }
+
+    for &(period, duration) in source.goals.iter() {
+        match target.goals.get(period) {
+            None => target.goals.set(period, duration),
+            Some(current) if current != duration => {
+                let question = format!(
+                    "Keep the {} goal of {} ({}) instead of {}?",
+                    period,
+                    from,
+                    format_duration(duration),
+                    format_duration(current)
+                );
+
+                if files.confirm(&question)? {
+                    target.goals.set(period, duration);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if source.active {
+        data.set_active(into, true);
+    }
+
+    data.retarget_interruptions(from, into);
+    data.remove_task(from);
+
+    replace_entries_and_save(files, &lines, &entries, &data)?;
+
+    println!("{} history entries moved from {} to {}.", count, from, into);
+    Ok(())
+}
+
+fn start(
+    files: &Files,
+    mnemonic: &str,
+    datetime: ForgetableDateTime,
+    note: Option<&str>,
+) -> Result<(), ExecutionError> {
+    let mut data = files.data.load()?;
+
+    let active = data
+        .task(mnemonic)
+        .ok_or_else(|| ExecutionError::UnknownTask(mnemonic.to_string()))?
+        .active;
+
+    let lines = files.history.read()?;
+    let mut entries: Vec<HistoryEntry> = lines.iter().map(|line| line.entry.clone()).collect();
+
+    if !add_start(files, &data, &mut entries, mnemonic, datetime, note)? {
+        return Ok(());
+    }
+
+    if active {
+        replace_entries(files, &lines, &entries)
+    } else {
+        data.set_active(mnemonic, true);
+        replace_entries_and_save(files, &lines, &entries, &data)
+    }
+}
+
+/// Stops the task being worked on and starts another one at the same moment. The note goes to
+/// the `stop` entry, since it's about the work done on the stopped task. Everything is asked
+/// before anything is written, and written at once.
+fn switch(
+    files: &Files,
+    mnemonic: &str,
+    datetime: ForgetableDateTime,
+    note: Option<&str>,
+    then_commit: bool,
+) -> Result<(), ExecutionError> {
+    check_task_exists(files, mnemonic)?;
+
+    let mut data = files.data.load()?;
+    let lines = files.history.read()?;
+    let mut entries: Vec<HistoryEntry> = lines.iter().map(|line| line.entry.clone()).collect();
+
+    let open = open_task_at(&entries, datetime.datetime).ok_or(ExecutionError::NoOpenTask)?;
+
+    if open == mnemonic {
+        return Err(ExecutionError::TaskIsOpen(open));
+    }
+
+    if !add_stop(
+        files,
+        &data,
+        &mut entries,
+        &open,
+        datetime,
+        note,
+        then_commit,
+    )? || !add_start(files, &data, &mut entries, mnemonic, datetime, None)?
+    {
+        return Ok(());
+    }
+
+    // Switching away from an interrupting task ends the interruption for good.
+    let ends_interruption = data
+        .general
+        .interruptions
+        .last()
+        .is_some_and(|last| last.by == open);
+
+    if ends_interruption {
+        data.general.interruptions.pop();
+    }
+
+    let activates = !data.task(mnemonic).is_some_and(|task| task.active);
+    data.set_active(mnemonic, true);
+
+    if ends_interruption || activates {
+        replace_entries_and_save(files, &lines, &entries, &data)
+    } else {
+        replace_entries(files, &lines, &entries)
+    }
+}
+
+/// Suspends the task being worked on and starts another one at the same moment. The suspended
+/// task is started again when the interrupting one is stopped.
+fn interrupt(
+    files: &Files,
+    mnemonic: &str,
+    datetime: ForgetableDateTime,
+    note: Option<&str>,
+) -> Result<(), ExecutionError> {
+    check_task_exists(files, mnemonic)?;
+
+    let lines = files.history.read()?;
+    let entries: Vec<HistoryEntry> = lines.iter().map(|line| line.entry.clone()).collect();
+
+    let open = open_task_at(&entries, datetime.datetime).ok_or(ExecutionError::NoOpenTask)?;
+
+    if open == mnemonic {
+        return Err(ExecutionError::TaskIsOpen(open));
+    }
+
+    let mut data = files.data.load()?;
+    let mut entries = entries;
+
+    if !add_stop(files, &data, &mut entries, &open, datetime, None, false)?
+        || !add_start(files, &data, &mut entries, mnemonic, datetime, note)?
+    {
+        return Ok(());
+    }
+
+    data.set_active(mnemonic, true);
+    data.general.interruptions.push(Interruption {
+        suspended: open.clone(),
+        by: mnemonic.to_string(),
+    });
+
+    replace_entries_and_save(files, &lines, &entries, &data)?;
+
+    println!("Task suspended: {}", open);
+    Ok(())
+}
+
+/// Stops a task. If it interrupted another task, the other task is started again at the same
+/// moment.
+fn stop_or_end_interruption(
+    files: &Files,
+    mnemonic: Option<&str>,
+    datetime: ForgetableDateTime,
+    note: Option<&str>,
+    then_commit: bool,
+) -> Result<(), ExecutionError> {
+    let mnemonic = match mnemonic {
+        Some(mnemonic) => {
+            check_task_exists(files, mnemonic)?;
+            mnemonic.to_string()
+        }
+        None => current_task(files)?.ok_or(ExecutionError::NoOpenTask)?,
+    };
+
+    let mut data = files.data.load()?;
+    let lines = files.history.read()?;
+    let mut entries: Vec<HistoryEntry> = lines.iter().map(|line| line.entry.clone()).collect();
+
+    let end = chronological_position(&entries, datetime.datetime);
+
+    let was_open = sessions(&entries[..end])
+        .iter()
+        .any(|session| session.is_open() && session.mnemonic == mnemonic);
+
+    if !add_stop(
+        files,
+        &data,
+        &mut entries,
+        &mnemonic,
+        datetime,
+        note,
+        then_commit,
+    )? {
+        return Ok(());
+    }
+
+    let interruption = match data.general.interruptions.last() {
+        Some(last) if was_open && last.by == mnemonic => data.general.interruptions.pop(),
+        _ => None,
+    };
+
+    match interruption {
+        Some(interruption) => {
+            insert_entry(
+                &mut entries,
+                HistoryEntry::Start {
+                    mnemonic: interruption.suspended.clone(),
+                    datetime,
+                    note: None,
+                },
+            );
+
+            replace_entries_and_save(files, &lines, &entries, &data)?;
+            println!("Task resumed: {}", interruption.suspended);
+        }
+        None => replace_entries(files, &lines, &entries)?,
+    }
+
+    Ok(())
+}
+
+/// Places a `commit` entry right after the last `stop` of the task before the given date/time,
+/// and displays the time to be logged in the external tool.
+fn commit(
+    files: &Files,
+    mnemonic: &str,
+    datetime: DateTime,
+    note: Option<&str>,
+) -> Result<(), ExecutionError> {
+    check_task_exists(files, mnemonic)?;
+
+    let data = files.data.load()?;
+    let lines = files.history.read()?;
+    let entries: Vec<HistoryEntry> = lines.iter().map(|line| line.entry.clone()).collect();
+    let end = end_before(&lines, datetime);
+
+    let last_stop = entries[..end]
+        .iter()
+        .rposition(|entry| is_stop_of(entry, mnemonic));
+
+    let last_commit = entries[..end]
+        .iter()
+        .rposition(|entry| is_commit_of(entry, mnemonic));
+
+    let last_stop = match (last_stop, last_commit) {
+        (Some(stop), Some(commit)) if stop < commit => None,
+        (stop, _) => stop,
+    };
+
+    let last_stop = match last_stop {
+        Some(index) => index,
+        None => {
+            println!("There's nothing to commit.");
+            return Ok(());
+        }
+    };
+
+    let offset = lines
+        .get(last_stop + 1)
+        .map(|line| line.offset)
+        .map_or_else(|| files.history.size(), Ok)?;
+
+    files.insert_at(
+        offset,
+        &HistoryEntry::Commit {
+            mnemonic: mnemonic.to_string(),
+            note: note.map(str::to_string),
+        },
+    )?;
+
+    print_commit(&data, &entries, mnemonic, last_commit, last_stop);
+    Ok(())
+}
+
+/// Adds a `start` entry for the task at its chronological place, asking first if the task is
+/// already started, and offering to stop the task open at that moment, if any. Returns whether
+/// the entry was added.
+fn add_start(
+    files: &Files,
+    data: &Data,
+    entries: &mut Vec<HistoryEntry>,
+    mnemonic: &str,
+    datetime: ForgetableDateTime,
+    note: Option<&str>,
+) -> Result<bool, ExecutionError> {
+    if let Some(last @ HistoryEntry::Start { .. }) =
+        last_session_entry(entries, mnemonic, datetime.datetime)
+    {
+        if !confirm_after(files, &last.clone())? {
+            return Ok(false);
+        }
+    }
+
+    if let Some(open) = open_task_at(entries, datetime.datetime).filter(|open| open != mnemonic) {
+        let question = format!("Task {} is open. Stop it at the same time?", open);
+
+        if files.confirm(&question)? {
+            add_stop(files, data, entries, &open, datetime, None, false)?;
+        }
+    }
+
+    insert_entry(
+        entries,
+        HistoryEntry::Start {
+            mnemonic: mnemonic.to_string(),
+            datetime,
+            note: note.map(str::to_string),
+        },
+    );
+
+    Ok(true)
+}
+
+/// Adds a `stop` entry for the task at its chronological place, followed by a `commit` if
+/// `then_commit`, asking first if the task is already stopped. Returns whether the entries
+/// were added.
+fn add_stop(
+    files: &Files,
+    data: &Data,
+    entries: &mut Vec<HistoryEntry>,
+    mnemonic: &str,
+    datetime: ForgetableDateTime,
+    note: Option<&str>,
+    then_commit: bool,
+) -> Result<bool, ExecutionError> {
+    match last_session_entry(entries, mnemonic, datetime.datetime) {
+        Some(last @ HistoryEntry::Stop { .. }) if !confirm_after(files, &last.clone())? => {
+            return Ok(false);
+        }
+        Some(HistoryEntry::Start {
+            datetime: start, ..
+        }) => println!(
+            "Session duration: {}",
+            format_duration(datetime.datetime - start.datetime)
+        ),
+        _ => {}
+    }
+
+    let stop = insert_entry(
+        entries,
+        HistoryEntry::Stop {
+            mnemonic: mnemonic.to_string(),
+            datetime,
+            note: note.map(str::to_string),
+        },
+    );
+
+    if then_commit {
+        let last_commit = entries[..stop]
+            .iter()
+            .rposition(|entry| is_commit_of(entry, mnemonic));
+
+        entries.insert(
+            stop + 1,
+            HistoryEntry::Commit {
+                mnemonic: mnemonic.to_string(),
+                note: None,
+            },
+        );
+
+        print_commit(data, entries, mnemonic, last_commit, stop);
+    }
+
+    Ok(true)
+}
+
+/// Displays the time to be logged by a commit of the sessions after the previous commit, up
+/// to the `stop` at the given index, and the time worked on the task afterwards.
+fn print_commit(
+    data: &Data,
+    entries: &[HistoryEntry],
+    mnemonic: &str,
+    last_commit: Option<usize>,
+    last_stop: usize,
+) {
+    let first = last_commit.map_or(0, |commit| commit + 1);
+    let now = Local::now();
+
+    let committed = worked_time(&entries[first..=last_stop], mnemonic, now);
+    let uncommitted = worked_time(&entries[last_stop + 1..], mnemonic, now);
+
+    match data.rounding(mnemonic) {
+        Some(rounding) => {
+            let billing = report::bill(&entries[..=last_stop], mnemonic, &rounding, now);
+
+            println!(
+                "Time to be logged: {} ({} worked, rounded {})",
+                format_duration(billing.logged),
+                format_duration(committed),
+                rounding
+            );
+
+            if billing.carry != Duration::zero() {
+                println!(
+                    "Carried over to the next commit: {}",
+                    format_balance(billing.carry)
+                );
+            }
+        }
+        None => println!("Time to be logged: {}", format_duration(committed)),
+    }
+
+    if uncommitted > Duration::zero() {
+        println!(
+            "Time worked after the commit: {}",
+            format_duration(uncommitted)
+        );
+    }
+}
+
+fn goal(files: &Files, action: GoalAction, mnemonic: Option<&str>) -> Result<(), ExecutionError> {
+    let mut data = files.data.load()?;
+
+    let goals = data
+        .goals_mut(mnemonic)
+        .ok_or_else(|| ExecutionError::UnknownTask(mnemonic.unwrap_or_default().to_string()))?;
+
+    match action {
+        GoalAction::Set(period, duration) => goals.set(period, duration),
+        GoalAction::Erase(period) => {
+            if !goals.erase(period) {
+                println!("There's no {} goal.", period);
+                return Ok(());
+            }
+        }
+        GoalAction::EraseAll => goals.clear(),
+    }
+
+    files.save(&data)?;
+    Ok(())
+}
+
+fn rounding(
+    files: &Files,
+    mnemonic: Option<&str>,
+    action: RoundingAction,
+) -> Result<(), ExecutionError> {
+    let mut data = files.data.load()?;
+    let general = data.general.rounding;
+
+    let rounding = match mnemonic {
+        Some(mnemonic) => {
+            &mut data
+                .task_mut(mnemonic)
+                .ok_or_else(|| ExecutionError::UnknownTask(mnemonic.to_string()))?
+                .rounding
+        }
+        None => &mut data.general.rounding,
+    };
+
+    match action {
+        RoundingAction::Set(policy) => *rounding = Some(policy),
+        RoundingAction::Erase => {
+            if rounding.take().is_none() {
+                println!("There's no rounding policy.");
+                return Ok(());
+            }
+        }
+        RoundingAction::Show => {
+            match (*rounding, general.filter(|_| mnemonic.is_some())) {
+                (Some(policy), _) => println!("Rounded {}", policy),
+                (None, Some(policy)) => println!("Rounded {} (general policy)", policy),
+                (None, None) => println!("There's no rounding policy."),
+            }
+
+            return Ok(());
+        }
+    }
+
+    files.save(&data)?;
+    Ok(())
+}
+
+fn rate(files: &Files, target: RateTarget, action: RateAction) -> Result<(), ExecutionError> {
+    let mut data = files.data.load()?;
+
+    let rate = match action {
+        RateAction::Set(rate) => Some(rate),
+        RateAction::Erase => None,
+        RateAction::Show => return show_rate(&data, target),
+    };
+
+    let erasing = rate.is_none();
+
+    let previous = match target {
+        RateTarget::Task(mnemonic) => {
+            let task = data
+                .task_mut(mnemonic)
+                .ok_or_else(|| ExecutionError::UnknownTask(mnemonic.to_string()))?;
+
+            std::mem::replace(&mut task.rate, rate)
+        }
+        RateTarget::Client(client) => match rate {
+            Some(rate) => data.general.rates.clients.insert(client.to_string(), rate),
+            None => data.general.rates.clients.shift_remove(client),
+        },
+        RateTarget::Default => std::mem::replace(&mut data.general.rates.default, rate),
+    };
+
+    if erasing && previous.is_none() {
+        println!("There's no rate.");
+        return Ok(());
+    }
+
+    files.save(&data)?;
+    Ok(())
+}
+
+/// Displays a rate. For a task without a rate of its own, the rate that applies to it is
+/// displayed, along with where it comes from.
+fn show_rate(data: &Data, target: RateTarget) -> Result<(), ExecutionError> {
+    let (rate, source): (Option<&Money>, Option<String>) = match target {
+        RateTarget::Task(mnemonic) => {
+            let task = data
+                .task(mnemonic)
+                .ok_or_else(|| ExecutionError::UnknownTask(mnemonic.to_string()))?;
+
+            let client = task.client.as_ref().and_then(|client| {
+                data.general
+                    .rates
+                    .clients
+                    .get(client)
+                    .map(|rate| (rate, format!("rate of {}", client)))
+            });
+
+            match (&task.rate, client, &data.general.rates.default) {
+                (Some(rate), _, _) => (Some(rate), None),
+                (None, Some((rate, source)), _) => (Some(rate), Some(source)),
+                (None, None, Some(rate)) => (Some(rate), Some(String::from("default rate"))),
+                (None, None, None) => (None, None),
+            }
+        }
+        RateTarget::Client(client) => (data.general.rates.clients.get(client), None),
+        RateTarget::Default => (data.general.rates.default.as_ref(), None),
+    };
+
+    match (rate, source) {
+        (Some(rate), Some(source)) => println!("{}/h ({})", rate, source),
+        (Some(rate), None) => println!("{}/h", rate),
+        (None, _) => println!("There's no rate."),
+    }
+
+    Ok(())
+}
+
+fn assign_client(
+    files: &Files,
+    mnemonic: &str,
+    client: Option<&str>,
+) -> Result<(), ExecutionError> {
+    let mut data = files.data.load()?;
+
+    let task = data
+        .task_mut(mnemonic)
+        .ok_or_else(|| ExecutionError::UnknownTask(mnemonic.to_string()))?;
+
+    task.client = client.map(str::to_string);
+    files.save(&data)?;
+    Ok(())
+}
+
+/// Writes an invoice for the time worked from the first day to the last one to the standard
+/// output. Unless it's a preview, the invoice gets the number after the last one.
+fn write_invoice(
+    files: &Files,
+    first: NaiveDate,
+    last: NaiveDate,
+    format: InvoiceFormat,
+    client: Option<&str>,
+    filter: SessionFilter,
+    preview: bool,
+) -> Result<(), ExecutionError> {
+    let mut data = files.data.load()?;
+
+    if let Some(client) = client {
+        if !data
+            .tasks
+            .values()
+            .any(|task| task.client.as_deref() == Some(client))
+        {
+            return Err(ExecutionError::UnknownClient(client.to_string()));
+        }
+    }
+
+    let lines = files.history.read()?;
+    let entries: Vec<&HistoryEntry> = lines.iter().map(|line| &line.entry).collect();
+    let now = Local::now();
+
+    let lines = invoice::lines(
+        &data,
+        &entries,
+        &Period::days(first, last),
+        client,
+        filter,
+        now,
+    );
+
+    if lines.is_empty() {
+        println!("No time worked in the period.");
+        return Ok(());
+    }
+
+    let number = if preview {
+        None
+    } else {
+        data.general.last_invoice += 1;
+        files.save(&data)?;
+        Some(data.general.last_invoice)
+    };
+
+    let invoice = Invoice {
+        number,
+        issued: now.date().naive_local(),
+        first,
+        last,
+        client: client.map(str::to_string),
+        lines,
+    };
+
+    print!("{}", invoice.render(format));
+    Ok(())
+}
+
+/// Displays the goals of a task, or of the work in general without a mnemonic, with the time
+/// worked in the current periods. A namespace that isn't a task itself displays the goals of
+/// each task within it.
+fn goals(files: &Files, mnemonic: Option<&str>) -> Result<(), ExecutionError> {
+    let data = files.data.load()?;
+
+    let is_namespace = mnemonic.is_some_and(|mnemonic| data.task(mnemonic).is_none());
+    let tasks: Vec<Option<&str>> = match mnemonic {
+        Some(mnemonic) if is_namespace => data
+            .tasks
+            .keys()
+            .filter(|task| is_within(task, mnemonic))
+            .map(|task| Some(task.as_str()))
+            .collect(),
+        mnemonic => vec![mnemonic],
+    };
+
+    if tasks.is_empty() {
+        return Err(ExecutionError::UnknownTask(
+            mnemonic.unwrap_or_default().to_string(),
+        ));
+    }
+
+    let tasks: Vec<(Option<&str>, &Goals)> = tasks
+        .into_iter()
+        .filter_map(|task| data.goals(task).map(|goals| (task, goals)))
+        .filter(|(_, goals)| !(is_namespace && goals.is_empty()))
+        .collect();
+
+    if tasks.iter().all(|(_, goals)| goals.is_empty()) {
+        println!("There are no goals.");
+        return Ok(());
+    }
+
+    let now = Local::now();
+    let totals = Totals::load(&files.history, now)?;
+
+    for (task, goals) in tasks {
+        let indent = if is_namespace {
+            println!("{}:", task.unwrap_or_default());
+            "  "
+        } else {
+            ""
+        };
+
+        for &(period, goal) in goals.iter() {
+            let current = match current_goal(&data, task, period, now) {
+                Some(current) => current,
+                None => {
+                    println!("{}{}: {}", indent, period, format_duration(goal));
+                    continue;
+                }
+            };
+
+            let worked = totals.worked(task, &Period::containing(period, now), now);
+            let mut parts = Vec::new();
+
+            if goal_for(goals, period, now) != Some(current) {
+                parts.push(format!("{} with absences", format_duration(current)));
+            }
+
+            parts.push(format!("{} worked", format_duration(worked)));
+            parts.push(remaining(current, worked));
+
+            println!(
+                "{}{}: {} ({})",
+                indent,
+                period,
+                format_duration(goal),
+                parts.join(", ")
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Displays the time worked in the current day, week and month, against the goals.
+/// With a mnemonic, the time worked on the task is displayed instead, including the tasks
+/// within its namespace.
+fn status(files: &Files, mnemonic: Option<&str>) -> Result<(), ExecutionError> {
+    let data = files.data.load()?;
+    let now = Local::now();
+    let totals = Totals::load(&files.history, now)?;
+
+    let current = totals.sessions().iter().rev().find(|session| {
+        session.is_open() && mnemonic.is_none_or(|mnemonic| is_within(&session.mnemonic, mnemonic))
+    });
+
+    let mnemonic = match mnemonic {
+        Some(mnemonic) => mnemonic,
+        None => {
+            let presence = totals
+                .presences()
+                .last()
+                .filter(|presence| presence.is_open());
+            let pause = totals.breaks().last().filter(|pause| pause.is_open());
+
+            match (presence, pause) {
+                (Some(_), Some(pause)) => println!(
+                    "On a break since {} ({})",
+                    pause.pause.datetime.format("%H:%M"),
+                    format_duration(now - pause.pause.datetime)
+                ),
+                (Some(presence), None) => println!(
+                    "Working since {} ({})",
+                    presence.enter.datetime.format("%H:%M"),
+                    format_duration(now - presence.enter.datetime)
+                ),
+                (None, _) => println!("Not working"),
+            }
+
+            print_progress(
+                &totals,
+                None,
+                &data,
+                presence.is_some() && pause.is_none(),
+                now,
+            );
+
+            if let Some(first) = data.general.balance_start {
+                let balance = balance_weeks(&data, &totals, first, now)
+                    .iter()
+                    .fold(Duration::zero(), |total, week| {
+                        total + week.worked - week.expected
+                    });
+
+                println!("Balance: {} until yesterday", format_balance(balance));
+            }
+
+            match current {
+                Some(session) => {
+                    let total = totals.task_total(&session.mnemonic, now);
+                    let unlogged = totals.unlogged(&session.mnemonic, now);
+
+                    println!(
+                        "Working on {} since {}, {} worked in total, {} unlogged",
+                        describe_task(&data, &session.mnemonic),
+                        session.start.datetime.format("%H:%M"),
+                        format_duration(total),
+                        format_duration(unlogged)
+                    );
+                }
+                None => println!("No active task"),
+            }
+
+            let suspended: Vec<&str> = data
+                .general
+                .interruptions
+                .iter()
+                .rev()
+                .map(|interruption| interruption.suspended.as_str())
+                .collect();
+
+            if !suspended.is_empty() {
+                println!("Suspended tasks: {}", suspended.join(", "));
+            }
+
+            return Ok(());
+        }
+    };
+
+    if data.task(mnemonic).is_none() && !data.tasks.keys().any(|task| is_within(task, mnemonic)) {
+        return Err(ExecutionError::UnknownTask(mnemonic.to_string()));
+    }
+
+    println!("{}", describe_task(&data, mnemonic));
+
+    match current {
+        Some(session) => println!(
+            "Working on {} since {} ({})",
+            session.mnemonic,
+            session.start.datetime.format("%H:%M"),
+            format_duration(session.duration(now))
+        ),
+        None => println!("Not working on it"),
+    }
+
+    print_progress(&totals, Some(mnemonic), &data, current.is_some(), now);
+
+    println!(
+        "Total: {} worked, {} unlogged",
+        format_duration(totals.task_total(mnemonic, now)),
+        format_duration(totals.unlogged(mnemonic, now))
+    );
+
+    Ok(())
+}
+
+/// Displays the time worked on each task in the month, week or day containing the given
+/// date/time. Namespaces are displayed with the total of the tasks within them, and the time
+/// spent on breaks is displayed apart from the work. General reports also list the absences.
+/// Reports combining profiles prefix the mnemonics with the profile names, so each profile
+/// is displayed as a namespace; the mnemonic restricts the report within each profile.
+fn report(
+    files: &Files,
+    profiles: &Profiles,
+    kind: GoalPeriod,
+    datetime: DateTime,
+    mnemonic: Option<&str>,
+    selection: Option<ProfileSelection>,
+) -> Result<(), ExecutionError> {
+    let names = match selection {
+        None => vec![None],
+        Some(ProfileSelection::All) => profiles.list()?.into_iter().map(Some).collect(),
+        Some(ProfileSelection::Named(names)) => names
+            .into_iter()
+            .map(|name| Some(name.to_string()))
+            .collect(),
+    };
+
+    let mut sources = Vec::new();
+
+    for name in names {
+        match &name {
+            Some(name) if !profiles.exists(name) => {
+                return Err(ExecutionError::UnknownProfile(name.clone()))
+            }
+            // The current profile's files are already recovered and locked.
+            Some(name) if name == profiles.current() => {
+                sources.push((name.clone(), files.data.load()?, files.history.read()?))
+            }
+            Some(name) => {
+                let files = profiles.files(name);
+                files.recover()?;
+                let _lock = files.lock(false)?;
+                sources.push((name.clone(), files.data.load()?, files.history.read()?));
+            }
+            None => sources.push((String::new(), files.data.load()?, files.history.read()?)),
+        }
+    }
+
+    let period = Period::containing(kind, datetime);
+    let now = Local::now();
+    let combined = sources.iter().any(|(name, _, _)| !name.is_empty());
+
+    let first = workday(period.start);
+    let last = workday(period.end).pred();
+
+    if first == last {
+        println!("{}", first.format("%Y-%m-%d %a"));
+    } else {
+        println!(
+            "{} to {}",
+            first.format("%Y-%m-%d"),
+            last.format("%Y-%m-%d")
+        );
+    }
+
+    let mut all_sessions = Vec::new();
+    let mut paused = Duration::zero();
+
+    for (name, data, lines) in &sources {
+        if mnemonic.is_none() {
+            for absence in &data.general.absences {
+                if absence.start <= last && first <= absence.end {
+                    if combined {
+                        println!("Absence ({}) {}", name, describe_absence(absence));
+                    } else {
+                        println!("Absence {}", describe_absence(absence));
+                    }
+                }
+            }
+        }
+
+        for mut session in sessions(lines.iter().map(|line| &line.entry)) {
+            if combined {
+                if mnemonic.is_some_and(|mnemonic| !is_within(&session.mnemonic, mnemonic)) {
+                    continue;
+                }
+
+                session.mnemonic = format!("{}/{}", name, session.mnemonic);
+            }
+
+            all_sessions.push(session);
+        }
+
+        paused = paused + break_time(&breaks(lines.iter().map(|line| &line.entry)), &period, now);
+    }
+
+    let report = report::report(&all_sessions, mnemonic.filter(|_| !combined), &period, now);
+
+    if report.is_empty() {
+        println!("No time worked.");
+        return Ok(());
+    }
+
+    let width = report
+        .iter()
+        .map(|line| line.depth * 2 + line.mnemonic.len())
+        .max()
+        .unwrap_or(0)
+        .max("Breaks".len());
+
+    let mut total = Duration::zero();
+
+    for line in &report {
+        let name = format!("{}{}", "  ".repeat(line.depth), line.mnemonic);
+        println!(
+            "{:width$}  {:>8}",
+            name,
+            format_duration(line.total),
+            width = width
+        );
+
+        let indent = "  ".repeat(line.depth + 1);
+
+        for note in &line.notes {
+            println!(
+                "{}- {}",
+                indent,
+                note.replace('\n', &format!("\n{}  ", indent))
+            );
+        }
+
+        if line.depth == 0 {
+            total = total + line.total;
+        }
+    }
+
+    println!(
+        "{:width$}  {:>8}",
+        "Total",
+        format_duration(total),
+        width = width
+    );
+
+    if mnemonic.is_none() && paused > Duration::zero() {
+        println!(
+            "{:width$}  {:>8}",
+            "Breaks",
+            format_duration(paused),
+            width = width
+        );
+    }
+
+    Ok(())
+}
+
+/// Displays the flex-time balance week by week: the time worked in general minus the time
+/// expected by the daily goals, accumulated since the start date. The current day is only
+/// counted once it's over.
+fn balance(files: &Files, since: Option<NaiveDate>) -> Result<(), ExecutionError> {
+    let mut data = files.data.load()?;
+
+    if let Some(since) = since {
+        data.general.balance_start = Some(since);
+        files.save(&data)?;
+    }
+
+    let first = match data.general.balance_start {
+        Some(first) => first,
+        None => {
+            println!("There's no start date for the balance. Set one with --since.");
+            return Ok(());
+        }
+    };
+
+    let now = Local::now();
+    let weeks = balance_weeks(&data, &Totals::load(&files.history, now)?, first, now);
+
+    if weeks.is_empty() {
+        println!("No days counted since {}.", first.format("%Y-%m-%d"));
+        return Ok(());
+    }
+
+    println!(
+        "{:10}  {:>8}  {:>8}  {:>8}  {:>8}",
+        "Week", "Worked", "Expected", "Diff", "Balance"
+    );
+
+    let mut total = Duration::zero();
+
+    for week in &weeks {
+        let difference = week.worked - week.expected;
+        total = total + difference;
+
+        println!(
+            "{:10}  {:>8}  {:>8}  {:>8}  {:>8}",
+            week.start.format("%Y-%m-%d"),
+            format_duration(week.worked),
+            format_duration(week.expected),
+            format_balance(difference),
+            format_balance(total)
+        );
+    }
+
+    Ok(())
+}
+
+fn export(
+    files: &Files,
+    format: ExchangeFormat,
+    mnemonic: Option<&str>,
+) -> Result<(), ExecutionError> {
+    let data = files.data.load()?;
+
+    if let Some(mnemonic) = mnemonic {
+        if data.task(mnemonic).is_none() {
+            return Err(ExecutionError::UnknownTask(mnemonic.to_string()));
+        }
+    }
+
+    let entries: Vec<HistoryEntry> = files
+        .history
+        .read()?
+        .into_iter()
+        .map(|line| line.entry)
+        .collect();
+
+    match format {
+        ExchangeFormat::Org => print!("{}", org::export(&data, &entries, mnemonic)),
+    }
+
+    Ok(())
+}
+
+/// Adds the imported sessions to the history as `start`/`stop` pairs. Sessions that are
+/// already in the history are skipped, so exporting and importing back has no effect, and so
+/// are the ones overlapping other sessions of the same task. Unknown tasks are created as
+/// inactive. Everything is written at once.
+fn import(files: &Files, format: ExchangeFormat, path: &str) -> Result<(), ExecutionError> {
+    let text = std::fs::read_to_string(path)?;
+
+    let tasks = match format {
+        ExchangeFormat::Org => org::parse(&text)?,
+    };
+
+    let mut data = files.data.load()?;
+    let lines = files.history.read()?;
+    let mut entries: Vec<HistoryEntry> = lines.iter().map(|line| line.entry.clone()).collect();
+    let (mut imported, mut skipped, mut overlapping) = (0, 0, 0);
+    let mut created = false;
+
+    for task in tasks {
+        let mnemonic = match (&task.mnemonic, &task.code) {
+            (Some(mnemonic), _) => mnemonic.clone(),
+            (None, Some(code)) => match data
+                .tasks
+                .iter()
+                .find(|(_, other)| other.code.as_ref() == Some(code))
+            {
+                Some((mnemonic, _)) => mnemonic.clone(),
+                None => {
+                    println!(
+                        "Skipping \"{}\": no task has the code {}.",
+                        task.title, code
+                    );
+                    continue;
+                }
+            },
+            (None, None) => {
+                println!("Skipping \"{}\": no MNEMONIC or CODE property.", task.title);
+                continue;
+            }
+        };
+
+        if !is_valid_mnemonic(&mnemonic) {
+            return Err(ExecutionError::InvalidMnemonic(mnemonic));
+        }
+
+        if data.task(&mnemonic).is_none() {
+            let mut new_task = Task::new(task.title.clone(), task.code.clone());
+            new_task.active = false;
+            data.add_task(&mnemonic, new_task);
+            created = true;
+        }
+
+        for clock in task.clocks {
+            let (start, stop) = (clock.start, clock.stop);
+            let existing: Vec<(DateTime, Option<DateTime>)> = sessions(&entries)
+                .into_iter()
+                .filter(|session| session.mnemonic == mnemonic)
+                .map(|session| {
+                    (
+                        session.start.datetime,
+                        session.stop.map(|stop| stop.datetime),
+                    )
+                })
+                .collect();
+
+            if existing.iter().any(|&(other_start, other_stop)| {
+                other_start == start && stop.is_none_or(|stop| other_stop == Some(stop))
+            }) {
+                skipped += 1;
+                continue;
+            }
+
+            // Open sessions overlap everything after their start.
+            if existing.iter().any(|&(other_start, other_stop)| {
+                other_stop.is_none_or(|other_stop| start < other_stop)
+                    && stop.is_none_or(|stop| other_start < stop)
+            }) {
+                overlapping += 1;
+                continue;
+            }
+
+            // The notes go to the entry that closes the session, if there is one.
+            let note = Some(clock.notes.join("\n")).filter(|note| !note.is_empty());
+
+            insert_entry(
+                &mut entries,
+                HistoryEntry::Start {
+                    mnemonic: mnemonic.clone(),
+                    datetime: ForgetableDateTime {
+                        datetime: start,
+                        forgotten: false,
+                    },
+                    note: note.clone().filter(|_| stop.is_none()),
+                },
+            );
+
+            if let Some(stop) = stop {
+                insert_entry(
+                    &mut entries,
+                    HistoryEntry::Stop {
+                        mnemonic: mnemonic.clone(),
+                        datetime: ForgetableDateTime {
+                            datetime: stop,
+                            forgotten: false,
+                        },
+                        note,
+                    },
+                );
+            }
+
+            imported += 1;
+        }
+    }
+
+    if created {
+        replace_entries_and_save(files, &lines, &entries, &data)?;
+    } else if imported > 0 {
+        replace_entries(files, &lines, &entries)?;
+    }
+
+    println!("Imported sessions: {}", imported);
+
+    if skipped > 0 {
+        println!("Sessions already in the history: {}", skipped);
+    }
+
+    if overlapping > 0 {
+        println!(
+            "Sessions overlapping others of the same task: {}",
+            overlapping
+        );
+    }
+
+    Ok(())
+}
+
+/// Reverts all the changes made by the last command that changed anything, after showing
+/// them to the user and asking for confirmation. Nothing is reverted if any of the changed
+/// entries or tasks has been changed again since then.
+fn undo(files: &Files) -> Result<(), ExecutionError> {
+    let transaction = match files.journal.load()? {
+        Some(transaction) if !transaction.changes.is_empty() => transaction,
+        _ => {
+            println!("There's nothing to undo.");
+            return Ok(());
+        }
+    };
+
+    println!("The following changes will be made:");
+
+    for change in transaction.changes.iter().rev() {
+        println!("- {}", change);
+    }
+
+    if !files.confirm("Proceed?")? {
+        return Ok(());
+    }
+
+    let mut data = files.data.load()?;
+    let mut replacements = Vec::new();
+
+    for change in transaction.changes.iter().rev() {
+        match change {
+            Change::HistoryInsert { offset, line } => {
+                replacements.push((*offset, vec![line.clone()], Vec::new()))
+            }
+            Change::HistoryReplace {
+                offset,
+                previous,
+                current,
+            } => replacements.push((*offset, current.clone(), previous.clone())),
+            Change::Task {
+                mnemonic,
+                index,
+                previous,
+                current,
+            } => {
+                if data.task(mnemonic) != current.as_ref() {
+                    return Err(ExecutionError::UndoConflict(format!("task {}", mnemonic)));
+                }
+
+                data.place_task(mnemonic, previous.clone(), *index);
+            }
+            Change::General { previous, current } => {
+                if data.general != *current {
+                    return Err(ExecutionError::UndoConflict(format!(
+                        "general {}",
+                        general_fields(current, &data.general).join(", ")
+                    )));
+                }
+
+                data.general = previous.clone();
+            }
+        }
+    }
+
+    let replacements: Vec<Replacement> = replacements
+        .iter()
+        .map(|(offset, previous, current)| Replacement {
+            offset: *offset,
+            previous,
+            current,
+        })
+        .collect();
+
+    let data_changed = transaction.changes.iter().any(|change| match change {
+        Change::HistoryInsert { .. } | Change::HistoryReplace { .. } => false,
+        Change::Task { .. } | Change::General { .. } => true,
+    });
+
+    // The journal is emptied along with the reverting changes, so a crash can't leave it
+    // holding changes already reverted.
+    let mut others = Vec::new();
+
+    if data_changed {
+        others.push(files.data.rewrite(&data)?);
+    }
+
+    others.push(files.journal.rewrite_empty()?);
+
+    if !files.history.replace_along(&replacements, others)? {
+        return Err(ExecutionError::UndoConflict(String::from(
+            "history entries",
+        )));
+    }
+
+    Ok(())
+}
+
+/// Lists the last entries, optionally only the ones about a task. The indices always refer to
+/// the whole history, so they can be used by `amend` regardless of the filter.
+fn log(files: &Files, mnemonic: Option<&str>, count: usize) -> Result<(), ExecutionError> {
+    let lines = files.history.read()?;
+    let ids = entry_ids(&lines);
+
+    let selected: Vec<usize> = (0..lines.len())
+        .filter(|&index| mnemonic.is_none_or(|mnemonic| lines[index].entry.is_about(mnemonic)))
+        .collect();
+
+    for &index in &selected[selected.len().saturating_sub(count)..] {
+        println!(
+            "{:>5}  {}  {}",
+            lines.len() - index,
+            ids[index],
+            lines[index].entry
+        );
+    }
+
+    Ok(())
+}
+
+/// Changes or deletes an entry, moving it to keep the history in chronological order.
+/// A change is refused if it leaves unmatched entries, e.g. a `stop` before its `start`.
+fn amend(
+    files: &Files,
+    selector: EntrySelector,
+    action: AmendAction,
+) -> Result<(), ExecutionError> {
+    let lines = files.history.read()?;
+    let index = select_entry(&lines, &selector)?;
+    let previous: Vec<HistoryEntry> = lines.iter().map(|line| line.entry.clone()).collect();
+    let mut entries = previous.clone();
+    let old = entries[index].clone();
+
+    match action {
+        AmendAction::Delete => {
+            entries.remove(index);
+
+            let sequence = Sequence::of(&old);
+            let breaks = sequence
+                .as_ref()
+                .is_some_and(|sequence| unmatched_entry(sequence, &previous, &entries).is_some());
+
+            if breaks {
+                let partner = sequence
+                    .and_then(|sequence| find_partner(&sequence, &previous, index))
+                    .ok_or_else(|| ExecutionError::UnmatchedEntry(old.to_string()))?;
+
+                println!("Deleting the entry alone would leave this one unmatched:");
+                println!("{}", previous[partner]);
+
+                if !files.confirm("Delete both?")? {
+                    return Ok(());
+                }
+
+                entries.remove(if partner < index {
+                    partner
+                } else {
+                    partner - 1
+                });
+            }
+        }
+        AmendAction::Change {
+            datetime,
+            forgotten,
+            mnemonic,
+        } => {
+            let mut new = old.clone();
+
+            if let Some(mnemonic) = mnemonic {
+                if old.mnemonic().is_none() {
+                    return Err(ExecutionError::NotApplicable(
+                        "the entry doesn't refer to a task",
+                    ));
+                }
+
+                check_task_exists(files, mnemonic)?;
+                new.set_mnemonic(mnemonic);
+            }
+
+            if datetime.is_some() || forgotten {
+                let current = old
+                    .forgettable_datetime()
+                    .ok_or(ExecutionError::NotApplicable("the entry has no date/time"))?;
+
+                new.set_datetime(ForgetableDateTime {
+                    datetime: datetime.unwrap_or(current.datetime),
+                    forgotten: forgotten || (datetime.is_none() && current.forgotten),
+                });
+            }
+
+            change_entry(&previous, &mut entries, index, new.clone())?;
+            println!("{}", new);
+        }
+    }
+
+    replace_entries(files, &lines, &entries)
+}
+
+/// Asks for an estimate of the date/time of each uncertain entry: the ones of a task, the ones
+/// of the work in general, i.e. without a task, or all of them. A time alone is the most recent
+/// fit until the date/time of the entry, which is when it was registered. Entries without an
+/// answer stay uncertain.
+fn resolve(files: &Files, mnemonic: Option<&str>, all: bool) -> Result<(), ExecutionError> {
+    if let Some(mnemonic) = mnemonic {
+        check_task_exists(files, mnemonic)?;
+    }
+
+    let lines = files.history.read()?;
+    let previous: Vec<HistoryEntry> = lines.iter().map(|line| line.entry.clone()).collect();
+    let mut entries = previous.clone();
+
+    let pending: Vec<&HistoryEntry> = previous
+        .iter()
+        .filter(|entry| {
+            entry
+                .forgettable_datetime()
+                .is_some_and(|datetime| datetime.forgotten)
+                && (all || entry.mnemonic() == mnemonic)
+        })
+        .collect();
+
+    if pending.is_empty() {
+        println!("There are no uncertain entries.");
+        return Ok(());
+    }
+
+    for old in pending {
+        let registered = old.datetime().expect("Uncertain entries have a date/time!");
+        println!("{}", old);
+
+        let datetime = loop {
+            let answer = files.ask("Estimated date/time (empty to skip):")?;
+
+            if answer.trim().is_empty() {
+                break None;
+            }
+
+            match parse_datetime(&answer, registered) {
+                Ok(datetime) => break Some(datetime),
+                Err(error) => println!("{}", error),
+            }
+        };
+
+        if let Some(datetime) = datetime {
+            let mut new = old.clone();
+            new.set_datetime(ForgetableDateTime {
+                datetime,
+                forgotten: false,
+            });
+
+            let index = entries
+                .iter()
+                .position(|entry| entry.to_string() == old.to_string())
+                .expect("Uncertain entries are kept until resolved!");
+
+            change_entry(&previous, &mut entries, index, new)?;
+        }
+    }
+
+    replace_entries(files, &lines, &entries)
+}
+
+/// Changes the config.toml file, which doesn't affect the current command.
+fn configure(action: ConfigAction) -> Result<(), ExecutionError> {
+    let path = config::path()?;
+    let mut config = Config::load(&path)?;
+
+    match action {
+        ConfigAction::Get(Some(key)) => {
+            match config.get(key)?.or(Config::default_value(key)?) {
+                Some(value) => println!("{}", value),
+                None => println!("{} isn't set.", key),
+            }
+
+            return Ok(());
+        }
+        ConfigAction::Get(None) => {
+            println!("Configuration file: {}", path.display());
+
+            for key in config::KEYS.iter() {
+                match (config.get(key)?, Config::default_value(key)?) {
+                    (Some(value), _) => println!("{} = {}", key, value),
+                    (None, Some(value)) => println!("{} = {} (default)", key, value),
+                    (None, None) => println!("{} isn't set", key),
+                }
+            }
+
+            return Ok(());
+        }
+        ConfigAction::Set(key, value) => config.set(key, Some(value))?,
+        ConfigAction::Unset(key) => config.set(key, None)?,
+    }
+
+    config.save(&path)?;
+    Ok(())
+}
+
+/// Checks the history and data files, listing the problems found with suggested fixes.
+fn doctor(files: &Files, days: u32) -> Result<(), ExecutionError> {
+    let (mut findings, mnemonics) = doctor::check_data(&read_text(files.data.path())?);
+
+    findings.extend(doctor::check_history(
+        &read_text(files.history.path())?,
+        mnemonics.as_deref(),
+        Duration::days(i64::from(days)),
+        Local::now(),
+    ));
+
+    for finding in &findings {
+        println!("{}:{}: {}", finding.file, finding.line, finding.problem);
+        println!("  fix: {}", finding.fix);
+    }
+
+    match findings.len() {
+        0 => println!("No problems found."),
+        1 => println!("1 problem found."),
+        count => println!("{} problems found.", count),
+    }
+
+    Ok(())
+}
+
+/// Fixes the common problems of the history, see `repair::repair`. The fixes are displayed
+/// along with a unified diff of each file, and only written if the user confirms them, after
+/// backing up the files.
+fn repair(files: &Files) -> Result<(), ExecutionError> {
+    let lines = files.history.read()?;
+    let mut data = files.data.load()?;
+    let repair = super::repair::repair(&lines, &data, |question| files.confirm(question))?;
+
+    if repair.fixes.is_empty() {
+        println!("Nothing to repair.");
+        return Ok(());
+    }
+
+    for fix in &repair.fixes {
+        println!("- {}", fix);
+    }
+
+    for mnemonic in &repair.tasks {
+        let mut task = Task::new(mnemonic.clone(), None);
+        task.active = false;
+        data.add_task(mnemonic, task);
+    }
+
+    // The diffs are made from the actual text of the files, blank lines included, so the line
+    // numbers match the files.
+    let replacements = changed_lines(files, &lines, &repair.entries)?;
+    let old_history = read_text(files.history.path())?;
+    let new_history = splice(&old_history, &replacements);
+
+    let old_data = read_text(files.data.path())?;
+    let new_data = if repair.tasks.is_empty() {
+        old_data.clone()
+    } else {
+        String::from_utf8_lossy(&files.data.rewrite(&data)?.tail).into_owned()
+    };
+
+    let text_lines = |text: &str| -> Vec<String> { text.lines().map(str::to_string).collect() };
+
+    println!();
+    print!(
+        "{}",
+        unified_diff(
+            "history.log",
+            &text_lines(&old_history),
+            &text_lines(&new_history)
+        )
+    );
+    print!(
+        "{}",
+        unified_diff("data.json", &text_lines(&old_data), &text_lines(&new_data))
+    );
+
+    if !files.confirm("Write the changes?")? {
+        return Ok(());
+    }
+
+    for path in &[files.history.path(), files.data.path()] {
+        if path.exists() {
+            let backup = backup(path)?;
+            println!("Backed up {} to {}", path.display(), backup.display());
+        }
+    }
+
+    match (replacements.is_empty(), repair.tasks.is_empty()) {
+        (false, false) => files.replace_and_save(replacements, &data),
+        (false, true) => files.replace(replacements),
+        (true, false) => files.save(&data),
+        (true, true) => Ok(()),
+    }
+}
+
+/// Lists the profiles, creates one or makes one the default, by changing the config.toml file.
+fn profile(profiles: &Profiles, action: ProfileAction) -> Result<(), ExecutionError> {
+    match action {
+        ProfileAction::List => {
+            for name in profiles.list()? {
+                if name == profiles.current() {
+                    println!("* {}", name);
+                } else {
+                    println!("  {}", name);
+                }
+            }
+
+            if !profiles.exists(profiles.current()) {
+                println!("* {} (missing)", profiles.current());
+            }
+        }
+        ProfileAction::Create(name) => {
+            if !is_valid_profile_name(name) {
+                return Err(ExecutionError::InvalidProfileName(name.to_string()));
+            }
+
+            if profiles.exists(name) {
+                return Err(ExecutionError::ProfileAlreadyExists(name.to_string()));
+            }
+
+            profiles.create(name)?;
+        }
+        ProfileAction::Use(name) => {
+            if !profiles.exists(name) {
+                return Err(ExecutionError::UnknownProfile(name.to_string()));
+            }
+
+            let path = config::path()?;
+            let mut config = Config::load(&path)?;
+
+            config.set(
+                "profile",
+                Some(name).filter(|&name| name != DEFAULT_PROFILE),
+            )?;
+            config.save(&path)?;
+        }
+    }
+
+    Ok(())
+}
+
+//==============================================================================
+//
+//                              Helper Functions
+//
+//==============================================================================
+
+fn select_entry(lines: &[HistoryLine], selector: &EntrySelector) -> Result<usize, ExecutionError> {
+    let index = match selector {
+        EntrySelector::Index(index) => lines.len().checked_sub(*index),
+        EntrySelector::Id(id) => entry_ids(lines)
+            .iter()
+            .position(|other| other.eq_ignore_ascii_case(id)),
+    };
+
+    index.ok_or_else(|| {
+        ExecutionError::UnknownEntry(match selector {
+            EntrySelector::Index(index) => index.to_string(),
+            EntrySelector::Id(id) => id.to_string(),
+        })
+    })
+}
+
+/// Replaces the entry at the index by a new version, moved to its chronological place along
+/// with the commit right after it, if any. Fails if an entry would be left unmatched, compared
+/// to the entries before any change.
+fn change_entry(
+    previous: &[HistoryEntry],
+    entries: &mut Vec<HistoryEntry>,
+    index: usize,
+    new: HistoryEntry,
+) -> Result<(), ExecutionError> {
+    let old = entries[index].clone();
+
+    // A commit right after a stop belongs to it, so it goes along with it.
+    let commit = match (&old, entries.get(index + 1)) {
+        (HistoryEntry::Stop { mnemonic, .. }, Some(next @ HistoryEntry::Commit { .. }))
+            if new.datetime() != old.datetime() && next.is_about(mnemonic) =>
+        {
+            Some(entries.remove(index + 1))
+        }
+        _ => None,
+    };
+
+    entries.remove(index);
+
+    let position = match new.datetime() {
+        Some(datetime) => chronological_position(entries, datetime),
+        None => index,
+    };
+
+    entries.insert(position, new.clone());
+
+    if let Some(commit) = commit {
+        entries.insert(position + 1, commit);
+    }
+
+    for entry in &[&old, &new] {
+        if let Some(sequence) = Sequence::of(entry) {
+            if let Some(unmatched) = unmatched_entry(&sequence, previous, entries) {
+                return Err(ExecutionError::UnmatchedEntry(unmatched));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// An entry of the sequence that's unmatched in `current` but not in `previous`, if any.
+fn unmatched_entry(
+    sequence: &Sequence,
+    previous: &[HistoryEntry],
+    current: &[HistoryEntry],
+) -> Option<String> {
+    let before: Vec<String> = sequence
+        .unmatched(previous)
+        .into_iter()
+        .map(|index| previous[index].to_string())
+        .collect();
+
+    let after = sequence.unmatched(current);
+
+    if after.len() <= before.len() {
+        return None;
+    }
+
+    after
+        .iter()
+        .map(|&index| current[index].to_string())
+        .find(|entry| !before.contains(entry))
+        .or_else(|| after.first().map(|&index| current[index].to_string()))
+}
+
+/// The entry that closes the period opened by the given one, or vice versa.
+fn find_partner(sequence: &Sequence, entries: &[HistoryEntry], index: usize) -> Option<usize> {
+    let opens = sequence.opens(&entries[index])?;
+
+    let partner = if opens {
+        (index + 1..entries.len()).find(|&other| sequence.opens(&entries[other]).is_some())
+    } else {
+        (0..index)
+            .rev()
+            .find(|&other| sequence.opens(&entries[other]).is_some())
+    };
+
+    partner.filter(|&other| sequence.opens(&entries[other]) == Some(!opens))
+}
+
+/// Writes the entries over the lines they were read from, replacing only what changed.
+fn replace_entries(
+    files: &Files,
+    lines: &[HistoryLine],
+    entries: &[HistoryEntry],
+) -> Result<(), ExecutionError> {
+    files.replace(changed_lines(files, lines, entries)?)
+}
+
+/// Writes the entries over the lines they were read from and saves the data file at once.
+/// See `replace_entries`.
+fn replace_entries_and_save(
+    files: &Files,
+    lines: &[HistoryLine],
+    entries: &[HistoryEntry],
+    data: &Data,
+) -> Result<(), ExecutionError> {
+    files.replace_and_save(changed_lines(files, lines, entries)?, data)
+}
+
+/// The replacements turning the lines into the entries, in the order expected by
+/// `HistoryFile::replace`. The entries are compared rather than the text of the lines, so
+/// unchanged lines are kept exactly as they are, e.g. when they have no UTC offset, and so
+/// are the blank lines between them.
+fn changed_lines(
+    files: &Files,
+    lines: &[HistoryLine],
+    entries: &[HistoryEntry],
+) -> Result<Vec<LineReplacement>, ExecutionError> {
+    let previous: Vec<String> = lines.iter().map(|line| line.entry.to_string()).collect();
+    let current: Vec<String> = entries.iter().map(HistoryEntry::to_string).collect();
+
+    let mut replacements = Vec::new();
+    let mut shift: i64 = 0;
+    let mut old = 0;
+    let mut new = 0;
+
+    for (old_end, new_end) in common_lines(&previous, &current) {
+        if old_end == old && new_end == new {
+            old += 1;
+            new += 1;
+            continue;
+        }
+
+        let added = current[new..new_end].to_vec();
+        let mut hunk = Vec::new();
+
+        if old_end > old {
+            // Removed lines separated by blank lines are replaced separately, so the blank
+            // lines stay, and the added lines take the place of the first removed ones.
+            let mut first = old;
+
+            for index in old + 1..=old_end {
+                let is_adjacent =
+                    index < old_end && lines[index].offset == end_of(&lines[index - 1]);
+
+                if !is_adjacent {
+                    let replaced = if first == old {
+                        added.clone()
+                    } else {
+                        Vec::new()
+                    };
+                    let removed = lines[first..index]
+                        .iter()
+                        .map(|line| line.text.clone())
+                        .collect();
+                    hunk.push((lines[first].offset, removed, replaced));
+                    first = index;
+                }
+            }
+        } else if let Some(next) = lines.get(old) {
+            hunk.push((next.offset, Vec::new(), added));
+        } else if let Some(last) = lines.last() {
+            // Appending after the last line, which may lack a line break, is done by
+            // replacing it along with the added lines.
+            let kept = vec![last.text.clone()];
+            hunk.push((last.offset, kept.clone(), [kept, added].concat()));
+        } else {
+            hunk.push((files.history.size()?, Vec::new(), added));
+        }
+
+        for (offset, previous, current) in hunk {
+            let offset = (offset as i64 + shift) as u64;
+            shift += byte_count(&current) - byte_count(&previous);
+            replacements.push((offset, previous, current));
+        }
+
+        old = old_end + 1;
+        new = new_end + 1;
+    }
+
+    Ok(replacements)
+}
+
+/// The indices of the lines both versions have in common, in order, followed by their
+/// lengths, so every difference comes before one of the pairs.
+fn common_lines(previous: &[String], current: &[String]) -> Vec<(usize, usize)> {
+    let prefix = previous
+        .iter()
+        .zip(current)
+        .take_while(|(previous, current)| previous == current)
+        .count();
+
+    let suffix = previous[prefix..]
+        .iter()
+        .rev()
+        .zip(current[prefix..].iter().rev())
+        .take_while(|(previous, current)| previous == current)
+        .count();
+
+    let old = &previous[prefix..previous.len() - suffix];
+    let new = &current[prefix..current.len() - suffix];
+
+    // The longest common subsequence of what remains, which is usually small.
+    let mut lengths = vec![vec![0; new.len() + 1]; old.len() + 1];
+
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut pairs: Vec<(usize, usize)> = (0..prefix).map(|index| (index, index)).collect();
+    let (mut i, mut j) = (0, 0);
+
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            pairs.push((prefix + i, prefix + j));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    let old_suffix = previous.len() - suffix;
+    let new_suffix = current.len() - suffix;
+
+    pairs.extend((0..=suffix).map(|index| (old_suffix + index, new_suffix + index)));
+    pairs
+}
+
+/// The offset right after the line break of a line.
+fn end_of(line: &HistoryLine) -> u64 {
+    line.offset + line.text.len() as u64 + 1
+}
+
+/// The number of bytes the lines take in the file.
+fn byte_count(lines: &[String]) -> i64 {
+    lines.iter().map(|line| line.len() as i64 + 1).sum()
+}
+
+/// The text of a history.log file once the replacements are applied, as
+/// `HistoryFile::replace` would write it.
+fn splice(text: &str, replacements: &[LineReplacement]) -> String {
+    let mut text = text.to_string();
+
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+
+    for (offset, previous, current) in replacements {
+        let start = *offset as usize;
+        let end = start + byte_count(previous) as usize;
+        let current: String = current.iter().map(|line| format!("{}\n", line)).collect();
+        text.replace_range(start..end, &current);
+    }
+
+    text
+}
+
+/// Displays the time worked in the current day, week and month, and the time left to reach
+/// the goals. If the work is still going on, the time the goals will be reached is displayed,
+/// taking the planned breaks into account. The time worked in general is followed by the time
+/// spent on breaks.
+fn print_progress(
+    totals: &Totals,
+    mnemonic: Option<&str>,
+    data: &Data,
+    working: bool,
+    now: DateTime,
+) {
+    const PERIODS: [(&str, GoalPeriod); 3] = [
+        ("Today", GoalPeriod::Day),
+        ("This week", GoalPeriod::Week),
+        ("This month", GoalPeriod::Month),
+    ];
+
+    for &(label, kind) in &PERIODS {
+        let period = Period::containing(kind, now);
+        let worked = totals.worked(mnemonic, &period, now);
+        let paused = totals.break_time(&period, now);
+        let mut parts = vec![format!("{} worked", format_duration(worked))];
+
+        if mnemonic.is_none() && paused > Duration::zero() {
+            parts.push(format!("{} of breaks", format_duration(paused)));
+        }
+
+        if let Some(goal) = current_goal(data, mnemonic, kind, now) {
+            parts.push(remaining(goal, worked));
+
+            if working && goal > worked {
+                let end = forecast(&data.general.breaks, now, goal - worked);
+
+                if end - now <= config::current().forecast_limit() {
+                    parts.push(format!("until {}", end_time(end, now)));
+                }
+            }
+        }
+
+        println!("{}: {}", label, parts.join(", "));
+    }
+}
+
+/// The goal of a task or of the work in general for the period containing the given date/time.
+/// General goals take the absences into account.
+fn current_goal(
+    data: &Data,
+    mnemonic: Option<&str>,
+    kind: GoalPeriod,
+    datetime: DateTime,
+) -> Option<Duration> {
+    match mnemonic {
+        Some(mnemonic) => data
+            .goals(Some(mnemonic))
+            .and_then(|goals| goal_for(goals, kind, datetime)),
+        None => general_goal_for(&data.general, kind, datetime),
+    }
+}
+
+/// The weeks counted by the flex-time balance, from its first day until yesterday.
+fn balance_weeks(
+    data: &Data,
+    totals: &Totals,
+    first: NaiveDate,
+    now: DateTime,
+) -> Vec<BalanceWeek> {
+    report::balance(
+        &data.general,
+        |period| totals.worked(None, period, now),
+        first,
+        workday(now),
+    )
+}
+
+/// Formats a balance with its sign, e.g. `+1h 30m`.
+fn format_balance(balance: Duration) -> String {
+    if balance > Duration::zero() {
+        format!("+{}", format_duration(balance))
+    } else {
+        format_duration(balance)
+    }
+}
+
+fn remaining(goal: Duration, worked: Duration) -> String {
+    if worked >= goal {
+        String::from("goal reached")
+    } else {
+        format!("{} left", format_duration(goal - worked))
+    }
+}
+
+/// Formats a date/time in the future, including the day only if it isn't today.
+fn end_time(datetime: DateTime, now: DateTime) -> String {
+    if datetime.date() == now.date() {
+        datetime.format("%H:%M").to_string()
+    } else {
+        datetime.format("%a %d %H:%M").to_string()
+    }
+}
+
+/// The dates of an absence followed by its type and name, e.g.
+/// `2019-07-01 to 2019-07-12: vacation`.
+fn describe_absence(absence: &Absence) -> String {
+    let mut parts = vec![absence.kind.to_string()];
+
+    if absence.half {
+        parts.push(String::from("half day"));
+    }
+
+    if let Some(name) = &absence.name {
+        parts.push(name.clone());
+    }
+
+    if absence.start == absence.end {
+        format!("{}: {}", absence.start.format("%Y-%m-%d"), parts.join(", "))
+    } else {
+        format!(
+            "{} to {}: {}",
+            absence.start.format("%Y-%m-%d"),
+            absence.end.format("%Y-%m-%d"),
+            parts.join(", ")
+        )
+    }
+}
+
+/// The title of a task followed by its mnemonic and code, or just the mnemonic if the task
+/// doesn't exist, e.g. for namespaces.
+fn describe_task(data: &Data, mnemonic: &str) -> String {
+    match data.task(mnemonic) {
+        Some(Task {
+            title,
+            code: Some(code),
+            ..
+        }) => format!("{} ({}, {})", title, mnemonic, code),
+        Some(task) => format!("{} ({})", task.title, mnemonic),
+        None => mnemonic.to_string(),
+    }
+}
+
+/// The history entries with the ones about a task changed to refer to another one,
+/// along with the number of changed entries.
+fn retarget_entries(lines: &[HistoryLine], from: &str, into: &str) -> (Vec<HistoryEntry>, usize) {
+    let mut count = 0;
+
+    let entries = lines
+        .iter()
+        .map(|line| {
+            let mut entry = line.entry.clone();
+
+            if entry.is_about(from) {
+                entry.set_mnemonic(into);
+                count += 1;
+            }
+
+            entry
+        })
+        .collect();
+
+    (entries, count)
+}
+
+/// The text of a file, empty if it doesn't exist.
+fn read_text(path: &Path) -> io::Result<String> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => Ok(text),
+        Err(ref error) if error.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+        Err(error) => Err(error),
+    }
+}
+
+/// Copies a file to a new one with the current date/time in its name.
+fn backup(path: &Path) -> Result<PathBuf, ExecutionError> {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(Local::now().format(".%Y%m%d-%H%M%S.bak").to_string());
+
+    let backup = path.with_file_name(name);
+    std::fs::copy(path, &backup)?;
+    Ok(backup)
+}
+
+fn check_task_exists(files: &Files, mnemonic: &str) -> Result<(), ExecutionError> {
+    match files.data.load()?.task(mnemonic) {
+        Some(_) => Ok(()),
+        None => Err(ExecutionError::UnknownTask(mnemonic.to_string())),
+    }
+}
+
+/// Finds the last entry at or before the given date/time that satisfies the predicate.
+fn last_entry_before<P>(
+    files: &Files,
+    datetime: DateTime,
+    predicate: P,
+) -> Result<Option<HistoryEntry>, ExecutionError>
+where
+    P: Fn(&HistoryEntry) -> bool,
+{
+    for line in files.history.read_rev()? {
+        let entry = line?.entry;
+
+        if entry.datetime().is_some_and(|other| other > datetime) {
+            continue;
+        }
+
+        if predicate(&entry) {
+            return Ok(Some(entry));
+        }
+    }
+
+    Ok(None)
+}
+
+/// The task with an open session at the given date/time, if any.
+fn open_task_at(entries: &[HistoryEntry], datetime: DateTime) -> Option<String> {
+    let end = chronological_position(entries, datetime);
+
+    sessions(&entries[..end])
+        .into_iter()
+        .rev()
+        .find(Session::is_open)
+        .map(|session| session.mnemonic)
+}
+
+/// The last `start` or `stop` entry of the task at or before the given date/time.
+fn last_session_entry<'a>(
+    entries: &'a [HistoryEntry],
+    mnemonic: &str,
+    datetime: DateTime,
+) -> Option<&'a HistoryEntry> {
+    entries[..chronological_position(entries, datetime)]
+        .iter()
+        .rev()
+        .find(|entry| match entry {
+            HistoryEntry::Start { .. } | HistoryEntry::Stop { .. } => entry.is_about(mnemonic),
+            _ => false,
+        })
+}
+
+/// Adds the entry at its chronological place, after the entries at the same date/time, as
+/// `HistoryFile::insert` does. Returns its index.
+fn insert_entry(entries: &mut Vec<HistoryEntry>, entry: HistoryEntry) -> usize {
+    let datetime = entry
+        .datetime()
+        .expect("Entries without date/time must be inserted at an explicit index!");
+
+    let index = chronological_position(entries, datetime);
+    entries.insert(index, entry);
+    index
+}
+
+/// The task with an open session, i.e. the last one started and not stopped yet.
+fn current_task(files: &Files) -> Result<Option<String>, ExecutionError> {
+    let mut stopped: Vec<String> = Vec::new();
+
+    for line in files.history.read_rev()? {
+        match line?.entry {
+            HistoryEntry::Stop { mnemonic, .. } => stopped.push(mnemonic),
+            HistoryEntry::Start { mnemonic, .. } if !stopped.contains(&mnemonic) => {
+                return Ok(Some(mnemonic));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(None)
+}
+
+/// Shows the last relevant entry to the user and asks whether to proceed anyway.
+fn confirm_after(files: &Files, last: &HistoryEntry) -> Result<bool, ExecutionError> {
+    println!("The last entry is: {}", last);
+    files.confirm("Proceed anyway?")
+}
+
+/// The number of lines at or before the given date/time. Entries without a date/time belong
+/// to the same moment as the entry before them.
+fn end_before(lines: &[HistoryLine], datetime: DateTime) -> usize {
+    lines
+        .iter()
+        .position(|line| line.entry.datetime().is_some_and(|other| other > datetime))
+        .unwrap_or(lines.len())
+}
+
+fn is_stop_of(entry: &HistoryEntry, mnemonic: &str) -> bool {
+    match entry {
+        HistoryEntry::Stop { .. } => entry.is_about(mnemonic),
+        _ => false,
+    }
+}
+
+fn is_commit_of(entry: &HistoryEntry, mnemonic: &str) -> bool {
+    match entry {
+        HistoryEntry::Commit { .. } => entry.is_about(mnemonic),
+        _ => false,
+    }
+}
+
+/// Total time worked on a task within the given entries. Open sessions last until `now`.
+fn worked_time<'a, I>(entries: I, mnemonic: &str, now: DateTime) -> Duration
+where
+    I: IntoIterator<Item = &'a HistoryEntry>,
+{
+    sessions(entries)
+        .iter()
+        .filter(|session| session.mnemonic == mnemonic)
+        .fold(Duration::zero(), |total, session| {
+            total + session.duration(now)
+        })
+}
+
+//==============================================================================
+//
+//                         Conversions Between Error Types
+//
+//==============================================================================
+
+impl From<io::Error> for ExecutionError {
+    fn from(error: io::Error) -> ExecutionError {
+        ExecutionError::Io(error)
+    }
+}
+
+impl From<HistoryError> for ExecutionError {
+    fn from(error: HistoryError) -> ExecutionError {
+        ExecutionError::History(error)
+    }
+}
+
+impl From<DataError> for ExecutionError {
+    fn from(error: DataError) -> ExecutionError {
+        ExecutionError::Data(error)
+    }
+}
+
+impl From<JournalError> for ExecutionError {
+    fn from(error: JournalError) -> ExecutionError {
+        ExecutionError::Journal(error)
+    }
+}
+
+impl From<ConfigError> for ExecutionError {
+    fn from(error: ConfigError) -> ExecutionError {
+        ExecutionError::Config(error)
+    }
+}
+
+impl From<OrgParseError> for ExecutionError {
+    fn from(error: OrgParseError) -> ExecutionError {
+        ExecutionError::OrgParseError(error)
+    }
+}
+
+impl From<IcalParseError> for ExecutionError {
+    fn from(error: IcalParseError) -> ExecutionError {
+        ExecutionError::IcalParseError(error)
+    }
+}
+
+//==============================================================================
+//
+//                           Display Implementations
+//
+//==============================================================================
+
+impl Display for ExecutionError {
+    fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FormatError> {
+        write!(f, "{}: ", config::current().paint(Color::Red, "error"))?;
+
+        match self {
+            ExecutionError::Io(error) => write!(f, "{}", error),
+            ExecutionError::History(error) => {
+                writeln!(f, "could not access the history file.")?;
+                write!(f, "cause: {}", error)
+            }
+            ExecutionError::Data(error) => {
+                writeln!(f, "could not access the data file.")?;
+                write!(f, "cause: {}", error)
+            }
+            ExecutionError::Journal(error) => {
+                writeln!(f, "could not access the undo journal.")?;
+                write!(f, "cause: {}", error)
+            }
+            ExecutionError::Config(error) => {
+                writeln!(f, "could not use the configuration.")?;
+                write!(f, "cause: {}", error)
+            }
+            ExecutionError::OrgParseError(error) => {
+                writeln!(f, "could not parse the Org file.")?;
+                write!(f, "cause: {}", error)
+            }
+            ExecutionError::IcalParseError(error) => {
+                writeln!(f, "could not parse the iCalendar file.")?;
+                write!(f, "cause: {}", error)
+            }
+            ExecutionError::InvalidMnemonic(mnemonic) => {
+                writeln!(f, "invalid mnemonic: {}", mnemonic)?;
+                write!(
+                    f,
+                    "mnemonics are restricted to letters, numbers and dashes, with slashes separating namespaces, e.g. project/feature."
+                )
+            }
+            ExecutionError::UnknownTask(mnemonic) => write!(f, "unknown task: {}", mnemonic),
+            ExecutionError::UnknownClient(client) => {
+                write!(f, "no task is assigned to the client: {}", client)
+            }
+            ExecutionError::InvalidProfileName(name) => {
+                writeln!(f, "invalid profile name: {}", name)?;
+                write!(
+                    f,
+                    "profile names are restricted to letters, numbers and dashes."
+                )
+            }
+            ExecutionError::UnknownProfile(name) => {
+                writeln!(f, "unknown profile: {}", name)?;
+                write!(f, "create it with: timelog profile create {}", name)
+            }
+            ExecutionError::ProfileAlreadyExists(name) => {
+                write!(f, "profile already exists: {}", name)
+            }
+            ExecutionError::TaskAlreadyExists(mnemonic) => {
+                write!(f, "task already exists: {}", mnemonic)
+            }
+            ExecutionError::TaskHasHistory(mnemonic) => {
+                writeln!(f, "task has history entries: {}", mnemonic)?;
+                write!(
+                    f,
+                    "use --archive to deactivate it or --purge to remove its history too."
+                )
+            }
+            ExecutionError::TaskIsOpen(mnemonic) => {
+                write!(f, "task is currently open: {}", mnemonic)
+            }
+            ExecutionError::NoOpenTask => write!(f, "no task is currently open"),
+            ExecutionError::UnknownEntry(entry) => write!(f, "unknown history entry: {}", entry),
+            ExecutionError::NotApplicable(reason) => {
+                write!(f, "the change is not applicable: {}", reason)
+            }
+            ExecutionError::UnmatchedEntry(entry) => {
+                writeln!(f, "the change would leave an unmatched entry:")?;
+                write!(f, "{}", entry)
+            }
+            ExecutionError::ConcurrentChange => {
+                write!(
+                    f,
+                    "the files were changed by another command in the meantime, please try again"
+                )
+            }
+            ExecutionError::Locked(path) => {
+                writeln!(f, "the files are being used by another timelog instance.")?;
+                write!(
+                    f,
+                    "cause: {} is still locked after {} seconds, try again once the other \
+                     instance has finished.",
+                    path.display(),
+                    LOCK_TIMEOUT.as_secs()
+                )
+            }
+            ExecutionError::UndoConflict(what) => {
+                writeln!(f, "could not undo the last changes.")?;
+                write!(f, "cause: {} changed in the meantime", what)
+            }
+        }
+    }
+}
+
+//==============================================================================
+//
+//                                   Tests
+//
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prompt::tests::answer;
+    use crate::tests::temp_dir;
+    use crate::tests::use_berlin_time;
+    use crate::DeleteMode;
+    use crate::GoalPeriod;
+
+    use core::str::FromStr;
+
+    use std::fs;
+
+    /// Files with the given history and a task for each mnemonic.
+    fn files(name: &str, tasks: &[&str], history: &[&str]) -> Files {
+        let files = Files::new(temp_dir(name));
+        let mut data = files.data.load().ok().unwrap();
+
+        for mnemonic in tasks {
+            data.add_task(mnemonic, Task::new(mnemonic.to_string(), None));
+        }
+
+        files.data.save(&data).ok().unwrap();
+
+        let text: String = history.iter().map(|line| format!("{}\n", line)).collect();
+        fs::write(files.history.path(), text).unwrap();
+
+        files
+    }
+
+    fn history(files: &Files) -> Vec<String> {
+        fs::read_to_string(files.history.path())
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// A date/time as written in the history.
+    fn at(datetime: &str) -> ForgetableDateTime {
+        HistoryEntry::from_str(&format!("enter {}", datetime))
+            .ok()
+            .and_then(|entry| entry.forgettable_datetime())
+            .unwrap()
+    }
+
+    #[test]
+    fn stop_closes_the_open_task() {
+        use_berlin_time();
+        let files = files(
+            "execution-stop",
+            &["dev", "ops"],
+            &[
+                "start dev 2026-06-10 9:00 +02:00",
+                "stop dev 2026-06-10 10:00 +02:00",
+                "start ops 2026-06-10 10:00 +02:00",
+            ],
+        );
+
+        stop_or_end_interruption(&files, None, at("2026-06-10 12:00 +02:00"), None, false)
+            .ok()
+            .unwrap();
+        assert_eq!(history(&files)[3], "stop ops 2026-06-10 12:00 +02:00");
+
+        assert!(matches!(
+            stop_or_end_interruption(&files, None, at("2026-06-10 13:00 +02:00"), None, false),
+            Err(ExecutionError::NoOpenTask)
+        ));
+    }
+
+    #[test]
+    fn commit_goes_right_after_the_last_stop() {
+        use_berlin_time();
+        let files = files(
+            "execution-commit",
+            &["dev", "ops"],
+            &[
+                "start dev 2026-06-10 9:00 +02:00",
+                "stop dev 2026-06-10 10:00 +02:00",
+                "start ops 2026-06-10 10:00 +02:00",
+                "stop ops 2026-06-10 11:00 +02:00",
+                "start dev 2026-06-10 14:00 +02:00",
+            ],
+        );
+
+        let now = at("2026-06-10 12:00 +02:00").datetime;
+
+        commit(&files, "dev", now, None).ok().unwrap();
+        assert_eq!(history(&files)[2], "commit dev");
+
+        // There's nothing new to commit until the next stop.
+        commit(&files, "dev", now, None).ok().unwrap();
+        assert_eq!(history(&files).len(), 6);
+    }
+
+    #[test]
+    fn unknown_tasks_are_refused() {
+        use_berlin_time();
+        let files = files("execution-unknown", &["dev"], &[]);
+
+        assert!(matches!(
+            start(&files, "ops", at("2026-06-10 9:00 +02:00"), None),
+            Err(ExecutionError::UnknownTask(_))
+        ));
+        assert!(matches!(
+            commit(&files, "ops", at("2026-06-10 9:00 +02:00").datetime, None),
+            Err(ExecutionError::UnknownTask(_))
+        ));
+    }
+
+    #[test]
+    fn import_skips_the_sessions_already_there() {
+        use_berlin_time();
+        let files = files(
+            "execution-import",
+            &["dev"],
+            &[
+                "start dev 2026-06-10 9:00 +02:00",
+                "stop dev 2026-06-10 11:00 +02:00",
+            ],
+        );
+
+        let path = files.history.path().with_file_name("work.org");
+
+        fs::write(
+            &path,
+            "* Development\n\
+             :PROPERTIES:\n\
+             :MNEMONIC: dev\n\
+             :END:\n\
+             CLOCK: [2026-06-11 Thu 09:00]--[2026-06-11 Thu 10:00] =>  1:00\n\
+             CLOCK: [2026-06-10 Wed 09:00]--[2026-06-10 Wed 11:00] =>  2:00\n\
+             * Support\n\
+             :PROPERTIES:\n\
+             :MNEMONIC: ops\n\
+             :CODE: OPS-2\n\
+             :END:\n\
+             CLOCK: [2026-06-11 Thu 10:00]--[2026-06-11 Thu 10:30] =>  0:30\n",
+        )
+        .unwrap();
+
+        let path = path.to_str().unwrap();
+
+        import(&files, ExchangeFormat::Org, path).ok().unwrap();
+
+        let imported = vec![
+            "start dev 2026-06-10 9:00 +02:00",
+            "stop dev 2026-06-10 11:00 +02:00",
+            "start dev 2026-06-11 9:00 +02:00",
+            "stop dev 2026-06-11 10:00 +02:00",
+            "start ops 2026-06-11 10:00 +02:00",
+            "stop ops 2026-06-11 10:30 +02:00",
+        ];
+
+        assert_eq!(history(&files), imported);
+
+        let data = files.data.load().ok().unwrap();
+        let task = data.task("ops").unwrap();
+
+        assert_eq!(task.title, "Support");
+        assert_eq!(task.code.as_deref(), Some("OPS-2"));
+        assert!(!task.active);
+
+        import(&files, ExchangeFormat::Org, path).ok().unwrap();
+        assert_eq!(history(&files), imported);
+    }
+
+    #[test]
+    fn import_skips_overlapping_sessions() {
+        use_berlin_time();
+        let files = files(
+            "execution-import-overlap",
+            &["dev"],
+            &[
+                "start dev 2026-06-10 9:00 +02:00",
+                "stop dev 2026-06-10 11:00 +02:00",
+            ],
+        );
+
+        let path = files.history.path().with_file_name("work.org");
+
+        fs::write(
+            &path,
+            "* Development\n\
+             :PROPERTIES:\n\
+             :MNEMONIC: dev\n\
+             :END:\n\
+             CLOCK: [2026-06-10 Wed 10:00]--[2026-06-10 Wed 12:00] =>  2:00\n\
+             CLOCK: [2026-06-10 Wed 11:00]--[2026-06-10 Wed 12:00] =>  1:00\n\
+             CLOCK: [2026-06-10 Wed 11:30]--[2026-06-10 Wed 13:00] =>  1:30\n\
+             * Support\n\
+             :PROPERTIES:\n\
+             :MNEMONIC: ops\n\
+             :END:\n\
+             CLOCK: [2026-06-10 Wed 10:00]--[2026-06-10 Wed 10:30] =>  0:30\n",
+        )
+        .unwrap();
+
+        import(&files, ExchangeFormat::Org, path.to_str().unwrap())
+            .ok()
+            .unwrap();
+
+        assert_eq!(
+            history(&files),
+            vec![
+                "start dev 2026-06-10 9:00 +02:00",
+                "start ops 2026-06-10 10:00 +02:00",
+                "stop ops 2026-06-10 10:30 +02:00",
+                "stop dev 2026-06-10 11:00 +02:00",
+                "start dev 2026-06-10 11:00 +02:00",
+                "stop dev 2026-06-10 12:00 +02:00",
+            ]
+        );
+
+        // The sessions and the new task are undone at once.
+        answer(&["y"]);
+        undo(&files).ok().unwrap();
+
+        assert_eq!(history(&files).len(), 2);
+        assert!(files.data.load().ok().unwrap().task("ops").is_none());
+    }
+
+    #[test]
+    fn undo_removes_the_inserted_entries() {
+        use_berlin_time();
+        let files = files(
+            "execution-undo-insert",
+            &["dev"],
+            &["start dev 2026-06-10 9:00 +02:00"],
+        );
+
+        stop_or_end_interruption(&files, None, at("2026-06-10 12:00 +02:00"), None, true)
+            .ok()
+            .unwrap();
+        assert_eq!(history(&files).len(), 3);
+
+        answer(&["n"]);
+        undo(&files).ok().unwrap();
+        assert_eq!(history(&files).len(), 3);
+
+        answer(&["y"]);
+        undo(&files).ok().unwrap();
+        assert_eq!(history(&files), vec!["start dev 2026-06-10 9:00 +02:00"]);
+
+        // The journal is emptied along with the changes, so they can't be reverted twice.
+        assert!(files
+            .journal
+            .load()
+            .ok()
+            .unwrap()
+            .unwrap()
+            .changes
+            .is_empty());
+
+        undo(&files).ok().unwrap();
+        assert_eq!(history(&files), vec!["start dev 2026-06-10 9:00 +02:00"]);
+    }
+
+    #[test]
+    fn undo_reverts_task_and_general_changes() {
+        use_berlin_time();
+        let files = files("execution-undo-data", &["dev", "ops"], &[]);
+
+        answer(&["Development"]);
+        edit(&files, "dev", Some("DEV-1")).ok().unwrap();
+
+        answer(&["y"]);
+        undo(&files).ok().unwrap();
+
+        let data = files.data.load().ok().unwrap();
+        let task = data.task("dev").unwrap();
+
+        assert_eq!(task.title, "dev");
+        assert_eq!(task.code, None);
+        assert_eq!(data.tasks.get_index_of("dev"), Some(0));
+
+        let files = Files::new(files.history.path().parent().unwrap());
+
+        goal(
+            &files,
+            GoalAction::Set(GoalPeriod::Day, Duration::hours(8)),
+            None,
+        )
+        .ok()
+        .unwrap();
+
+        answer(&["y"]);
+        undo(&files).ok().unwrap();
+        assert!(files.data.load().ok().unwrap().general.goals.is_empty());
+    }
+
+    #[test]
+    fn undo_refuses_what_changed_afterwards() {
+        use_berlin_time();
+        let files = files("execution-undo-conflict", &["dev"], &[]);
+
+        answer(&["Development"]);
+        edit(&files, "dev", None).ok().unwrap();
+
+        let mut data = files.data.load().ok().unwrap();
+        data.task_mut("dev").unwrap().code = Some("DEV-1".to_string());
+        files.data.save(&data).ok().unwrap();
+
+        answer(&["y"]);
+        assert!(matches!(
+            undo(&files),
+            Err(ExecutionError::UndoConflict(ref what)) if what == "task dev"
+        ));
+
+        let files = Files::new(files.history.path().parent().unwrap());
+
+        goal(
+            &files,
+            GoalAction::Set(GoalPeriod::Day, Duration::hours(8)),
+            None,
+        )
+        .ok()
+        .unwrap();
+
+        let mut data = files.data.load().ok().unwrap();
+        data.general.last_invoice = 7;
+        files.data.save(&data).ok().unwrap();
+
+        answer(&["y"]);
+        assert!(matches!(
+            undo(&files),
+            Err(ExecutionError::UndoConflict(ref what)) if what == "general invoice number"
+        ));
+
+        let files = Files::new(files.history.path().parent().unwrap());
+
+        start(&files, "dev", at("2026-06-10 9:00 +02:00"), None)
+            .ok()
+            .unwrap();
+        fs::write(files.history.path(), "start dev 2026-06-10 9:30 +02:00\n").unwrap();
+
+        answer(&["y"]);
+        assert!(matches!(
+            undo(&files),
+            Err(ExecutionError::UndoConflict(ref what)) if what == "history entries"
+        ));
+        assert_eq!(history(&files), vec!["start dev 2026-06-10 9:30 +02:00"]);
+    }
+
+    fn workday(name: &str) -> Files {
+        files(
+            name,
+            &["dev", "ops"],
+            &[
+                "enter 2026-06-10 8:30 +02:00",
+                "start dev 2026-06-10 9:00 +02:00",
+                "stop dev 2026-06-10 10:00 +02:00",
+                "commit dev",
+                "start ops 2026-06-10 10:30 +02:00",
+                "stop ops 2026-06-10 11:00 +02:00",
+                "exit 2026-06-10 17:00 +02:00",
+            ],
+        )
+    }
+
+    fn change_datetime(datetime: &str) -> AmendAction<'static> {
+        AmendAction::Change {
+            datetime: Some(at(datetime).datetime),
+            forgotten: false,
+            mnemonic: None,
+        }
+    }
+
+    #[test]
+    fn amend_keeps_the_chronological_order() {
+        use_berlin_time();
+        let files = workday("execution-amend-move");
+
+        amend(
+            &files,
+            EntrySelector::Index(5),
+            change_datetime("2026-06-10 10:45 +02:00"),
+        )
+        .ok()
+        .unwrap();
+
+        assert_eq!(
+            history(&files),
+            vec![
+                "enter 2026-06-10 8:30 +02:00",
+                "start dev 2026-06-10 9:00 +02:00",
+                "start ops 2026-06-10 10:30 +02:00",
+                "stop dev 2026-06-10 10:45 +02:00",
+                "commit dev",
+                "stop ops 2026-06-10 11:00 +02:00",
+                "exit 2026-06-10 17:00 +02:00",
+            ]
+        );
+
+        answer(&["y"]);
+        undo(&files).ok().unwrap();
+        assert_eq!(
+            history(&files),
+            history(&workday("execution-amend-original"))
+        );
+    }
+
+    #[test]
+    fn amend_refuses_a_stop_before_its_start() {
+        use_berlin_time();
+        let files = workday("execution-amend-refused");
+
+        assert!(matches!(
+            amend(&files, EntrySelector::Index(5), change_datetime("2026-06-10 8:45 +02:00")),
+            Err(ExecutionError::UnmatchedEntry(ref entry)) if entry == "stop dev 2026-06-10 8:45 +02:00"
+        ));
+        assert!(matches!(
+            amend(
+                &files,
+                EntrySelector::Index(7),
+                AmendAction::Change {
+                    datetime: None,
+                    forgotten: false,
+                    mnemonic: Some("ops"),
+                }
+            ),
+            Err(ExecutionError::NotApplicable(_))
+        ));
+
+        assert_eq!(history(&files), history(&workday("execution-amend-kept")));
+    }
+
+    #[test]
+    fn amend_deletes_the_partner_if_agreed() {
+        use_berlin_time();
+        let files = workday("execution-amend-delete");
+
+        answer(&["n"]);
+        amend(&files, EntrySelector::Index(3), AmendAction::Delete)
+            .ok()
+            .unwrap();
+        assert_eq!(history(&files).len(), 7);
+
+        answer(&["y"]);
+        amend(&files, EntrySelector::Index(3), AmendAction::Delete)
+            .ok()
+            .unwrap();
+        amend(&files, EntrySelector::Index(2), AmendAction::Delete)
+            .ok()
+            .unwrap();
+
+        assert_eq!(
+            history(&files),
+            vec![
+                "enter 2026-06-10 8:30 +02:00",
+                "start dev 2026-06-10 9:00 +02:00",
+                "stop dev 2026-06-10 10:00 +02:00",
+                "exit 2026-06-10 17:00 +02:00",
+            ]
+        );
+    }
+
+    #[test]
+    fn amend_keeps_the_ids_of_the_other_entries() {
+        use_berlin_time();
+        let files = workday("execution-amend-ids");
+        let ids = entry_ids(&files.history.read().ok().unwrap());
+
+        amend(
+            &files,
+            EntrySelector::Id(&ids[0].to_uppercase()),
+            AmendAction::Change {
+                datetime: None,
+                forgotten: true,
+                mnemonic: None,
+            },
+        )
+        .ok()
+        .unwrap();
+
+        assert_eq!(history(&files)[0], "enter 2026-06-10 8:30 +02:00?");
+
+        let amended = entry_ids(&files.history.read().ok().unwrap());
+
+        assert_ne!(amended[0], ids[0]);
+        assert_eq!(amended[1..], ids[1..]);
+
+        assert!(matches!(
+            amend(&files, EntrySelector::Id(&ids[0]), AmendAction::Delete),
+            Err(ExecutionError::UnknownEntry(_))
+        ));
+        assert!(matches!(
+            amend(&files, EntrySelector::Index(8), AmendAction::Delete),
+            Err(ExecutionError::UnknownEntry(_))
+        ));
+    }
+
+    #[test]
+    fn amend_keeps_the_other_lines_as_they_are() {
+        use_berlin_time();
+        let files = files("execution-amend-raw", &["dev", "ops"], &[]);
+        let original = "enter 2026-06-10 8:30\n\
+                        start dev 2026-06-10 9:00 +02:00\n\
+                        \n\
+                        stop dev 2026-06-10 10:00 +02:00\n\
+                        commit dev\n\
+                        start ops 2026-06-10 10:30\n\
+                        stop ops 2026-06-10 11:00 +02:00\n\
+                        exit 2026-06-10 17:00 +02:00\n";
+        fs::write(files.history.path(), original).unwrap();
+
+        amend(
+            &files,
+            EntrySelector::Index(5),
+            change_datetime("2026-06-10 10:45 +02:00"),
+        )
+        .ok()
+        .unwrap();
+
+        // Only the moved lines are written, so the blank line and the lines without UTC
+        // offset are kept.
+        assert_eq!(
+            fs::read_to_string(files.history.path()).unwrap(),
+            "enter 2026-06-10 8:30\n\
+             start dev 2026-06-10 9:00 +02:00\n\
+             \n\
+             start ops 2026-06-10 10:30\n\
+             stop dev 2026-06-10 10:45 +02:00\n\
+             commit dev\n\
+             stop ops 2026-06-10 11:00 +02:00\n\
+             exit 2026-06-10 17:00 +02:00\n"
+        );
+
+        answer(&["y"]);
+        undo(&files).ok().unwrap();
+        assert_eq!(fs::read_to_string(files.history.path()).unwrap(), original);
+    }
+
+    #[test]
+    fn amend_appends_after_a_last_line_without_line_break() {
+        use_berlin_time();
+        let files = files("execution-amend-append", &["ops"], &[]);
+        let original = "start ops 2026-06-10 10:30 +02:00\n\
+                        stop ops 2026-06-10 11:00 +02:00\n\
+                        \n\
+                        exit 2026-06-10 17:00";
+        fs::write(files.history.path(), original).unwrap();
+
+        amend(
+            &files,
+            EntrySelector::Index(2),
+            change_datetime("2026-06-10 18:00 +02:00"),
+        )
+        .ok()
+        .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(files.history.path()).unwrap(),
+            "start ops 2026-06-10 10:30 +02:00\n\
+             \n\
+             exit 2026-06-10 17:00\n\
+             stop ops 2026-06-10 18:00 +02:00\n"
+        );
+    }
+
+    #[test]
+    fn resolve_sets_the_estimates() {
+        use_berlin_time();
+        let files = files(
+            "execution-resolve",
+            &["dev"],
+            &[
+                "enter 2026-06-10 8:30 +02:00?",
+                "start dev 2026-06-10 9:00 +02:00",
+                "stop dev 2026-06-10 12:00 +02:00?",
+            ],
+        );
+
+        // A time alone refers to the day the entry was registered.
+        answer(&["tomorrow", "11:30"]);
+        resolve(&files, Some("dev"), false).ok().unwrap();
+
+        answer(&[""]);
+        resolve(&files, None, false).ok().unwrap();
+
+        assert_eq!(
+            history(&files),
+            vec![
+                "enter 2026-06-10 8:30 +02:00?",
+                "start dev 2026-06-10 9:00 +02:00",
+                "stop dev 2026-06-10 11:30 +02:00",
+            ]
+        );
+    }
+
+    #[test]
+    fn delete_refuses_tasks_with_history() {
+        use_berlin_time();
+        let files = files(
+            "execution-delete",
+            &["dev", "ops", "new"],
+            &[
+                "start dev 2026-06-10 9:00 +02:00",
+                "stop dev 2026-06-10 10:00 +02:00",
+            ],
+        );
+
+        assert!(matches!(
+            delete(&files, "dev", DeleteMode::Default),
+            Err(ExecutionError::TaskHasHistory(_))
+        ));
+        assert!(matches!(
+            delete(&files, "old", DeleteMode::Default),
+            Err(ExecutionError::UnknownTask(_))
+        ));
+
+        delete(&files, "new", DeleteMode::Default).ok().unwrap();
+
+        let data = files.data.load().ok().unwrap();
+        let mnemonics: Vec<&String> = data.tasks.keys().collect();
+        assert_eq!(mnemonics, vec!["dev", "ops"]);
+    }
+
+    #[test]
+    fn delete_archives_tasks_unless_open() {
+        use_berlin_time();
+        let files = files(
+            "execution-delete-archive",
+            &["dev", "ops"],
+            &[
+                "start dev 2026-06-10 9:00 +02:00",
+                "stop dev 2026-06-10 10:00 +02:00",
+                "start ops 2026-06-10 10:00 +02:00",
+            ],
+        );
+
+        assert!(matches!(
+            delete(&files, "ops", DeleteMode::Archive),
+            Err(ExecutionError::TaskIsOpen(_))
+        ));
+
+        delete(&files, "dev", DeleteMode::Archive).ok().unwrap();
+
+        let data = files.data.load().ok().unwrap();
+        assert!(!data.task("dev").unwrap().active);
+        assert!(data.task("ops").unwrap().active);
+        assert_eq!(history(&files).len(), 3);
+    }
+
+    #[test]
+    fn delete_purges_the_history_if_agreed() {
+        use_berlin_time();
+        let files = files(
+            "execution-delete-purge",
+            &["dev", "ops"],
+            &[
+                "start dev 2026-06-10 9:00 +02:00",
+                "stop dev 2026-06-10 10:00 +02:00",
+                "commit dev",
+                "start ops 2026-06-10 10:00 +02:00",
+                "stop ops 2026-06-10 11:00 +02:00",
+            ],
+        );
+
+        answer(&["n"]);
+        delete(&files, "dev", DeleteMode::Purge).ok().unwrap();
+        assert_eq!(history(&files).len(), 5);
+
+        answer(&["y"]);
+        delete(&files, "dev", DeleteMode::Purge).ok().unwrap();
+
+        assert_eq!(
+            history(&files),
+            vec![
+                "start ops 2026-06-10 10:00 +02:00",
+                "stop ops 2026-06-10 11:00 +02:00"
+            ]
+        );
+        assert!(files.data.load().ok().unwrap().task("dev").is_none());
+
+        let mut backups: Vec<String> = fs::read_dir(files.history.path().parent().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.ends_with(".bak"))
+            .collect();
+
+        backups.sort();
+
+        assert_eq!(backups.len(), 2);
+        assert!(backups[0].starts_with("data.json."));
+        assert!(backups[1].starts_with("history.log."));
+    }
+
+    #[test]
+    fn rename_rewrites_the_history() {
+        use_berlin_time();
+        let files = files(
+            "execution-rename",
+            &["dev", "ops"],
+            &[
+                "start dev 2026-06-10 9:00 +02:00",
+                "stop dev 2026-06-10 10:00 +02:00",
+                "commit dev",
+                "start ops 2026-06-10 10:00 +02:00",
+            ],
+        );
+
+        assert!(matches!(
+            rename(&files, "dev", "ops"),
+            Err(ExecutionError::TaskAlreadyExists(_))
+        ));
+        assert!(matches!(
+            rename(&files, "dev", "dev team"),
+            Err(ExecutionError::InvalidMnemonic(_))
+        ));
+
+        rename(&files, "dev", "api").ok().unwrap();
+
+        assert_eq!(
+            history(&files),
+            vec![
+                "start api 2026-06-10 9:00 +02:00",
+                "stop api 2026-06-10 10:00 +02:00",
+                "commit api",
+                "start ops 2026-06-10 10:00 +02:00",
+            ]
+        );
+
+        let data = files.data.load().ok().unwrap();
+        let mnemonics: Vec<&String> = data.tasks.keys().collect();
+        assert_eq!(mnemonics, vec!["api", "ops"]);
+    }
+
+    #[test]
+    fn merge_asks_which_code_and_goals_to_keep() {
+        use_berlin_time();
+        let files = files(
+            "execution-merge",
+            &["dev", "api"],
+            &[
+                "start api 2026-06-10 9:00 +02:00",
+                "stop api 2026-06-10 10:00 +02:00",
+                "start dev 2026-06-10 10:00 +02:00",
+                "stop dev 2026-06-10 11:00 +02:00",
+            ],
+        );
+
+        let mut data = files.data.load().ok().unwrap();
+        let api = data.task_mut("api").unwrap();
+        api.code = Some("API-1".to_string());
+        api.goals.set(GoalPeriod::Day, Duration::hours(2));
+        api.goals.set(GoalPeriod::Week, Duration::hours(10));
+        let dev = data.task_mut("dev").unwrap();
+        dev.code = Some("DEV-1".to_string());
+        dev.goals.set(GoalPeriod::Day, Duration::hours(4));
+        files.data.save(&data).ok().unwrap();
+
+        // Keep the code of api, but not its daily goal.
+        answer(&["y", "n"]);
+        merge(&files, "api", "dev").ok().unwrap();
+
+        assert_eq!(
+            history(&files),
+            vec![
+                "start dev 2026-06-10 9:00 +02:00",
+                "stop dev 2026-06-10 10:00 +02:00",
+                "start dev 2026-06-10 10:00 +02:00",
+                "stop dev 2026-06-10 11:00 +02:00",
+            ]
+        );
+
+        let data = files.data.load().ok().unwrap();
+        let dev = data.task("dev").unwrap();
+
+        assert!(data.task("api").is_none());
+        assert_eq!(dev.code.as_deref(), Some("API-1"));
+        assert_eq!(dev.goals.get(GoalPeriod::Day), Some(Duration::hours(4)));
+        assert_eq!(dev.goals.get(GoalPeriod::Week), Some(Duration::hours(10)));
+    }
+
+    #[test]
+    fn merge_refuses_overlapping_sessions() {
+        use_berlin_time();
+        let files = files(
+            "execution-merge-overlap",
+            &["dev", "api"],
+            &[
+                "start dev 2026-06-10 9:00 +02:00",
+                "start api 2026-06-10 9:30 +02:00",
+                "stop api 2026-06-10 10:00 +02:00",
+                "stop dev 2026-06-10 11:00 +02:00",
+            ],
+        );
+
+        assert!(matches!(
+            merge(&files, "api", "dev"),
+            Err(ExecutionError::UnmatchedEntry(ref entry)) if entry == "start dev 2026-06-10 9:30 +02:00"
+        ));
+        assert!(matches!(
+            merge(&files, "dev", "dev"),
+            Err(ExecutionError::NotApplicable(_))
+        ));
+
+        assert_eq!(history(&files)[1], "start api 2026-06-10 9:30 +02:00");
+        assert!(files.data.load().ok().unwrap().task("api").is_some());
+    }
+
+    #[test]
+    fn notes_go_along_with_the_entries() {
+        use_berlin_time();
+        let files = files("execution-notes", &["dev"], &[]);
+
+        start(&files, "dev", at("2026-06-10 9:00 +02:00"), Some("Review"))
+            .ok()
+            .unwrap();
+        stop_or_end_interruption(
+            &files,
+            None,
+            at("2026-06-10 10:00 +02:00"),
+            Some("Done"),
+            false,
+        )
+        .ok()
+        .unwrap();
+        commit(
+            &files,
+            "dev",
+            at("2026-06-10 11:00 +02:00").datetime,
+            Some("Logged"),
+        )
+        .ok()
+        .unwrap();
+
+        assert_eq!(
+            history(&files),
+            vec![
+                r#"start dev 2026-06-10 9:00 +02:00 "Review""#,
+                r#"stop dev 2026-06-10 10:00 +02:00 "Done""#,
+                r#"commit dev "Logged""#,
+            ]
+        );
+    }
+
+    #[test]
+    fn a_break_suspends_the_open_task() {
+        use_berlin_time();
+        let files = files(
+            "execution-pause",
+            &["dev"],
+            &[
+                "enter 2026-06-10 8:30 +02:00",
+                "start dev 2026-06-10 9:00 +02:00",
+            ],
+        );
+
+        resume(&files, at("2026-06-10 11:00 +02:00")).ok().unwrap();
+        assert_eq!(history(&files).len(), 2);
+
+        pause(&files, at("2026-06-10 12:00 +02:00")).ok().unwrap();
+        resume(&files, at("2026-06-10 12:45 +02:00")).ok().unwrap();
+
+        assert_eq!(
+            history(&files)[2..],
+            [
+                "stop dev 2026-06-10 12:00 +02:00",
+                "pause 2026-06-10 12:00 +02:00",
+                "resume 2026-06-10 12:45 +02:00",
+                "start dev 2026-06-10 12:45 +02:00",
+            ]
+        );
+    }
+
+    #[test]
+    fn resuming_leaves_stopped_tasks_alone() {
+        use_berlin_time();
+        let files = files(
+            "execution-pause-stopped",
+            &["dev"],
+            &["start dev 2026-06-10 9:00 +02:00"],
+        );
+
+        stop_or_end_interruption(&files, None, at("2026-06-10 12:00 +02:00"), None, false)
+            .ok()
+            .unwrap();
+        pause(&files, at("2026-06-10 12:00 +02:00")).ok().unwrap();
+        resume(&files, at("2026-06-10 12:45 +02:00")).ok().unwrap();
+
+        assert_eq!(
+            history(&files),
+            vec![
+                "start dev 2026-06-10 9:00 +02:00",
+                "stop dev 2026-06-10 12:00 +02:00",
+                "pause 2026-06-10 12:00 +02:00",
+                "resume 2026-06-10 12:45 +02:00",
+            ]
+        );
+        assert!(files.data.load().ok().unwrap().general.paused.is_none());
+    }
+
+    #[test]
+    fn pauses_are_undone_along_with_the_paused_task() {
+        use_berlin_time();
+        let files = files(
+            "execution-pause-undo",
+            &["dev"],
+            &["start dev 2026-06-10 9:00 +02:00"],
+        );
+
+        let paused = |files: &Files| files.data.load().ok().unwrap().general.paused;
+
+        pause(&files, at("2026-06-10 12:00 +02:00")).ok().unwrap();
+        assert_eq!(paused(&files).as_deref(), Some("dev"));
+
+        answer(&["y"]);
+        undo(&files).ok().unwrap();
+
+        assert_eq!(history(&files), vec!["start dev 2026-06-10 9:00 +02:00"]);
+        assert!(paused(&files).is_none());
+    }
+
+    #[test]
+    fn switch_stops_and_starts_at_once() {
+        use_berlin_time();
+        let files = files(
+            "execution-switch",
+            &["dev", "ops"],
+            &["start dev 2026-06-10 9:00 +02:00"],
+        );
+
+        assert!(matches!(
+            switch(&files, "dev", at("2026-06-10 10:00 +02:00"), None, false),
+            Err(ExecutionError::TaskIsOpen(_))
+        ));
+
+        switch(
+            &files,
+            "ops",
+            at("2026-06-10 10:00 +02:00"),
+            Some("Done"),
+            true,
+        )
+        .ok()
+        .unwrap();
+
+        assert_eq!(
+            history(&files),
+            vec![
+                "start dev 2026-06-10 9:00 +02:00",
+                r#"stop dev 2026-06-10 10:00 +02:00 "Done""#,
+                "commit dev",
+                "start ops 2026-06-10 10:00 +02:00",
+            ]
+        );
+
+        stop_or_end_interruption(&files, None, at("2026-06-10 11:00 +02:00"), None, false)
+            .ok()
+            .unwrap();
+
+        assert!(matches!(
+            switch(&files, "dev", at("2026-06-10 12:00 +02:00"), None, false),
+            Err(ExecutionError::NoOpenTask)
+        ));
+    }
+
+    #[test]
+    fn switch_asks_before_writing_anything() {
+        use_berlin_time();
+        let original = [
+            "start ops 2026-06-10 9:00 +02:00",
+            "start dev 2026-06-10 9:30 +02:00",
+        ];
+        let files = files("execution-switch-ask", &["dev", "ops"], &original);
+
+        answer(&["n"]);
+        switch(&files, "ops", at("2026-06-10 10:00 +02:00"), None, false)
+            .ok()
+            .unwrap();
+        assert_eq!(history(&files), original);
+
+        answer(&["y"]);
+        switch(&files, "ops", at("2026-06-10 10:00 +02:00"), None, false)
+            .ok()
+            .unwrap();
+        assert_eq!(
+            history(&files),
+            vec![
+                "start ops 2026-06-10 9:00 +02:00",
+                "start dev 2026-06-10 9:30 +02:00",
+                "stop dev 2026-06-10 10:00 +02:00",
+                "start ops 2026-06-10 10:00 +02:00",
+            ]
+        );
+
+        // Both entries were written at once, so they're reverted at once.
+        answer(&["y"]);
+        undo(&files).ok().unwrap();
+        assert_eq!(history(&files), original);
+    }
+
+    #[test]
+    fn start_offers_to_stop_the_open_task() {
+        use_berlin_time();
+        let files = files(
+            "execution-start-open",
+            &["dev", "ops", "web"],
+            &["start dev 2026-06-10 9:00 +02:00"],
+        );
+
+        answer(&["y"]);
+        start(&files, "ops", at("2026-06-10 10:00 +02:00"), None)
+            .ok()
+            .unwrap();
+
+        answer(&["n"]);
+        start(&files, "web", at("2026-06-10 11:00 +02:00"), None)
+            .ok()
+            .unwrap();
+
+        assert_eq!(
+            history(&files),
+            vec![
+                "start dev 2026-06-10 9:00 +02:00",
+                "stop dev 2026-06-10 10:00 +02:00",
+                "start ops 2026-06-10 10:00 +02:00",
+                "start web 2026-06-10 11:00 +02:00",
+            ]
+        );
+    }
+
+    #[test]
+    fn interruptions_resume_the_suspended_tasks() {
+        use_berlin_time();
+        let files = files(
+            "execution-interrupt",
+            &["dev", "ops", "web"],
+            &["start dev 2026-06-10 9:00 +02:00"],
+        );
+
+        interrupt(&files, "ops", at("2026-06-10 10:00 +02:00"), Some("Outage"))
+            .ok()
+            .unwrap();
+        interrupt(&files, "web", at("2026-06-10 10:30 +02:00"), None)
+            .ok()
+            .unwrap();
+
+        assert_eq!(
+            files.data.load().ok().unwrap().general.interruptions.len(),
+            2
+        );
+
+        stop_or_end_interruption(&files, None, at("2026-06-10 11:00 +02:00"), None, false)
+            .ok()
+            .unwrap();
+        stop_or_end_interruption(
+            &files,
+            Some("ops"),
+            at("2026-06-10 11:30 +02:00"),
+            None,
+            true,
+        )
+        .ok()
+        .unwrap();
+
+        assert_eq!(
+            history(&files),
+            vec![
+                "start dev 2026-06-10 9:00 +02:00",
+                "stop dev 2026-06-10 10:00 +02:00",
+                r#"start ops 2026-06-10 10:00 +02:00 "Outage""#,
+                "stop ops 2026-06-10 10:30 +02:00",
+                "start web 2026-06-10 10:30 +02:00",
+                "stop web 2026-06-10 11:00 +02:00",
+                "start ops 2026-06-10 11:00 +02:00",
+                "stop ops 2026-06-10 11:30 +02:00",
+                "commit ops",
+                "start dev 2026-06-10 11:30 +02:00",
+            ]
+        );
+
+        assert!(files
+            .data
+            .load()
+            .ok()
+            .unwrap()
+            .general
+            .interruptions
+            .is_empty());
+    }
+
+    #[test]
+    fn interruptions_are_undone_at_once() {
+        use_berlin_time();
+        let files = files(
+            "execution-interrupt-undo",
+            &["dev", "ops"],
+            &["start dev 2026-06-10 9:00 +02:00"],
+        );
+
+        let interruptions = |files: &Files| files.data.load().ok().unwrap().general.interruptions;
+
+        interrupt(&files, "ops", at("2026-06-10 10:00 +02:00"), None)
+            .ok()
+            .unwrap();
+
+        // Each invocation starts a new transaction.
+        let files = Files::new(files.history.path().parent().unwrap());
+
+        stop_or_end_interruption(&files, None, at("2026-06-10 11:00 +02:00"), None, false)
+            .ok()
+            .unwrap();
+
+        assert_eq!(history(&files).len(), 5);
+        assert!(interruptions(&files).is_empty());
+
+        answer(&["y"]);
+        undo(&files).ok().unwrap();
+
+        assert_eq!(
+            history(&files),
+            vec![
+                "start dev 2026-06-10 9:00 +02:00",
+                "stop dev 2026-06-10 10:00 +02:00",
+                "start ops 2026-06-10 10:00 +02:00",
+            ]
+        );
+        assert_eq!(interruptions(&files).len(), 1);
+    }
+
+    #[test]
+    fn invoice_previews_keep_the_number() {
+        use_berlin_time();
+        let files = files(
+            "execution-invoice",
+            &["dev"],
+            &[
+                "start dev 2026-06-10 9:00 +02:00",
+                "stop dev 2026-06-10 10:00 +02:00",
+            ],
+        );
+
+        let date = |date| NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+        let invoice = |preview| {
+            write_invoice(
+                &files,
+                date("2026-06-01"),
+                date("2026-06-30"),
+                InvoiceFormat::Text,
+                None,
+                SessionFilter::All,
+                preview,
+            )
+            .ok()
+            .unwrap();
+
+            files.data.load().ok().unwrap().general.last_invoice
+        };
+
+        assert_eq!(invoice(true), 0);
+        assert_eq!(invoice(false), 1);
+        assert_eq!(invoice(true), 1);
+        assert_eq!(invoice(false), 2);
+
+        // Without any time worked in the period, no number is used either.
+        write_invoice(
+            &files,
+            date("2026-07-01"),
+            date("2026-07-31"),
+            InvoiceFormat::Text,
+            None,
+            SessionFilter::All,
+            false,
+        )
+        .ok()
+        .unwrap();
+        assert_eq!(files.data.load().ok().unwrap().general.last_invoice, 2);
+
+        assert!(matches!(
+            write_invoice(
+                &files,
+                date("2026-06-01"),
+                date("2026-06-30"),
+                InvoiceFormat::Text,
+                Some("acme"),
+                SessionFilter::All,
+                false,
+            ),
+            Err(ExecutionError::UnknownClient(_))
+        ));
+    }
+
+    #[test]
+    fn shared_locks_exclude_exclusive_ones_only() {
+        let files = files("execution-lock", &[], &[]);
+        let timeout = StdDuration::from_millis(100);
+
+        let shared = files.lock_within(false, timeout).ok().unwrap();
+        assert!(files.lock_within(false, timeout).is_ok());
+        assert!(matches!(
+            files.lock_within(true, timeout),
+            Err(ExecutionError::Locked(_))
+        ));
+
+        drop(shared);
+        let exclusive = files.lock_within(true, timeout).ok().unwrap();
+        assert!(matches!(
+            files.lock_within(false, timeout),
+            Err(ExecutionError::Locked(_))
+        ));
+
+        drop(exclusive);
+        assert!(files.lock_within(true, timeout).is_ok());
+    }
+
+    #[test]
+    fn locks_wait_for_the_conflicting_ones() {
+        let files = files("execution-lock-wait", &[], &[]);
+        let exclusive = files.lock(true).ok().unwrap();
+
+        let started = Instant::now();
+        assert!(files
+            .lock_within(false, StdDuration::from_millis(200))
+            .is_err());
+        assert!(started.elapsed() >= StdDuration::from_millis(200));
+
+        let release = thread::spawn(move || {
+            thread::sleep(StdDuration::from_millis(100));
+            drop(exclusive);
+        });
+
+        assert!(files.lock_within(true, StdDuration::from_secs(5)).is_ok());
+        release.join().unwrap();
+    }
+
+    #[test]
+    fn prompts_release_the_held_lock() {
+        let files = files("execution-lock-prompt", &[], &[]);
+        let timeout = StdDuration::from_millis(100);
+        files.hold(true).ok().unwrap();
+
+        let answer = files.unlocked(|| {
+            assert!(files.lock_within(true, timeout).is_ok());
+            Ok(42)
+        });
+
+        assert!(matches!(answer, Ok(42)));
+        assert!(matches!(
+            files.lock_within(false, timeout),
+            Err(ExecutionError::Locked(_))
+        ));
+
+        files.release();
+        assert!(files.lock_within(true, timeout).is_ok());
+    }
+
+    #[test]
+    fn changes_made_during_a_prompt_are_refused() {
+        let files = files("execution-lock-change", &[], &[]);
+        files.hold(true).ok().unwrap();
+
+        let answer = files.unlocked(|| {
+            let mut history = OpenOptions::new().append(true).open(files.history.path())?;
+            io::Write::write_all(&mut history, b"enter 2026-06-10 9:00 +02:00\n")
+        });
+
+        assert!(matches!(answer, Err(ExecutionError::ConcurrentChange)));
+        files.release();
+    }
+
+    #[test]
+    fn repair_keeps_the_blank_lines() {
+        use_berlin_time();
+        let files = files("execution-repair", &["dev"], &[]);
+        fs::write(
+            files.history.path(),
+            "enter 2026-06-10 8:30\n\
+             start dev 2026-06-10 9:00 +02:00\n\
+             start dev 2026-06-10 9:00 +02:00\n\
+             \n\
+             stop dev 2026-06-10 10:00 +02:00\n\
+             exit 2026-06-10 17:00 +02:00\n",
+        )
+        .unwrap();
+
+        answer(&["y"]);
+        repair(&files).ok().unwrap();
+
+        assert_eq!(
+            fs::read_to_string(files.history.path()).unwrap(),
+            "enter 2026-06-10 8:30\n\
+             start dev 2026-06-10 9:00 +02:00\n\
+             \n\
+             stop dev 2026-06-10 10:00 +02:00\n\
+             exit 2026-06-10 17:00 +02:00\n"
+        );
+
+        let backups = fs::read_dir(files.history.path().parent().unwrap())
+            .unwrap()
+            .filter(|entry| {
+                let name = entry.as_ref().unwrap().file_name();
+                name.to_string_lossy().ends_with(".bak")
+            })
+            .count();
+
+        assert_eq!(backups, 2);
+    }
+}
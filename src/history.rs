@@ -0,0 +1,1418 @@
+use chrono::prelude::*;
+use chrono::Duration;
+
+use core::str::FromStr;
+
+use super::index;
+use super::local_moments;
+use super::wal;
+use super::wal::Rewrite;
+use super::DateTime;
+use super::ForgetableDateTime;
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fmt::Error as FormatError;
+use std::fmt::Formatter;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+//==============================================================================
+//
+//                              Type Definitions
+//
+//==============================================================================
+
+/// Represents a single line of the history.log file.
+#[derive(Clone)]
+pub enum HistoryEntry {
+    Enter {
+        datetime: ForgetableDateTime,
+    },
+    Exit {
+        datetime: ForgetableDateTime,
+    },
+    /// A break starts. The task being worked on, if any, is stopped right before it.
+    Pause {
+        datetime: ForgetableDateTime,
+    },
+    /// A break ends. The task stopped by the `pause` entry, if any, is started right after it.
+    Resume {
+        datetime: ForgetableDateTime,
+    },
+    Start {
+        mnemonic: String,
+        datetime: ForgetableDateTime,
+        note: Option<String>,
+    },
+    Stop {
+        mnemonic: String,
+        datetime: ForgetableDateTime,
+        note: Option<String>,
+    },
+    /// All work sessions above on the task have been logged into the external tool.
+    /// A commit has no date/time of its own: it's always placed right after a `stop` entry.
+    Commit {
+        mnemonic: String,
+        note: Option<String>,
+    },
+}
+
+/// An entry together with its line in the history.log file.
+pub struct HistoryLine {
+    /// Byte offset of the line.
+    pub offset: u64,
+    /// The line exactly as it is in the file, without the line break.
+    pub text: String,
+    pub entry: HistoryEntry,
+}
+
+/// Where an entry was added to the history.log file.
+pub struct Insertion {
+    /// Byte offset of the new line.
+    pub offset: u64,
+    /// Whether the new line became the last one of the file.
+    pub is_last: bool,
+}
+
+/// Consecutive lines of the history.log file to be replaced by other ones.
+pub struct Replacement<'a> {
+    pub offset: u64,
+    pub previous: &'a [String],
+    pub current: &'a [String],
+}
+
+/// A period worked on a task, delimited by a `start` and an optional `stop` entry.
+pub struct Session {
+    pub mnemonic: String,
+    pub start: ForgetableDateTime,
+    pub stop: Option<ForgetableDateTime>,
+    /// Notes of the `start` and `stop` entries, and of the `commit` right after the session.
+    pub notes: Vec<String>,
+}
+
+/// A period at the workplace, delimited by an `enter` and an optional `exit` entry.
+pub struct Presence {
+    pub enter: ForgetableDateTime,
+    pub exit: Option<ForgetableDateTime>,
+}
+
+/// A break at the workplace, delimited by a `pause` and an optional `resume` entry.
+pub struct Break {
+    pub pause: ForgetableDateTime,
+    pub resume: Option<ForgetableDateTime>,
+}
+
+/// Entries that open and close periods alternately: `enter`/`exit` for the presence at the
+/// workplace, `pause`/`resume` for the breaks, and `start`/`stop` for the sessions of a task.
+pub enum Sequence<'a> {
+    Presence,
+    Break,
+    Task(&'a str),
+}
+
+/// Provides access to the history.log file.
+///
+/// The file is read from the end to the start whenever possible, because the most recent
+/// entries are the most likely ones to be accessed. See docs/files.md.
+pub struct HistoryFile {
+    path: PathBuf,
+}
+
+/// Iterates over the lines of a file from the last to the first one.
+struct ReverseLines {
+    file: File,
+    chunk: Vec<u8>,
+    chunk_start: u64,
+    finished: bool,
+}
+
+//==============================================================================
+//
+//                                Error Types
+//
+//==============================================================================
+
+pub enum HistoryParseError {
+    UnknownEntryType(String),
+    MissingField,
+    UnexpectedField(String),
+    InvalidDate(String),
+    InvalidTime(String),
+    NonExistentDateTime(String),
+    InvalidOffset(String),
+    InvalidNote(String),
+}
+
+pub enum HistoryError {
+    Io(io::Error),
+    Parse {
+        line: String,
+        error: HistoryParseError,
+    },
+}
+
+//==============================================================================
+//
+//                               Entry Handling
+//
+//==============================================================================
+
+impl HistoryEntry {
+    /// The date/time of the entry, if it has one.
+    pub fn datetime(&self) -> Option<DateTime> {
+        self.forgettable_datetime()
+            .map(|datetime| datetime.datetime)
+    }
+
+    pub fn forgettable_datetime(&self) -> Option<ForgetableDateTime> {
+        match self {
+            HistoryEntry::Enter { datetime }
+            | HistoryEntry::Exit { datetime }
+            | HistoryEntry::Pause { datetime }
+            | HistoryEntry::Resume { datetime }
+            | HistoryEntry::Start { datetime, .. }
+            | HistoryEntry::Stop { datetime, .. } => Some(*datetime),
+            HistoryEntry::Commit { .. } => None,
+        }
+    }
+
+    /// The mnemonic of the task the entry refers to, if any.
+    pub fn mnemonic(&self) -> Option<&str> {
+        match self {
+            HistoryEntry::Start { mnemonic, .. }
+            | HistoryEntry::Stop { mnemonic, .. }
+            | HistoryEntry::Commit { mnemonic, .. } => Some(mnemonic),
+            HistoryEntry::Enter { .. }
+            | HistoryEntry::Exit { .. }
+            | HistoryEntry::Pause { .. }
+            | HistoryEntry::Resume { .. } => None,
+        }
+    }
+
+    pub fn is_about(&self, task: &str) -> bool {
+        self.mnemonic() == Some(task)
+    }
+
+    pub fn set_datetime(&mut self, new: ForgetableDateTime) {
+        match self {
+            HistoryEntry::Enter { datetime }
+            | HistoryEntry::Exit { datetime }
+            | HistoryEntry::Pause { datetime }
+            | HistoryEntry::Resume { datetime }
+            | HistoryEntry::Start { datetime, .. }
+            | HistoryEntry::Stop { datetime, .. } => *datetime = new,
+            HistoryEntry::Commit { .. } => {}
+        }
+    }
+
+    pub fn set_mnemonic(&mut self, new: &str) {
+        match self {
+            HistoryEntry::Start { mnemonic, .. }
+            | HistoryEntry::Stop { mnemonic, .. }
+            | HistoryEntry::Commit { mnemonic, .. } => *mnemonic = new.to_string(),
+            HistoryEntry::Enter { .. }
+            | HistoryEntry::Exit { .. }
+            | HistoryEntry::Pause { .. }
+            | HistoryEntry::Resume { .. } => {}
+        }
+    }
+
+    pub fn note(&self) -> Option<&str> {
+        match self {
+            HistoryEntry::Start { note, .. }
+            | HistoryEntry::Stop { note, .. }
+            | HistoryEntry::Commit { note, .. } => note.as_deref(),
+            HistoryEntry::Enter { .. }
+            | HistoryEntry::Exit { .. }
+            | HistoryEntry::Pause { .. }
+            | HistoryEntry::Resume { .. } => None,
+        }
+    }
+}
+
+/// Groups the `start` and `stop` entries of each task into sessions, in chronological order
+/// of their start. A `start` without a matching `stop` results in an open session.
+pub fn sessions<'a, I>(entries: I) -> Vec<Session>
+where
+    I: IntoIterator<Item = &'a HistoryEntry>,
+{
+    let mut sessions: Vec<Session> = Vec::new();
+
+    for entry in entries {
+        match entry {
+            HistoryEntry::Start {
+                mnemonic,
+                datetime,
+                note,
+            } => sessions.push(Session {
+                mnemonic: mnemonic.clone(),
+                start: *datetime,
+                stop: None,
+                notes: note.iter().cloned().collect(),
+            }),
+            HistoryEntry::Stop {
+                mnemonic,
+                datetime,
+                note,
+            } => {
+                if let Some(session) = sessions
+                    .iter_mut()
+                    .rev()
+                    .find(|session| &session.mnemonic == mnemonic)
+                {
+                    if session.stop.is_none() {
+                        session.stop = Some(*datetime);
+                        session.notes.extend(note.iter().cloned());
+                    }
+                }
+            }
+            HistoryEntry::Commit {
+                mnemonic,
+                note: Some(note),
+            } => {
+                if let Some(session) = sessions
+                    .iter_mut()
+                    .rev()
+                    .find(|session| &session.mnemonic == mnemonic)
+                {
+                    session.notes.push(note.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    sessions
+}
+
+/// Groups the `enter` and `exit` entries into periods at the workplace, in chronological order.
+/// An `enter` without a matching `exit` results in an open period.
+pub fn presences<'a, I>(entries: I) -> Vec<Presence>
+where
+    I: IntoIterator<Item = &'a HistoryEntry>,
+{
+    let mut presences: Vec<Presence> = Vec::new();
+
+    for entry in entries {
+        match entry {
+            HistoryEntry::Enter { datetime } => presences.push(Presence {
+                enter: *datetime,
+                exit: None,
+            }),
+            HistoryEntry::Exit { datetime } => {
+                if let Some(presence) = presences.last_mut() {
+                    if presence.exit.is_none() {
+                        presence.exit = Some(*datetime);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    presences
+}
+
+/// Groups the `pause` and `resume` entries into breaks, in chronological order.
+/// A `pause` without a matching `resume` results in an open break.
+pub fn breaks<'a, I>(entries: I) -> Vec<Break>
+where
+    I: IntoIterator<Item = &'a HistoryEntry>,
+{
+    let mut breaks: Vec<Break> = Vec::new();
+
+    for entry in entries {
+        match entry {
+            HistoryEntry::Pause { datetime } => breaks.push(Break {
+                pause: *datetime,
+                resume: None,
+            }),
+            HistoryEntry::Resume { datetime } => {
+                if let Some(open) = breaks.last_mut() {
+                    if open.resume.is_none() {
+                        open.resume = Some(*datetime);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    breaks
+}
+
+/// The position where an entry with the given date/time should be inserted to keep the
+/// entries in chronological order. See `HistoryFile::insert`.
+pub fn chronological_position(entries: &[HistoryEntry], datetime: DateTime) -> usize {
+    let mut position = entries.len();
+
+    for (index, entry) in entries.iter().enumerate().rev() {
+        match entry.datetime() {
+            Some(other) if other > datetime => position = index,
+            Some(_) => break,
+            None => {}
+        }
+    }
+
+    position
+}
+
+impl<'a> Sequence<'a> {
+    /// The sequence the entry belongs to, if any.
+    pub fn of(entry: &'a HistoryEntry) -> Option<Sequence<'a>> {
+        match entry {
+            HistoryEntry::Enter { .. } | HistoryEntry::Exit { .. } => Some(Sequence::Presence),
+            HistoryEntry::Pause { .. } | HistoryEntry::Resume { .. } => Some(Sequence::Break),
+            HistoryEntry::Start { mnemonic, .. } | HistoryEntry::Stop { mnemonic, .. } => {
+                Some(Sequence::Task(mnemonic))
+            }
+            HistoryEntry::Commit { .. } => None,
+        }
+    }
+
+    /// Whether the entry opens (`true`) or closes (`false`) a period of this sequence.
+    pub fn opens(&self, entry: &HistoryEntry) -> Option<bool> {
+        match (self, entry) {
+            (Sequence::Presence, HistoryEntry::Enter { .. }) => Some(true),
+            (Sequence::Presence, HistoryEntry::Exit { .. }) => Some(false),
+            (Sequence::Break, HistoryEntry::Pause { .. }) => Some(true),
+            (Sequence::Break, HistoryEntry::Resume { .. }) => Some(false),
+            (Sequence::Task(task), HistoryEntry::Start { mnemonic, .. }) if task == mnemonic => {
+                Some(true)
+            }
+            (Sequence::Task(task), HistoryEntry::Stop { mnemonic, .. }) if task == mnemonic => {
+                Some(false)
+            }
+            _ => None,
+        }
+    }
+
+    /// Indices of the entries that break the alternation of the sequence, i.e. entries that
+    /// open a period while another one is open, or close a period when none is open.
+    pub fn unmatched(&self, entries: &[HistoryEntry]) -> Vec<usize> {
+        let mut open = false;
+        let mut unmatched = Vec::new();
+
+        for (index, entry) in entries.iter().enumerate() {
+            if let Some(opens) = self.opens(entry) {
+                if opens == open {
+                    unmatched.push(index);
+                }
+
+                open = opens;
+            }
+        }
+
+        unmatched
+    }
+}
+
+/// The IDs of the given lines. An ID is a hash of the line contents, so it doesn't change
+/// when other entries are added or removed. Identical lines are told apart by the number of
+/// identical lines before them.
+pub fn entry_ids(lines: &[HistoryLine]) -> Vec<String> {
+    let mut occurrences: HashMap<&str, usize> = HashMap::new();
+
+    lines
+        .iter()
+        .map(|line| {
+            let text = line.text.trim();
+            let occurrence = occurrences.entry(text).or_insert(0);
+            let id = entry_id(text, *occurrence);
+
+            *occurrence += 1;
+            id
+        })
+        .collect()
+}
+
+/// 32-bit FNV-1a hash, which is stable across Rust versions, unlike the standard hasher.
+fn entry_id(text: &str, occurrence: usize) -> String {
+    let mut hash: u32 = 0x811c_9dc5;
+
+    for byte in text
+        .bytes()
+        .chain((occurrence as u64).to_le_bytes().iter().cloned())
+    {
+        hash ^= u32::from(byte);
+        hash = hash.wrapping_mul(0x0100_0193);
+    }
+
+    format!("{:08x}", hash)
+}
+
+impl Session {
+    /// The duration of the session. Open sessions are considered to last until `now`.
+    pub fn duration(&self, now: DateTime) -> Duration {
+        self.stop.map(|stop| stop.datetime).unwrap_or(now) - self.start.datetime
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.stop.is_none()
+    }
+}
+
+impl Presence {
+    pub fn is_open(&self) -> bool {
+        self.exit.is_none()
+    }
+}
+
+impl Break {
+    pub fn is_open(&self) -> bool {
+        self.resume.is_none()
+    }
+}
+
+//==============================================================================
+//
+//                                File Handling
+//
+//==============================================================================
+
+impl HistoryFile {
+    pub fn new<P: AsRef<Path>>(path: P) -> HistoryFile {
+        HistoryFile {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reads all the entries from the first to the last one.
+    pub fn read(&self) -> Result<Vec<HistoryLine>, HistoryError> {
+        self.read_from(0)
+    }
+
+    /// Reads the entries from the line starting at `offset` to the last one.
+    pub fn read_from(&self, offset: u64) -> Result<Vec<HistoryLine>, HistoryError> {
+        let mut file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error.into()),
+        };
+
+        file.seek(SeekFrom::Start(offset))?;
+
+        let mut reader = BufReader::new(file);
+        let mut lines = Vec::new();
+        let mut offset = offset;
+        let mut buffer = String::new();
+
+        while reader.read_line(&mut buffer)? > 0 {
+            let line = buffer.trim();
+
+            if !line.is_empty() {
+                lines.push(HistoryLine {
+                    offset,
+                    entry: parse_line(line)?,
+                    text: buffer.trim_end_matches('\n').to_string(),
+                });
+            }
+
+            offset += buffer.len() as u64;
+            buffer.clear();
+        }
+
+        Ok(lines)
+    }
+
+    /// Reads the entries from the last to the first one, stopping at the first error.
+    pub fn read_rev(
+        &self,
+    ) -> Result<impl Iterator<Item = Result<HistoryLine, HistoryError>>, HistoryError> {
+        let lines = match File::open(&self.path) {
+            Ok(file) => Some(ReverseLines::new(file)?),
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => None,
+            Err(error) => return Err(error.into()),
+        };
+
+        Ok(lines
+            .into_iter()
+            .flatten()
+            .filter(|line| match line {
+                Ok((_, text)) => !text.trim().is_empty(),
+                Err(_) => true,
+            })
+            .map(|line| {
+                let (offset, text) = line?;
+                Ok(HistoryLine {
+                    offset,
+                    entry: parse_line(text.trim())?,
+                    text,
+                })
+            }))
+    }
+
+    /// Adds an entry at the appropriate place to keep the file in chronological order.
+    /// Entries with the same date/time are kept in the order they were added.
+    pub fn insert(&self, entry: &HistoryEntry) -> Result<Insertion, HistoryError> {
+        self.insert_along(entry, |_| Ok(Vec::new()))
+    }
+
+    /// Adds an entry along with rewrites of other files in the same directory, made knowing
+    /// where the entry goes, so a crash can't leave some of them done and others not. See
+    /// `insert`.
+    pub fn insert_along<F, E>(&self, entry: &HistoryEntry, others: F) -> Result<Insertion, E>
+    where
+        F: FnOnce(&Insertion) -> Result<Vec<Rewrite>, E>,
+        E: From<HistoryError>,
+    {
+        let offset = self.chronological_offset(entry)?;
+        self.insert_at_along(offset, entry, others)
+    }
+
+    /// The offset of the line the entry must be inserted before, to keep the chronological
+    /// order.
+    fn chronological_offset(&self, entry: &HistoryEntry) -> Result<u64, HistoryError> {
+        let datetime = entry
+            .datetime()
+            .expect("Entries without date/time must be inserted at an explicit offset!");
+
+        let mut offset = self.size()?;
+
+        for line in self.read_rev()? {
+            let line = line?;
+
+            match line.entry.datetime() {
+                Some(other) if other > datetime => offset = line.offset,
+                Some(_) => break,
+                None => {}
+            }
+        }
+
+        Ok(offset)
+    }
+
+    /// Adds an entry right before the line starting at `offset`, following the approach
+    /// described in docs/files.md: the remainder of the file is buffered, the file is
+    /// truncated and then the new entry and the buffer are appended back, through the
+    /// write-ahead log. The index of the history is then updated, see `index::after_write`.
+    pub fn insert_at(&self, offset: u64, entry: &HistoryEntry) -> Result<Insertion, HistoryError> {
+        self.insert_at_along(offset, entry, |_| Ok(Vec::new()))
+    }
+
+    /// Adds an entry right before the line starting at `offset`, along with rewrites of other
+    /// files. See `insert_at` and `insert_along`.
+    pub fn insert_at_along<F, E>(
+        &self,
+        offset: u64,
+        entry: &HistoryEntry,
+        others: F,
+    ) -> Result<Insertion, E>
+    where
+        F: FnOnce(&Insertion) -> Result<Vec<Rewrite>, E>,
+        E: From<HistoryError>,
+    {
+        let (rewrite, insertion, size) = self.insertion(offset, entry)?;
+
+        let mut rewrites = vec![rewrite];
+        rewrites.extend(others(&insertion)?);
+
+        wal::rewrite_tails(&rewrites).map_err(HistoryError::from)?;
+        index::after_write(&self.path, offset, size).map_err(HistoryError::from)?;
+        Ok(insertion)
+    }
+
+    /// The rewrite adding the entry before the line at `offset`, where the entry ends up and
+    /// the size of the file before the rewrite.
+    fn insertion(
+        &self,
+        offset: u64,
+        entry: &HistoryEntry,
+    ) -> Result<(Rewrite, Insertion, u64), HistoryError> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.path)?;
+
+        let mut buffer = Vec::new();
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_to_end(&mut buffer)?;
+
+        let is_last = buffer.iter().all(u8::is_ascii_whitespace);
+        let needs_line_break = offset > 0 && {
+            let mut previous = [0; 1];
+            file.seek(SeekFrom::Start(offset - 1))?;
+            file.read_exact(&mut previous)?;
+            previous[0] != b'\n'
+        };
+
+        let mut tail = Vec::new();
+
+        if needs_line_break {
+            writeln!(tail)?;
+        }
+
+        writeln!(tail, "{}", entry)?;
+        tail.extend_from_slice(&buffer);
+
+        let size = offset + buffer.len() as u64;
+        let rewrite = Rewrite {
+            path: self.path.clone(),
+            offset,
+            tail,
+        };
+
+        let insertion = Insertion {
+            offset: if needs_line_break { offset + 1 } else { offset },
+            is_last,
+        };
+
+        Ok((rewrite, insertion, size))
+    }
+
+    /// Appends an entry to the end of the file, without checking the chronological order.
+    pub fn append(&self, entry: &HistoryEntry) -> Result<Insertion, HistoryError> {
+        let offset = self.size()?;
+        self.insert_at(offset, entry)
+    }
+
+    /// Applies the replacements in the given order, i.e. the offset of each replacement must
+    /// already take into account the previous ones. Uses the same approach as `insert_at`,
+    /// starting from the smallest offset.
+    /// If the lines to be replaced are not found, nothing is done and `false` is returned.
+    pub fn replace(&self, replacements: &[Replacement]) -> Result<bool, HistoryError> {
+        self.replace_along(replacements, Vec::new())
+    }
+
+    /// Applies the replacements along with rewrites of other files in the same directory, so
+    /// a crash can't leave some of them done and others not. See `replace`.
+    pub fn replace_along(
+        &self,
+        replacements: &[Replacement],
+        others: Vec<Rewrite>,
+    ) -> Result<bool, HistoryError> {
+        let start = match replacements
+            .iter()
+            .map(|replacement| replacement.offset)
+            .min()
+        {
+            Some(start) => start,
+            None => {
+                wal::rewrite_tails(&others)?;
+                return Ok(true);
+            }
+        };
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.path)?;
+
+        let mut buffer = Vec::new();
+        file.seek(SeekFrom::Start(start))?;
+        file.read_to_end(&mut buffer)?;
+
+        let size = start + buffer.len() as u64;
+
+        if buffer.last().is_some_and(|&byte| byte != b'\n') {
+            buffer.push(b'\n');
+        }
+
+        for replacement in replacements {
+            let index = (replacement.offset - start) as usize;
+            let previous = join_lines(replacement.previous);
+            let current = join_lines(replacement.current);
+
+            let found = buffer
+                .get(index..)
+                .is_some_and(|rest| rest.starts_with(previous.as_bytes()));
+
+            if !found {
+                return Ok(false);
+            }
+
+            buffer.splice(index..index + previous.len(), current.bytes());
+        }
+
+        let mut rewrites = vec![Rewrite {
+            path: self.path.clone(),
+            offset: start,
+            tail: buffer,
+        }];
+
+        rewrites.extend(others);
+        wal::rewrite_tails(&rewrites)?;
+        index::after_write(&self.path, start, size)?;
+        Ok(true)
+    }
+
+    /// See `wal::recover`. The index is removed if the history was rewritten, since it's not
+    /// known where.
+    pub fn recover(&self) -> Result<bool, HistoryError> {
+        let rewritten = wal::recover(&self.path)?;
+
+        if rewritten.contains(&self.path) {
+            index::remove(&self.path)?;
+        }
+
+        Ok(!rewritten.is_empty())
+    }
+
+    pub fn is_pending(&self) -> bool {
+        wal::is_pending(&self.path)
+    }
+
+    /// The size of the file in bytes, i.e. the offset right after the last entry.
+    pub fn size(&self) -> Result<u64, HistoryError> {
+        match std::fs::metadata(&self.path) {
+            Ok(metadata) => Ok(metadata.len()),
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => Ok(0),
+            Err(error) => Err(error.into()),
+        }
+    }
+}
+
+impl ReverseLines {
+    const CHUNK_SIZE: u64 = 4096;
+
+    fn new(file: File) -> io::Result<ReverseLines> {
+        let chunk_start = file.metadata()?.len();
+
+        Ok(ReverseLines {
+            file,
+            chunk: Vec::new(),
+            chunk_start,
+            finished: false,
+        })
+    }
+
+    /// Prepends the previous chunk of the file to the current one.
+    fn read_chunk(&mut self) -> io::Result<()> {
+        let start = self.chunk_start.saturating_sub(Self::CHUNK_SIZE);
+        let mut chunk = vec![0; (self.chunk_start - start) as usize];
+
+        self.file.seek(SeekFrom::Start(start))?;
+        self.file.read_exact(&mut chunk)?;
+        chunk.extend_from_slice(&self.chunk);
+
+        self.chunk = chunk;
+        self.chunk_start = start;
+        Ok(())
+    }
+}
+
+impl Iterator for ReverseLines {
+    type Item = io::Result<(u64, String)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.finished {
+                return None;
+            }
+
+            if let Some(index) = self.chunk.iter().rposition(|&byte| byte == b'\n') {
+                let line = self.chunk.split_off(index + 1);
+                self.chunk.truncate(index);
+
+                if line.is_empty() {
+                    continue;
+                }
+
+                let offset = self.chunk_start + index as u64 + 1;
+                return Some(Ok((offset, String::from_utf8_lossy(&line).into_owned())));
+            }
+
+            if self.chunk_start == 0 {
+                self.finished = true;
+                let line = std::mem::take(&mut self.chunk);
+                return Some(Ok((0, String::from_utf8_lossy(&line).into_owned())));
+            }
+
+            if let Err(error) = self.read_chunk() {
+                self.finished = true;
+                return Some(Err(error));
+            }
+        }
+    }
+}
+
+//==============================================================================
+//
+//                         Conversions Between Error Types
+//
+//==============================================================================
+
+impl From<io::Error> for HistoryError {
+    fn from(error: io::Error) -> HistoryError {
+        HistoryError::Io(error)
+    }
+}
+
+//==============================================================================
+//
+//                           Display Implementations
+//
+//==============================================================================
+
+impl Display for HistoryEntry {
+    fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FormatError> {
+        match self {
+            HistoryEntry::Enter { datetime } => write!(f, "enter {}", FormatDateTime(datetime)),
+            HistoryEntry::Exit { datetime } => write!(f, "exit {}", FormatDateTime(datetime)),
+            HistoryEntry::Pause { datetime } => write!(f, "pause {}", FormatDateTime(datetime)),
+            HistoryEntry::Resume { datetime } => {
+                write!(f, "resume {}", FormatDateTime(datetime))
+            }
+            HistoryEntry::Start {
+                mnemonic, datetime, ..
+            } => write!(f, "start {} {}", mnemonic, FormatDateTime(datetime)),
+            HistoryEntry::Stop {
+                mnemonic, datetime, ..
+            } => write!(f, "stop {} {}", mnemonic, FormatDateTime(datetime)),
+            HistoryEntry::Commit { mnemonic, .. } => write!(f, "commit {}", mnemonic),
+        }?;
+
+        match self.note() {
+            Some(note) => write!(f, " {}", QuoteNote(note)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Formats a note as stored in the history.log file: between double quotes, with quotes,
+/// backslashes and line breaks escaped by a backslash, e.g. `"fixed the \"login\" bug"`.
+struct QuoteNote<'a>(&'a str);
+
+impl<'a> Display for QuoteNote<'a> {
+    fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FormatError> {
+        write!(f, "\"")?;
+
+        for c in self.0.chars() {
+            match c {
+                '"' => write!(f, "\\\"")?,
+                '\\' => write!(f, "\\\\")?,
+                '\n' => write!(f, "\\n")?,
+                '\r' => write!(f, "\\r")?,
+                '\t' => write!(f, "\\t")?,
+                c => write!(f, "{}", c)?,
+            }
+        }
+
+        write!(f, "\"")
+    }
+}
+
+/// Formats a date/time as stored in the history.log file, e.g. `2019-02-22 9:30 -03:00`.
+/// The UTC offset identifies the moment even when the local time is repeated by a DST change.
+/// Seconds are only written when they're not zero, and forgotten entries get a question mark.
+struct FormatDateTime<'a>(&'a ForgetableDateTime);
+
+impl<'a> Display for FormatDateTime<'a> {
+    fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FormatError> {
+        let datetime = self.0.datetime;
+        let format = if datetime.second() == 0 {
+            "%Y-%m-%d %-H:%M %:z"
+        } else {
+            "%Y-%m-%d %-H:%M:%S %:z"
+        };
+
+        write!(f, "{}", datetime.format(format))?;
+
+        if self.0.forgotten {
+            write!(f, "?")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for HistoryParseError {
+    fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FormatError> {
+        match self {
+            HistoryParseError::UnknownEntryType(kind) => write!(f, "unknown entry type: {}", kind),
+            HistoryParseError::MissingField => write!(f, "missing field"),
+            HistoryParseError::UnexpectedField(field) => write!(f, "unexpected field: {}", field),
+            HistoryParseError::InvalidDate(date) => write!(f, "invalid date: {}", date),
+            HistoryParseError::InvalidTime(time) => write!(f, "invalid time: {}", time),
+            HistoryParseError::InvalidOffset(offset) => write!(f, "invalid UTC offset: {}", offset),
+            HistoryParseError::InvalidNote(note) => write!(f, "invalid note: {}", note),
+            HistoryParseError::NonExistentDateTime(datetime) => {
+                write!(
+                    f,
+                    "date/time doesn't exist in the local time zone: {}",
+                    datetime
+                )
+            }
+        }
+    }
+}
+
+impl Display for HistoryError {
+    fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FormatError> {
+        match self {
+            HistoryError::Io(error) => write!(f, "{}", error),
+            HistoryError::Parse { line, error } => {
+                write!(f, "invalid history entry \"{}\": {}", line, error)
+            }
+        }
+    }
+}
+
+//==============================================================================
+//
+//                               Parsing Functions
+//
+//==============================================================================
+
+impl FromStr for HistoryEntry {
+    type Err = HistoryParseError;
+
+    fn from_str(line: &str) -> Result<Self, HistoryParseError> {
+        let (line, note) = match line.find('"') {
+            Some(quote) => (&line[..quote], Some(parse_note(&line[quote..])?)),
+            None => (line, None),
+        };
+
+        let has_note = note.is_some();
+        let mut fields = line.split_whitespace();
+        let kind = fields.next().ok_or(HistoryParseError::MissingField)?;
+
+        let entry = match kind {
+            "enter" => HistoryEntry::Enter {
+                datetime: parse_datetime_fields(&mut fields)?,
+            },
+            "exit" => HistoryEntry::Exit {
+                datetime: parse_datetime_fields(&mut fields)?,
+            },
+            "pause" => HistoryEntry::Pause {
+                datetime: parse_datetime_fields(&mut fields)?,
+            },
+            "resume" => HistoryEntry::Resume {
+                datetime: parse_datetime_fields(&mut fields)?,
+            },
+            "start" => HistoryEntry::Start {
+                mnemonic: parse_mnemonic_field(&mut fields)?,
+                datetime: parse_datetime_fields(&mut fields)?,
+                note,
+            },
+            "stop" => HistoryEntry::Stop {
+                mnemonic: parse_mnemonic_field(&mut fields)?,
+                datetime: parse_datetime_fields(&mut fields)?,
+                note,
+            },
+            "commit" => HistoryEntry::Commit {
+                mnemonic: parse_mnemonic_field(&mut fields)?,
+                note,
+            },
+            _ => return Err(HistoryParseError::UnknownEntryType(kind.to_string())),
+        };
+
+        if has_note && entry.note().is_none() {
+            return Err(HistoryParseError::UnexpectedField(String::from("\"")));
+        }
+
+        match fields.next() {
+            Some(field) => Err(HistoryParseError::UnexpectedField(field.to_string())),
+            None => Ok(entry),
+        }
+    }
+}
+
+fn join_lines(lines: &[String]) -> String {
+    lines.iter().map(|line| format!("{}\n", line)).collect()
+}
+
+fn parse_line(line: &str) -> Result<HistoryEntry, HistoryError> {
+    HistoryEntry::from_str(line).map_err(|error| HistoryError::Parse {
+        line: line.to_string(),
+        error,
+    })
+}
+
+fn parse_mnemonic_field<'a, I>(fields: &mut I) -> Result<String, HistoryParseError>
+where
+    I: Iterator<Item = &'a str>,
+{
+    fields
+        .next()
+        .map(str::to_string)
+        .ok_or(HistoryParseError::MissingField)
+}
+
+/// Parses a note written by `QuoteNote`. Nothing but whitespace may follow the closing quote.
+fn parse_note(text: &str) -> Result<String, HistoryParseError> {
+    let invalid = || HistoryParseError::InvalidNote(text.to_string());
+    let mut chars = text.chars();
+    let mut note = String::new();
+
+    if chars.next() != Some('"') {
+        return Err(invalid());
+    }
+
+    loop {
+        match chars.next().ok_or_else(invalid)? {
+            '"' => break,
+            '\\' => note.push(match chars.next().ok_or_else(invalid)? {
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                c @ '"' | c @ '\\' => c,
+                _ => return Err(invalid()),
+            }),
+            c => note.push(c),
+        }
+    }
+
+    match chars.as_str().split_whitespace().next() {
+        Some(field) => Err(HistoryParseError::UnexpectedField(field.to_string())),
+        None => Ok(note),
+    }
+}
+
+/// Parses a date/time written by `FormatDateTime`. Entries written before UTC offsets were
+/// stored have none, so their date/time is taken as local, and the first one is taken when a
+/// DST change repeats it.
+fn parse_datetime_fields<'a, I>(fields: &mut I) -> Result<ForgetableDateTime, HistoryParseError>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let date = fields.next().ok_or(HistoryParseError::MissingField)?;
+    let time = fields.next().ok_or(HistoryParseError::MissingField)?;
+    let offset = fields.next();
+
+    let (time, offset, forgotten) = match offset {
+        Some(offset) => match offset.strip_suffix('?') {
+            Some(offset) => (time, Some(offset), true),
+            None => (time, Some(offset), false),
+        },
+        None => match time.strip_suffix('?') {
+            Some(time) => (time, None, true),
+            None => (time, None, false),
+        },
+    };
+
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| HistoryParseError::InvalidDate(date.to_string()))?;
+
+    let time = NaiveTime::parse_from_str(time, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M"))
+        .map_err(|_| HistoryParseError::InvalidTime(time.to_string()))?;
+
+    let naive = date.and_time(time);
+
+    let datetime = match offset {
+        Some(offset) => parse_offset(offset)
+            .and_then(|offset| offset.from_local_datetime(&naive).single())
+            .ok_or_else(|| HistoryParseError::InvalidOffset(offset.to_string()))?
+            .with_timezone(&Local),
+        None => local_moments(naive)
+            .first()
+            .cloned()
+            .ok_or_else(|| HistoryParseError::NonExistentDateTime(naive.to_string()))?,
+    };
+
+    Ok(ForgetableDateTime {
+        datetime,
+        forgotten,
+    })
+}
+
+/// Parses a UTC offset such as `-03:00` or `+05:30`.
+fn parse_offset(offset: &str) -> Option<FixedOffset> {
+    let sign = match offset.get(..1)? {
+        "+" => 1,
+        "-" => -1,
+        _ => return None,
+    };
+
+    let time = NaiveTime::parse_from_str(offset.get(1..)?, "%H:%M").ok()?;
+    FixedOffset::east_opt(sign * time.num_seconds_from_midnight() as i32)
+}
+
+//==============================================================================
+//
+//                                   Tests
+//
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::temp_dir;
+    use crate::tests::use_berlin_time;
+
+    use std::fs;
+
+    fn entry(line: &str) -> HistoryEntry {
+        match HistoryEntry::from_str(line) {
+            Ok(entry) => entry,
+            Err(error) => panic!("{}: {}", line, error),
+        }
+    }
+
+    fn contents(history: &HistoryFile) -> String {
+        fs::read_to_string(history.path()).unwrap()
+    }
+
+    #[test]
+    fn entries_round_trip() {
+        use_berlin_time();
+        for line in &[
+            "enter 2026-06-10 8:30 +02:00",
+            "exit 2026-06-10 17:05:30 +02:00",
+            "pause 2026-01-12 12:00 +01:00",
+            "resume 2026-01-12 12:45 +01:00?",
+            "start dev 2026-06-10 9:00 +02:00",
+            "stop dev/api 2026-06-10 11:15 +02:00?",
+            "commit dev",
+        ] {
+            assert_eq!(entry(line).to_string(), *line);
+        }
+    }
+
+    #[test]
+    fn invalid_lines_are_refused() {
+        use_berlin_time();
+        assert!(matches!(
+            HistoryEntry::from_str("begin 2026-06-10 8:30 +02:00"),
+            Err(HistoryParseError::UnknownEntryType(_))
+        ));
+        assert!(matches!(
+            HistoryEntry::from_str("start dev"),
+            Err(HistoryParseError::MissingField)
+        ));
+        assert!(matches!(
+            HistoryEntry::from_str("enter 2026-06-10 8:30 +02:00 dev"),
+            Err(HistoryParseError::UnexpectedField(_))
+        ));
+        assert!(matches!(
+            HistoryEntry::from_str("enter 2026-06-31 8:30"),
+            Err(HistoryParseError::InvalidDate(_))
+        ));
+        assert!(matches!(
+            HistoryEntry::from_str("enter 2026-06-10 25:30"),
+            Err(HistoryParseError::InvalidTime(_))
+        ));
+        assert!(matches!(
+            HistoryEntry::from_str("enter 2026-06-10 8:30 02:00"),
+            Err(HistoryParseError::InvalidOffset(_))
+        ));
+    }
+
+    #[test]
+    fn both_moments_of_a_repeated_time_round_trip() {
+        use_berlin_time();
+
+        for line in &[
+            "start dev 2026-10-25 2:30 +02:00",
+            "start dev 2026-10-25 2:30 +01:00",
+        ] {
+            assert_eq!(entry(line).to_string(), *line);
+        }
+    }
+
+    #[test]
+    fn lines_without_offset_are_local() {
+        use_berlin_time();
+
+        assert_eq!(
+            entry("enter 2026-06-10 8:30?").to_string(),
+            "enter 2026-06-10 8:30 +02:00?"
+        );
+        assert_eq!(
+            entry("enter 2026-10-25 2:30").to_string(),
+            "enter 2026-10-25 2:30 +02:00"
+        );
+        assert!(matches!(
+            HistoryEntry::from_str("enter 2026-03-29 2:30"),
+            Err(HistoryParseError::NonExistentDateTime(_))
+        ));
+    }
+
+    #[test]
+    fn insert_keeps_the_chronological_order() {
+        use_berlin_time();
+        let history = HistoryFile::new(temp_dir("history-insert").join("history.log"));
+
+        let insert = |line: &str| history.insert(&entry(line)).ok().unwrap();
+
+        assert_eq!(insert("enter 2026-06-10 8:30 +02:00").offset, 0);
+        assert!(insert("exit 2026-06-10 17:00 +02:00").is_last);
+
+        let insertion = insert("start dev 2026-06-10 9:00 +02:00");
+        assert_eq!(insertion.offset, 29);
+        assert!(!insertion.is_last);
+
+        assert!(!insert("stop dev 2026-06-10 12:00 +02:00").is_last);
+
+        // Entries at the same date/time go after the ones already there.
+        assert_eq!(insert("start ops 2026-06-10 12:00 +02:00").offset, 95);
+
+        assert_eq!(
+            contents(&history),
+            "enter 2026-06-10 8:30 +02:00\n\
+             start dev 2026-06-10 9:00 +02:00\n\
+             stop dev 2026-06-10 12:00 +02:00\n\
+             start ops 2026-06-10 12:00 +02:00\n\
+             exit 2026-06-10 17:00 +02:00\n"
+        );
+    }
+
+    #[test]
+    fn insertions_rewrite_other_files_along() {
+        use_berlin_time();
+        let dir = temp_dir("history-insert-along");
+        let history = HistoryFile::new(dir.join("history.log"));
+        let other = dir.join("other.json");
+
+        history
+            .insert(&entry("exit 2026-06-10 17:00 +02:00"))
+            .ok()
+            .unwrap();
+
+        let insertion = history
+            .insert_along(&entry("enter 2026-06-10 8:30 +02:00"), |insertion| {
+                Ok::<_, HistoryError>(vec![Rewrite {
+                    path: other.clone(),
+                    offset: 0,
+                    tail: insertion.offset.to_string().into_bytes(),
+                }])
+            })
+            .ok()
+            .unwrap();
+
+        assert_eq!(insertion.offset, 0);
+        assert_eq!(fs::read_to_string(&other).unwrap(), "0");
+        assert!(!history.is_pending());
+        assert_eq!(
+            contents(&history),
+            "enter 2026-06-10 8:30 +02:00\nexit 2026-06-10 17:00 +02:00\n"
+        );
+    }
+
+    #[test]
+    fn read_rev_is_read_backwards() {
+        use_berlin_time();
+        let history = HistoryFile::new(temp_dir("history-read-rev").join("history.log"));
+        let text = "enter 2026-06-10 8:30 +02:00\n\nstart dev 2026-06-10 9:00 +02:00\nstop dev 2026-06-10 12:00 +02:00";
+
+        fs::write(history.path(), text).unwrap();
+
+        let forward: Vec<(u64, String)> = history
+            .read()
+            .ok()
+            .unwrap()
+            .iter()
+            .map(|line| (line.offset, line.entry.to_string()))
+            .collect();
+
+        let mut backward: Vec<(u64, String)> = history
+            .read_rev()
+            .ok()
+            .unwrap()
+            .map(|line| line.map(|line| (line.offset, line.entry.to_string())))
+            .collect::<Result<_, _>>()
+            .ok()
+            .unwrap();
+
+        backward.reverse();
+        assert_eq!(forward, backward);
+        assert_eq!(forward[1].0, 30);
+    }
+
+    #[test]
+    fn sessions_pair_starts_and_stops() {
+        use_berlin_time();
+        let entries: Vec<HistoryEntry> = [
+            "start dev 2026-06-10 9:00 +02:00",
+            "start ops 2026-06-10 10:00 +02:00",
+            "stop dev 2026-06-10 11:00 +02:00",
+            "stop dev 2026-06-10 11:30 +02:00",
+            "start dev 2026-06-10 13:00 +02:00",
+        ]
+        .iter()
+        .map(|line| entry(line))
+        .collect();
+
+        let sessions: Vec<(String, bool)> = sessions(&entries)
+            .iter()
+            .map(|session| (session.mnemonic.clone(), session.is_open()))
+            .collect();
+
+        assert_eq!(
+            sessions,
+            vec![
+                ("dev".to_string(), false),
+                ("ops".to_string(), true),
+                ("dev".to_string(), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn identical_lines_have_different_ids() {
+        use_berlin_time();
+        let history = HistoryFile::new(temp_dir("history-ids").join("history.log"));
+
+        fs::write(
+            history.path(),
+            "start dev 2026-06-10 9:00 +02:00\ncommit dev\nstart dev 2026-06-11 9:00 +02:00\ncommit dev\n",
+        )
+        .unwrap();
+
+        let ids = entry_ids(&history.read().ok().unwrap());
+
+        assert_eq!(ids.len(), 4);
+        assert_ne!(ids[1], ids[3]);
+        assert!(ids
+            .iter()
+            .all(|id| id.len() == 8 && id.chars().all(|c| c.is_ascii_hexdigit())));
+    }
+
+    #[test]
+    fn notes_round_trip() {
+        use_berlin_time();
+        for line in &[
+            r#"start dev 2026-06-10 9:00 +02:00 "plain note""#,
+            r#"stop dev 2026-06-10 9:00 +02:00? "a \"quoted\" note\nin two lines""#,
+            r#"commit dev "tab\there, backslash \\ there""#,
+        ] {
+            assert_eq!(entry(line).to_string(), *line);
+        }
+
+        assert!(matches!(
+            HistoryEntry::from_str("enter 2026-06-10 8:30 +02:00 \"note\""),
+            Err(HistoryParseError::UnexpectedField(_))
+        ));
+        assert!(matches!(
+            HistoryEntry::from_str("commit dev \"unterminated"),
+            Err(HistoryParseError::InvalidNote(_))
+        ));
+    }
+
+    #[test]
+    fn sessions_collect_the_notes() {
+        use_berlin_time();
+        let entries: Vec<HistoryEntry> = [
+            r#"start dev 2026-06-10 9:00 +02:00 "started""#,
+            r#"stop dev 2026-06-10 10:00 +02:00 "stopped""#,
+            r#"commit dev "committed""#,
+        ]
+        .iter()
+        .map(|line| entry(line))
+        .collect();
+
+        assert_eq!(
+            sessions(&entries)[0].notes,
+            vec!["started", "stopped", "committed"]
+        );
+    }
+}
@@ -0,0 +1,325 @@
+use chrono::prelude::*;
+
+use std::fmt::Display;
+use std::fmt::Error as FormatError;
+use std::fmt::Formatter;
+
+//==============================================================================
+//
+//                              Type Definitions
+//
+//==============================================================================
+
+/// An all-day VEVENT of an iCalendar file.
+pub struct AllDayEvent {
+    pub summary: Option<String>,
+    pub start: NaiveDate,
+    /// The last day of the event, included.
+    pub end: NaiveDate,
+}
+
+/// The properties of a VEVENT read so far.
+struct PartialEvent {
+    /// Number of the BEGIN line.
+    line: usize,
+    summary: Option<String>,
+    start: Option<NaiveDate>,
+    end: Option<NaiveDate>,
+    /// Whether DTSTART has a time, so it's not an all-day event.
+    timed: bool,
+    /// Number of components open inside the event, e.g. VALARM.
+    nesting: usize,
+}
+
+//==============================================================================
+//
+//                                Error Types
+//
+//==============================================================================
+
+pub enum IcalParseError {
+    InvalidDate { line: usize },
+    UnterminatedEvent { line: usize },
+}
+
+//==============================================================================
+//
+//                                   Import
+//
+//==============================================================================
+
+/// Reads the all-day events of an iCalendar file, i.e. the VEVENTs whose DTSTART is a date
+/// without a time. Other events are ignored, and so are recurrence rules. DTEND is the day
+/// after the event, as in the iCalendar format; without it, the event lasts a single day.
+pub fn parse(text: &str) -> Result<Vec<AllDayEvent>, IcalParseError> {
+    let mut events = Vec::new();
+    let mut event: Option<PartialEvent> = None;
+
+    for (number, line) in unfold(text) {
+        let (name, parameters, value) = match split_property(&line) {
+            Some(property) => property,
+            None => continue,
+        };
+
+        let current = match event.as_mut() {
+            Some(current) => current,
+            None => {
+                if name == "BEGIN" && value.eq_ignore_ascii_case("VEVENT") {
+                    event = Some(PartialEvent::new(number));
+                }
+
+                continue;
+            }
+        };
+
+        match name.as_str() {
+            "BEGIN" => current.nesting += 1,
+            "END" if current.nesting > 0 => current.nesting -= 1,
+            "END" => {
+                events.extend(current.finish());
+                event = None;
+            }
+            _ if current.nesting > 0 => {}
+            "SUMMARY" => current.summary = Some(unescape(value)),
+            "DTSTART" if is_date(parameters, value) => {
+                current.start = Some(parse_date(value, number)?);
+            }
+            "DTSTART" => current.timed = true,
+            "DTEND" if is_date(parameters, value) => {
+                current.end = Some(parse_date(value, number)?);
+            }
+            _ => {}
+        }
+    }
+
+    match event {
+        Some(event) => Err(IcalParseError::UnterminatedEvent { line: event.line }),
+        None => Ok(events),
+    }
+}
+
+impl PartialEvent {
+    fn new(line: usize) -> PartialEvent {
+        PartialEvent {
+            line,
+            summary: None,
+            start: None,
+            end: None,
+            timed: false,
+            nesting: 0,
+        }
+    }
+
+    /// The event, if it's an all-day one.
+    fn finish(&mut self) -> Option<AllDayEvent> {
+        let start = self.start.filter(|_| !self.timed)?;
+
+        Some(AllDayEvent {
+            summary: self.summary.take(),
+            start,
+            end: self.end.map_or(start, |end| end.pred().max(start)),
+        })
+    }
+}
+
+/// Joins the lines folded by the iCalendar format: a line starting with a space or a tab
+/// continues the previous one. Each line comes with the number of its first physical line.
+fn unfold(text: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+
+        match lines.last_mut() {
+            Some((_, previous)) if line.starts_with(' ') || line.starts_with('\t') => {
+                previous.push_str(&line[1..]);
+            }
+            _ => lines.push((index + 1, line.to_string())),
+        }
+    }
+
+    lines
+}
+
+/// Splits a content line into its upper-case name, its parameters and its value, e.g.
+/// `DTSTART;VALUE=DATE:20191225`. The value starts at the first colon outside quotes.
+fn split_property(line: &str) -> Option<(String, &str, &str)> {
+    let mut quoted = false;
+
+    let colon = line.char_indices().find_map(|(index, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(index),
+        _ => None,
+    })?;
+
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+    let (name, parameters) = head.split_at(head.find(';').unwrap_or(head.len()));
+
+    Some((name.trim().to_uppercase(), parameters, value.trim()))
+}
+
+/// Whether a DTSTART or DTEND value is a date without a time.
+fn is_date(parameters: &str, value: &str) -> bool {
+    parameters.to_uppercase().contains("VALUE=DATE") && !value.contains('T')
+        || value.len() == 8 && value.chars().all(|c| c.is_ascii_digit())
+}
+
+fn parse_date(value: &str, line: usize) -> Result<NaiveDate, IcalParseError> {
+    NaiveDate::parse_from_str(value, "%Y%m%d").map_err(|_| IcalParseError::InvalidDate { line })
+}
+
+/// Reverts the escaping of text values, e.g. `\,` for a comma.
+fn unescape(value: &str) -> String {
+    let mut text = String::new();
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') | Some('N') => text.push('\n'),
+            Some(other) => text.push(other),
+            None => text.push('\\'),
+        }
+    }
+
+    text
+}
+
+//==============================================================================
+//
+//                           Display Implementations
+//
+//==============================================================================
+
+impl Display for IcalParseError {
+    fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FormatError> {
+        match self {
+            IcalParseError::InvalidDate { line } => write!(f, "invalid date at line {}", line),
+            IcalParseError::UnterminatedEvent { line } => {
+                write!(f, "VEVENT without an END line at line {}", line)
+            }
+        }
+    }
+}
+
+//==============================================================================
+//
+//                                   Tests
+//
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    fn events(text: &str) -> Vec<(Option<String>, NaiveDate, NaiveDate)> {
+        parse(text)
+            .ok()
+            .unwrap()
+            .into_iter()
+            .map(|event| (event.summary, event.start, event.end))
+            .collect()
+    }
+
+    #[test]
+    fn parse_reads_all_day_events() {
+        let text = "BEGIN:VCALENDAR\r\n\
+                    BEGIN:VEVENT\r\n\
+                    SUMMARY:Tag der Deutschen\r\n  Einheit\r\n\
+                    DTSTART;VALUE=DATE:20261003\r\n\
+                    DTEND;VALUE=DATE:20261004\r\n\
+                    END:VEVENT\r\n\
+                    BEGIN:VEVENT\r\n\
+                    SUMMARY:Weihnachten\\, 1. und 2. Tag\r\n\
+                    DTSTART:20261225\r\n\
+                    DTEND:20261227\r\n\
+                    END:VEVENT\r\n\
+                    BEGIN:VEVENT\r\n\
+                    SUMMARY:Silvester\r\n\
+                    DTSTART;VALUE=DATE:20261231\r\n\
+                    END:VEVENT\r\n\
+                    END:VCALENDAR\r\n";
+
+        // DTEND is excluded, and an event without it lasts a single day.
+        assert_eq!(
+            events(text),
+            vec![
+                (
+                    Some("Tag der Deutschen Einheit".to_string()),
+                    date("2026-10-03"),
+                    date("2026-10-03")
+                ),
+                (
+                    Some("Weihnachten, 1. und 2. Tag".to_string()),
+                    date("2026-12-25"),
+                    date("2026-12-26")
+                ),
+                (
+                    Some("Silvester".to_string()),
+                    date("2026-12-31"),
+                    date("2026-12-31")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_skips_timed_events_and_nested_components() {
+        let text = "BEGIN:VCALENDAR\n\
+                    BEGIN:VEVENT\n\
+                    SUMMARY:Meeting\n\
+                    DTSTART:20261005T090000Z\n\
+                    DTEND:20261005T100000Z\n\
+                    END:VEVENT\n\
+                    BEGIN:VEVENT\n\
+                    SUMMARY:Feiertag\n\
+                    DTSTART;VALUE=DATE:20261101\n\
+                    BEGIN:VALARM\n\
+                    SUMMARY:Reminder\n\
+                    DTSTART;VALUE=DATE:20261031\n\
+                    END:VALARM\n\
+                    END:VEVENT\n\
+                    END:VCALENDAR\n";
+
+        assert_eq!(
+            events(text),
+            vec![(
+                Some("Feiertag".to_string()),
+                date("2026-11-01"),
+                date("2026-11-01")
+            )]
+        );
+    }
+
+    #[test]
+    fn parse_refuses_invalid_files() {
+        let invalid_date = "BEGIN:VEVENT\nDTSTART;VALUE=DATE:20261350\nEND:VEVENT\n";
+        assert!(matches!(
+            parse(invalid_date),
+            Err(IcalParseError::InvalidDate { line: 2 })
+        ));
+
+        let unterminated = "BEGIN:VCALENDAR\nBEGIN:VEVENT\nDTSTART:20261003\n";
+        assert!(matches!(
+            parse(unterminated),
+            Err(IcalParseError::UnterminatedEvent { line: 2 })
+        ));
+    }
+
+    #[test]
+    fn unescape_reverts_the_text_escaping() {
+        assert_eq!(unescape(r"a\, b\; c\\d\ne\Nf"), "a, b; c\\d\ne\nf");
+        assert_eq!(unescape("trailing\\"), "trailing\\");
+    }
+}
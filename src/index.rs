@@ -0,0 +1,888 @@
+use chrono::prelude::*;
+use chrono::Duration;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::config;
+use super::history::breaks;
+use super::history::presences;
+use super::history::sessions;
+use super::history::Break;
+use super::history::HistoryEntry;
+use super::history::HistoryError;
+use super::history::HistoryFile;
+use super::history::HistoryLine;
+use super::history::Presence;
+use super::history::Session;
+use super::is_within;
+use super::report::break_time;
+use super::report::presence_time;
+use super::report::start_of_day;
+use super::report::task_time;
+use super::report::workday;
+use super::report::Period;
+use super::wal;
+use super::DateTime;
+use super::ForgetableDateTime;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+use std::time::SystemTime;
+
+//==============================================================================
+//
+//                              Type Definitions
+//
+//==============================================================================
+
+/// The contents of the history.index file: the totals of the history.log entries before a
+/// checkpoint, so the totals of the whole history only need the entries after it to be read.
+/// See docs/files.md.
+#[derive(Serialize, Deserialize)]
+struct Index {
+    version: u32,
+    /// The time days started at and the time zone when the entries were split into days.
+    day_start: NaiveTime,
+    zone: String,
+    /// Byte offset of the checkpoint: the entries before it are counted by the index.
+    offset: u64,
+    /// Checksum of the history before the checkpoint.
+    checksum: u64,
+    days: BTreeMap<NaiveDate, Day>,
+    /// Seconds worked on each task.
+    tasks: BTreeMap<String, i64>,
+    /// Seconds worked on each task since its last commit, i.e. not logged yet.
+    unlogged: BTreeMap<String, i64>,
+    presence: OpenPeriods,
+    breaks: OpenPeriods,
+    sessions: BTreeMap<String, OpenPeriods>,
+}
+
+/// Seconds spent at the workplace, on breaks and on each task in a day.
+#[derive(Serialize, Deserialize, Default)]
+struct Day {
+    presence: i64,
+    breaks: i64,
+    tasks: BTreeMap<String, i64>,
+}
+
+/// The periods of a sequence still open at the checkpoint. Only the last one can be closed by
+/// a later entry: the others were opened again before being closed, so they never are.
+#[derive(Serialize, Deserialize, Default)]
+struct OpenPeriods {
+    starts: Vec<OpenPeriod>,
+    last_is_open: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct OpenPeriod {
+    /// Byte offset of the entry opening the period, which keeps the order of the entries.
+    offset: u64,
+    start: DateTime,
+}
+
+/// The totals of the history: the ones of the index, plus the ones of the entries after its
+/// checkpoint. Periods must start and end at the start of a day.
+pub struct Totals {
+    index: Index,
+    /// The periods open at the checkpoint and the ones of the entries after it.
+    sessions: Vec<Session>,
+    presences: Vec<Presence>,
+    breaks: Vec<Break>,
+    /// The sessions after the last commit of their task, among the ones above.
+    uncommitted: Vec<Session>,
+}
+
+const VERSION: u32 = 2;
+
+/// How many days before today are left after the checkpoint, so recent entries can be changed
+/// without the index being rebuilt.
+const RECENT_DAYS: i64 = 7;
+
+//==============================================================================
+//
+//                                   Totals
+//
+//==============================================================================
+
+impl Totals {
+    /// Loads the index of the history, rebuilding it if it's missing or stale, and reads the
+    /// entries after its checkpoint. Entries older than `RECENT_DAYS` are added to the index,
+    /// moving the checkpoint forward.
+    pub fn load(history: &HistoryFile, now: DateTime) -> Result<Totals, HistoryError> {
+        let path = index_path(history.path());
+        let size = history.size()?;
+
+        let (mut index, lines, is_outdated) = match Index::load(&path, history.path(), size) {
+            Some((index, is_checked)) => {
+                let lines = history.read_from(index.offset)?;
+                (index, lines, is_checked)
+            }
+            None => (Index::new(), history.read()?, true),
+        };
+
+        let cutoff = start_of_day(workday(now) - Duration::days(RECENT_DAYS));
+        let count = lines
+            .iter()
+            .position(|line| line.entry.datetime().is_some_and(|other| other >= cutoff))
+            .unwrap_or(lines.len());
+
+        // Commands only reading the history save the index too, holding just a shared lock,
+        // which is why it's saved by renaming a new file over it. The index only saves time,
+        // so failing to save it isn't an error.
+        if is_outdated || count > 0 {
+            let offset = lines.get(count).map_or(size, |line| line.offset);
+
+            index.add(&lines[..count]);
+            index.checksum = checksum(history.path(), index.checksum, index.offset, offset)?;
+            index.offset = offset;
+
+            let _ = index.save(&path);
+        }
+
+        let mut entries = index.open_entries();
+        entries.extend(lines.into_iter().skip(count).map(|line| line.entry));
+
+        // Commits after the checkpoint log the time of the index as well.
+        let mut last_commits: BTreeMap<&str, usize> = BTreeMap::new();
+
+        for (position, entry) in entries.iter().enumerate() {
+            if let HistoryEntry::Commit { mnemonic, .. } = entry {
+                index.unlogged.remove(mnemonic);
+                last_commits.insert(mnemonic, position);
+            }
+        }
+
+        let uncommitted: Vec<&HistoryEntry> = entries
+            .iter()
+            .enumerate()
+            .filter(|(position, entry)| {
+                entry.mnemonic().is_some_and(|mnemonic| {
+                    last_commits
+                        .get(mnemonic)
+                        .is_none_or(|commit| position > commit)
+                })
+            })
+            .map(|(_, entry)| entry)
+            .collect();
+
+        Ok(Totals {
+            sessions: sessions(&entries),
+            presences: presences(&entries),
+            breaks: breaks(&entries),
+            uncommitted: sessions(uncommitted),
+            index,
+        })
+    }
+
+    /// The sessions open at the checkpoint and the ones after it.
+    pub fn sessions(&self) -> &[Session] {
+        &self.sessions
+    }
+
+    /// The periods at the workplace open at the checkpoint and the ones after it.
+    pub fn presences(&self) -> &[Presence] {
+        &self.presences
+    }
+
+    /// The breaks open at the checkpoint and the ones after it.
+    pub fn breaks(&self) -> &[Break] {
+        &self.breaks
+    }
+
+    /// Time worked within the period: the time spent at the workplace except for the breaks,
+    /// or the time worked on a task and the tasks within its namespace.
+    pub fn worked(&self, mnemonic: Option<&str>, period: &Period, now: DateTime) -> Duration {
+        match mnemonic {
+            Some(namespace) => {
+                let seconds = self
+                    .index
+                    .days_within(period)
+                    .flat_map(|day| &day.tasks)
+                    .filter(|(task, _)| is_within(task, namespace))
+                    .map(|(_, seconds)| seconds)
+                    .sum();
+
+                Duration::seconds(seconds) + task_time(&self.sessions, namespace, period, now)
+            }
+            None => {
+                let seconds = self.index.days_within(period).map(|day| day.presence).sum();
+
+                Duration::seconds(seconds) + presence_time(&self.presences, period, now)
+                    - self.break_time(period, now)
+            }
+        }
+    }
+
+    /// Time spent on breaks within the period.
+    pub fn break_time(&self, period: &Period, now: DateTime) -> Duration {
+        let seconds = self.index.days_within(period).map(|day| day.breaks).sum();
+        Duration::seconds(seconds) + break_time(&self.breaks, period, now)
+    }
+
+    /// Time worked on a task and the tasks within its namespace in the whole history.
+    pub fn task_total(&self, namespace: &str, now: DateTime) -> Duration {
+        let seconds = self
+            .index
+            .tasks
+            .iter()
+            .filter(|(task, _)| is_within(task, namespace))
+            .map(|(_, seconds)| seconds)
+            .sum();
+
+        Duration::seconds(seconds) + task_time(&self.sessions, namespace, &Period::all(), now)
+    }
+
+    /// Time worked on a task and the tasks within its namespace since their last commits.
+    pub fn unlogged(&self, namespace: &str, now: DateTime) -> Duration {
+        let seconds = self
+            .index
+            .unlogged
+            .iter()
+            .filter(|(task, _)| is_within(task, namespace))
+            .map(|(_, seconds)| seconds)
+            .sum();
+
+        Duration::seconds(seconds) + task_time(&self.uncommitted, namespace, &Period::all(), now)
+    }
+}
+
+/// Removes the index of the history, after the history was changed in an unknown way.
+pub fn remove(history: &Path) -> io::Result<()> {
+    match fs::remove_file(index_path(history)) {
+        Err(ref error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Keeps the index of the history up to date with a change made at `offset` of the history,
+/// whose size was `size` before it. The index is removed if the change comes before its
+/// checkpoint, since its totals don't include it. Otherwise the index gets the modification
+/// time of the history, so it's known the history wasn't changed by another program. Only
+/// called while holding the exclusive lock, since the index is changed in place.
+pub fn after_write(history: &Path, offset: u64, size: u64) -> io::Result<()> {
+    let path = index_path(history);
+
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let is_stale = offset < size
+        && serde_json::from_reader::<_, Index>(io::BufReader::new(File::open(&path)?))
+            .map_or(true, |index| offset < index.offset);
+
+    if is_stale {
+        fs::remove_file(&path)
+    } else {
+        stamp(&path, history)
+    }
+}
+
+//==============================================================================
+//
+//                               Index Handling
+//
+//==============================================================================
+
+impl Index {
+    fn new() -> Index {
+        Index {
+            version: VERSION,
+            day_start: config::current().day_start(),
+            zone: zone(),
+            offset: 0,
+            checksum: wal::checksum(&[]),
+            days: BTreeMap::new(),
+            tasks: BTreeMap::new(),
+            unlogged: BTreeMap::new(),
+            presence: OpenPeriods::default(),
+            breaks: OpenPeriods::default(),
+            sessions: BTreeMap::new(),
+        }
+    }
+
+    /// Loads the index, unless it's missing, unreadable or stale: created by another version,
+    /// with other settings, or for a history changed before the checkpoint. If the history was
+    /// changed by another program, its entries before the checkpoint are checked against the
+    /// checksum, and the index is returned along with `true`, meaning it should be saved again
+    /// so they aren't checked every time.
+    fn load(path: &Path, history: &Path, size: u64) -> Option<(Index, bool)> {
+        let file = File::open(path).ok()?;
+        let index: Index = serde_json::from_reader(io::BufReader::new(file)).ok()?;
+
+        let is_valid = index.version == VERSION
+            && index.day_start == config::current().day_start()
+            && index.zone == zone()
+            && index.offset <= size;
+
+        if !is_valid {
+            return None;
+        }
+
+        if modified(history).ok()? <= modified(path).ok()? {
+            return Some((index, false));
+        }
+
+        let checksum = checksum(history, wal::checksum(&[]), 0, index.offset).ok()?;
+        Some(index)
+            .filter(|index| index.checksum == checksum)
+            .map(|index| (index, true))
+    }
+
+    /// Writes a new file and renames it over the old one, so instances reading the history at
+    /// the same time never see an incomplete index.
+    fn save(&self, path: &Path) -> io::Result<()> {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(format!(".{}.tmp", process::id()));
+
+        let temporary = path.with_file_name(name);
+        fs::write(&temporary, serde_json::to_vec(self)?)?;
+        fs::rename(&temporary, path)
+    }
+
+    /// Adds the periods closed by the entries, splitting them into days, and keeps the ones
+    /// left open.
+    fn add(&mut self, lines: &[HistoryLine]) {
+        for line in lines {
+            let offset = line.offset;
+
+            match &line.entry {
+                HistoryEntry::Enter { datetime } => self.presence.open(offset, datetime.datetime),
+                HistoryEntry::Exit { datetime } => {
+                    if let Some(start) = self.presence.close() {
+                        self.split(start, datetime.datetime, |day, seconds| {
+                            day.presence += seconds
+                        });
+                    }
+                }
+                HistoryEntry::Pause { datetime } => self.breaks.open(offset, datetime.datetime),
+                HistoryEntry::Resume { datetime } => {
+                    if let Some(start) = self.breaks.close() {
+                        self.split(start, datetime.datetime, |day, seconds| {
+                            day.breaks += seconds
+                        });
+                    }
+                }
+                HistoryEntry::Start {
+                    mnemonic, datetime, ..
+                } => self
+                    .sessions
+                    .entry(mnemonic.clone())
+                    .or_default()
+                    .open(offset, datetime.datetime),
+                HistoryEntry::Stop {
+                    mnemonic, datetime, ..
+                } => {
+                    let start = self.sessions.get_mut(mnemonic).and_then(OpenPeriods::close);
+
+                    if self
+                        .sessions
+                        .get(mnemonic)
+                        .is_some_and(|open| open.starts.is_empty())
+                    {
+                        self.sessions.remove(mnemonic);
+                    }
+
+                    if let Some(start) = start {
+                        let total = self.split(start, datetime.datetime, |day, seconds| {
+                            *day.tasks.entry(mnemonic.clone()).or_default() += seconds
+                        });
+
+                        *self.tasks.entry(mnemonic.clone()).or_default() += total;
+                        *self.unlogged.entry(mnemonic.clone()).or_default() += total;
+                    }
+                }
+                HistoryEntry::Commit { mnemonic, .. } => {
+                    self.unlogged.remove(mnemonic);
+                }
+            }
+        }
+    }
+
+    /// Adds the seconds of the interval within each day to it. Returns the seconds of the
+    /// whole interval.
+    fn split<F>(&mut self, start: DateTime, stop: DateTime, mut add: F) -> i64
+    where
+        F: FnMut(&mut Day, i64),
+    {
+        let mut total = 0;
+        let mut date = workday(start).pred();
+
+        // Days starting at a time skipped by a DST change may not line up with `workday`,
+        // hence the day before and the day after are checked too.
+        while date <= workday(stop).succ() {
+            let seconds = Period::days(date, date).overlap(start, stop).num_seconds();
+
+            if seconds > 0 {
+                add(self.days.entry(date).or_default(), seconds);
+                total += seconds;
+            }
+
+            date = date.succ();
+        }
+
+        total
+    }
+
+    /// The days entirely within the period.
+    fn days_within<'a>(&'a self, period: &'a Period) -> impl Iterator<Item = &'a Day> + 'a {
+        let first = workday(period.start)
+            .pred_opt()
+            .unwrap_or(chrono::naive::MIN_DATE);
+        let last = workday(period.end);
+
+        self.days
+            .range(first..=last)
+            .filter(move |(&date, _)| {
+                let day = Period::days(date, date);
+                day.start >= period.start && day.end <= period.end
+            })
+            .map(|(_, day)| day)
+    }
+
+    /// Entries opening the periods open at the checkpoint, in their original order, so they're
+    /// matched by the entries after it as if the whole history was read.
+    fn open_entries(&self) -> Vec<HistoryEntry> {
+        let mut entries: Vec<(u64, HistoryEntry)> = Vec::new();
+
+        self.presence.push_entries(
+            &mut entries,
+            |datetime| HistoryEntry::Enter { datetime },
+            |datetime| HistoryEntry::Exit { datetime },
+        );
+
+        self.breaks.push_entries(
+            &mut entries,
+            |datetime| HistoryEntry::Pause { datetime },
+            |datetime| HistoryEntry::Resume { datetime },
+        );
+
+        for (mnemonic, open) in &self.sessions {
+            open.push_entries(
+                &mut entries,
+                |datetime| HistoryEntry::Start {
+                    mnemonic: mnemonic.clone(),
+                    datetime,
+                    note: None,
+                },
+                |datetime| HistoryEntry::Stop {
+                    mnemonic: mnemonic.clone(),
+                    datetime,
+                    note: None,
+                },
+            );
+        }
+
+        entries.sort_by_key(|(offset, _)| *offset);
+        entries.into_iter().map(|(_, entry)| entry).collect()
+    }
+}
+
+impl OpenPeriods {
+    fn open(&mut self, offset: u64, start: DateTime) {
+        self.starts.push(OpenPeriod { offset, start });
+        self.last_is_open = true;
+    }
+
+    /// Closes the last period, returning its start, unless it's closed already.
+    fn close(&mut self) -> Option<DateTime> {
+        if self.last_is_open {
+            self.last_is_open = false;
+            self.starts.pop().map(|period| period.start)
+        } else {
+            None
+        }
+    }
+
+    /// Pushes the entries opening the periods. If the last period is closed, an empty period
+    /// is pushed after them, so a later closing entry doesn't close the others.
+    fn push_entries<O, C>(&self, entries: &mut Vec<(u64, HistoryEntry)>, opening: O, closing: C)
+    where
+        O: Fn(ForgetableDateTime) -> HistoryEntry,
+        C: Fn(ForgetableDateTime) -> HistoryEntry,
+    {
+        let datetime = |start| ForgetableDateTime {
+            datetime: start,
+            forgotten: false,
+        };
+
+        for period in &self.starts {
+            entries.push((period.offset, opening(datetime(period.start))));
+        }
+
+        if let (Some(last), false) = (self.starts.last(), self.last_is_open) {
+            entries.push((last.offset, opening(datetime(last.start))));
+            entries.push((last.offset, closing(datetime(last.start))));
+        }
+    }
+}
+
+/// The index of a history.log file is the history.index file next to it.
+fn index_path(history: &Path) -> PathBuf {
+    history.with_extension("index")
+}
+
+/// Continues the checksum over the bytes of the history from `start` to `end`.
+fn checksum(history: &Path, checksum: u64, start: u64, end: u64) -> io::Result<u64> {
+    if start >= end {
+        return Ok(checksum);
+    }
+
+    let mut file = File::open(history)?;
+    file.seek(SeekFrom::Start(start))?;
+
+    let mut reader = file.take(end - start);
+    let mut buffer = vec![0; 64 * 1024];
+    let mut checksum = checksum;
+
+    loop {
+        match reader.read(&mut buffer)? {
+            0 => return Ok(checksum),
+            count => checksum = wal::extend_checksum(checksum, &buffer[..count]),
+        }
+    }
+}
+
+/// Gives the index the modification time of the history.
+fn stamp(path: &Path, history: &Path) -> io::Result<()> {
+    OpenOptions::new()
+        .write(true)
+        .open(path)?
+        .set_modified(modified(history)?)
+}
+
+fn modified(path: &Path) -> io::Result<SystemTime> {
+    fs::metadata(path)?.modified()
+}
+
+/// Identifies the local time zone by its UTC offsets in January and July, which is enough to
+/// notice it was changed.
+fn zone() -> String {
+    [1, 7]
+        .iter()
+        .map(|&month| {
+            let moment = NaiveDate::from_ymd(2020, month, 1).and_hms(12, 0, 0);
+            Local.from_utc_datetime(&moment).offset().to_string()
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+//==============================================================================
+//
+//                                   Tests
+//
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::bill;
+    use crate::tests::temp_dir;
+    use crate::tests::use_berlin_time;
+    use crate::GoalPeriod;
+    use crate::Rounding;
+    use crate::RoundingDirection;
+    use crate::RoundingScope;
+
+    use std::time::Duration as StdDuration;
+
+    fn lines(texts: &[&str]) -> Vec<HistoryLine> {
+        let mut offset = 0;
+
+        texts
+            .iter()
+            .map(|&text| {
+                let line = HistoryLine {
+                    offset,
+                    text: text.to_string(),
+                    entry: text.parse().ok().unwrap(),
+                };
+
+                offset += text.len() as u64 + 1;
+                line
+            })
+            .collect()
+    }
+
+    fn date(day: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(day, "%Y-%m-%d").unwrap()
+    }
+
+    /// Twenty days of work on two tasks, with a session of a third one left open for two weeks
+    /// and one of the first one open at the end.
+    fn history_text() -> String {
+        let mut text = String::new();
+
+        for day in 1..=20 {
+            let line =
+                |entry: &str, time: &str| format!("{} 2026-06-{:02} {} +02:00\n", entry, day, time);
+
+            text += &line("enter", "9:00");
+            text += &line("start dev", "9:00");
+            text += &line("stop dev", "12:00");
+
+            if day == 5 {
+                text += &line("start qa", "12:00");
+            }
+
+            if day == 18 {
+                text += &line("stop qa", "12:00");
+            }
+
+            if day % 4 == 0 {
+                text += "commit dev\n";
+            }
+
+            text += &line("pause", "12:00");
+            text += &line("resume", "13:00");
+            text += &line("start ops", "13:00");
+
+            if day < 20 {
+                text += &line("stop ops", "17:30");
+
+                if day % 3 == 0 {
+                    text += "commit ops\n";
+                }
+
+                text += &line("exit", "17:30");
+            }
+        }
+
+        text
+    }
+
+    fn now() -> DateTime {
+        Local.ymd(2026, 6, 20).and_hms(16, 0, 0)
+    }
+
+    fn setup(name: &str) -> HistoryFile {
+        use_berlin_time();
+        let path = temp_dir(name).join("history.log");
+        fs::write(&path, history_text()).unwrap();
+        HistoryFile::new(path)
+    }
+
+    /// Checks the totals against the ones of the whole history.
+    fn check(totals: &Totals, history: &HistoryFile) {
+        let entries: Vec<HistoryEntry> = history
+            .read()
+            .ok()
+            .unwrap()
+            .into_iter()
+            .map(|line| line.entry)
+            .collect();
+
+        let all_sessions = sessions(&entries);
+        let all_presences = presences(&entries);
+        let all_breaks = breaks(&entries);
+        let now = now();
+
+        let periods = [
+            Period::containing(GoalPeriod::Day, now),
+            Period::containing(GoalPeriod::Week, now),
+            Period::containing(GoalPeriod::Month, now),
+            Period::days(date("2026-06-03"), date("2026-06-09")),
+        ];
+
+        for period in &periods {
+            assert_eq!(
+                totals.worked(None, period, now),
+                presence_time(&all_presences, period, now) - break_time(&all_breaks, period, now)
+            );
+
+            for task in &["dev", "ops", "qa"] {
+                assert_eq!(
+                    totals.worked(Some(task), period, now),
+                    task_time(&all_sessions, task, period, now)
+                );
+            }
+        }
+
+        let rounding = Rounding {
+            direction: RoundingDirection::Down,
+            step: 1,
+            per: RoundingScope::Commit,
+        };
+
+        for task in &["dev", "ops", "qa"] {
+            assert_eq!(
+                totals.task_total(task, now),
+                task_time(&all_sessions, task, &Period::all(), now)
+            );
+            assert_eq!(
+                totals.unlogged(task, now),
+                bill(&entries, task, &rounding, now).worked
+            );
+        }
+    }
+
+    #[test]
+    fn sessions_are_split_into_days() {
+        use_berlin_time();
+        let mut index = Index::new();
+
+        index.add(&lines(&[
+            "start dev 2026-06-10 22:00 +02:00",
+            "stop dev 2026-06-11 2:30 +02:00",
+            "enter 2026-03-29 0:00 +01:00",
+            "exit 2026-03-30 0:00 +02:00",
+        ]));
+
+        assert_eq!(index.days[&date("2026-06-10")].tasks["dev"], 2 * 3600);
+        assert_eq!(index.days[&date("2026-06-11")].tasks["dev"], 5 * 1800);
+        assert_eq!(index.tasks["dev"], 9 * 1800);
+        assert_eq!(index.unlogged["dev"], 9 * 1800);
+
+        // The day the clocks are put forward is an hour shorter.
+        assert_eq!(index.days[&date("2026-03-29")].presence, 23 * 3600);
+        assert!(!index.days.contains_key(&date("2026-03-30")));
+    }
+
+    #[test]
+    fn open_periods_are_kept() {
+        use_berlin_time();
+        let mut index = Index::new();
+
+        index.add(&lines(&[
+            "start dev 2026-06-10 9:00 +02:00",
+            "start dev 2026-06-10 10:00 +02:00",
+            "stop dev 2026-06-10 11:00 +02:00",
+            "commit dev",
+            "enter 2026-06-10 8:00 +02:00",
+        ]));
+
+        assert_eq!(index.tasks["dev"], 3600);
+        assert!(!index.unlogged.contains_key("dev"));
+        assert_eq!(index.sessions["dev"].starts.len(), 1);
+        assert!(!index.sessions["dev"].last_is_open);
+        assert!(index.presence.last_is_open);
+
+        // The session started at 9:00 is never stopped, so it's only counted as open.
+        let entries = index.open_entries();
+        let open = sessions(&entries);
+
+        assert_eq!(open.iter().filter(|session| session.is_open()).count(), 1);
+    }
+
+    #[test]
+    fn totals_match_the_whole_history() {
+        let history = setup("index-totals");
+
+        let totals = Totals::load(&history, now()).ok().unwrap();
+        check(&totals, &history);
+
+        let index = Index::load(
+            &index_path(history.path()),
+            history.path(),
+            history.size().ok().unwrap(),
+        );
+        let (index, is_checked) = index.unwrap();
+        assert!(index.offset > 0);
+        assert!(!is_checked);
+
+        let totals = Totals::load(&history, now()).ok().unwrap();
+        check(&totals, &history);
+    }
+
+    #[test]
+    fn a_change_before_the_checkpoint_removes_the_index() {
+        let history = setup("index-change");
+        Totals::load(&history, now()).ok().unwrap();
+
+        let path = index_path(history.path());
+        let size = history.size().ok().unwrap();
+
+        after_write(history.path(), size, size).unwrap();
+        assert!(path.exists());
+
+        after_write(history.path(), 0, size).unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn a_hand_edit_before_the_checkpoint_is_noticed() {
+        let history = setup("index-edit");
+        Totals::load(&history, now()).ok().unwrap();
+
+        // The same length, so only the checksum tells.
+        let text =
+            history_text().replacen("stop dev 2026-06-01 12:00", "stop dev 2026-06-01 11:00", 1);
+        fs::write(history.path(), text).unwrap();
+        make_stale(history.path());
+
+        let size = history.size().ok().unwrap();
+        assert!(Index::load(&index_path(history.path()), history.path(), size).is_none());
+
+        let totals = Totals::load(&history, now()).ok().unwrap();
+        check(&totals, &history);
+    }
+
+    #[test]
+    fn a_hand_edit_after_the_checkpoint_keeps_the_index() {
+        let history = setup("index-recent-edit");
+        Totals::load(&history, now()).ok().unwrap();
+
+        let text =
+            history_text().replacen("stop dev 2026-06-19 12:00", "stop dev 2026-06-19 11:00", 1);
+        fs::write(history.path(), text).unwrap();
+        make_stale(history.path());
+
+        let path = index_path(history.path());
+        let size = history.size().ok().unwrap();
+        assert!(matches!(
+            Index::load(&path, history.path(), size),
+            Some((_, true))
+        ));
+
+        // Once saved again, the history isn't checked anymore.
+        let totals = Totals::load(&history, now()).ok().unwrap();
+        check(&totals, &history);
+        assert!(matches!(
+            Index::load(&path, history.path(), size),
+            Some((_, false))
+        ));
+    }
+
+    #[test]
+    fn recovering_the_history_removes_the_index() {
+        let history = setup("index-recover");
+        Totals::load(&history, now()).ok().unwrap();
+
+        let tail = b"commit dev\n";
+        let size = history.size().ok().unwrap();
+        let mut wal =
+            format!("{} {} {:016x}\n", size, tail.len(), wal::checksum(tail)).into_bytes();
+        wal.extend_from_slice(tail);
+        fs::write(history.path().with_extension("log.wal"), wal).unwrap();
+
+        assert!(history.recover().ok().unwrap());
+        assert!(!index_path(history.path()).exists());
+
+        let totals = Totals::load(&history, now()).ok().unwrap();
+        check(&totals, &history);
+        assert_eq!(totals.unlogged("dev", now()), Duration::zero());
+    }
+
+    /// Makes the history look changed after the index was saved.
+    fn make_stale(history: &Path) {
+        let earlier = SystemTime::now() - StdDuration::from_secs(10);
+
+        OpenOptions::new()
+            .write(true)
+            .open(index_path(history))
+            .unwrap()
+            .set_modified(earlier)
+            .unwrap();
+    }
+}
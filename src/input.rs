@@ -4,20 +4,55 @@ use chrono::prelude::*;
 
 use core::str::FromStr;
 
+use lazy_static::lazy_static;
+
+use regex::Regex;
+
+use super::config;
+use super::config::DateOrder;
+use super::data::Absence;
+use super::local_moments;
 use super::parse_duration;
+use super::report::workday;
+use super::AbsenceKind;
+use super::AmendAction;
 use super::Command;
+use super::ConfigAction;
 use super::DateTime;
+use super::DateTimeParseError;
+use super::DeleteMode;
 use super::DurationParseError;
+use super::EntrySelector;
+use super::ExchangeFormat;
 use super::ForgetableDateTime;
 use super::GoalAction;
 use super::GoalPeriod;
+use super::InvalidAbsenceKind;
+use super::InvalidEntrySelector;
+use super::InvalidExchangeFormat;
 use super::InvalidGoalPeriod;
+use super::InvalidInvoiceFormat;
+use super::InvalidMoney;
+use super::InvalidRoundingDirection;
+use super::InvalidRoundingScope;
+use super::InvoiceFormat;
+use super::Money;
+use super::ProfileAction;
+use super::ProfileSelection;
+use super::RateAction;
+use super::RateTarget;
+use super::Rounding;
+use super::RoundingAction;
+use super::RoundingDirection;
+use super::RoundingScope;
+use super::SessionFilter;
 
 use std::convert::From;
 use std::convert::TryFrom;
 use std::fmt::Display;
 use std::fmt::Error as FormatError;
 use std::fmt::Formatter;
+use std::num::ParseIntError;
 
 //==============================================================================
 //
@@ -35,6 +70,29 @@ pub enum CommandInput<'a> {
     Exit {
         datetime: ForgetableDateTimeInput<'a>,
     },
+    Pause {
+        datetime: ForgetableDateTimeInput<'a>,
+    },
+    Resume {
+        datetime: ForgetableDateTimeInput<'a>,
+    },
+    Break {
+        start: &'a str,
+        duration: Option<&'a str>,
+    },
+    Breaks,
+    Absence {
+        start: &'a str,
+        end: Option<&'a str>,
+        kind: Option<&'a str>,
+        half: bool,
+        name: Option<&'a str>,
+        erase: bool,
+    },
+    Absences,
+    ImportHolidays {
+        path: &'a str,
+    },
     Create {
         mnemonic: &'a str,
         code: Option<&'a str>,
@@ -45,22 +103,47 @@ pub enum CommandInput<'a> {
     },
     Delete {
         mnemonic: &'a str,
+        archive: bool,
+        purge: bool,
+    },
+    Rename {
+        old: &'a str,
+        new: &'a str,
+    },
+    Merge {
+        from: &'a str,
+        into: &'a str,
     },
     Start {
-        mnemonic: &'a str,
+        mnemonic: Option<&'a str>,
         datetime: ForgetableDateTimeInput<'a>,
+        note: Option<&'a str>,
     },
     Stop {
         mnemonic: Option<&'a str>,
         datetime: ForgetableDateTimeInput<'a>,
+        note: Option<&'a str>,
         commit: bool,
     },
-    Commit {
+    Interrupt {
+        mnemonic: &'a str,
+        datetime: ForgetableDateTimeInput<'a>,
+        note: Option<&'a str>,
+    },
+    Switch {
         mnemonic: &'a str,
+        datetime: ForgetableDateTimeInput<'a>,
+        note: Option<&'a str>,
+        commit: bool,
+    },
+    Commit {
+        mnemonic: Option<&'a str>,
         datetime: Option<&'a str>,
+        note: Option<&'a str>,
     },
     Resolve {
         mnemonic: Option<&'a str>,
+        all: bool,
     },
     Goal {
         action: GoalActionInput<'a>,
@@ -70,9 +153,85 @@ pub enum CommandInput<'a> {
     Goals {
         mnemonic: Option<&'a str>,
     },
+    Rounding {
+        mnemonic: Option<&'a str>,
+        direction: Option<&'a str>,
+        step: Option<&'a str>,
+        per: Option<&'a str>,
+        erase: bool,
+    },
+    Rate {
+        mnemonic: Option<&'a str>,
+        client: Option<&'a str>,
+        rate: Option<&'a str>,
+        erase: bool,
+    },
+    Client {
+        mnemonic: &'a str,
+        client: Option<&'a str>,
+    },
+    Invoice {
+        first: &'a str,
+        last: &'a str,
+        format: Option<&'a str>,
+        client: Option<&'a str>,
+        committed: bool,
+        uncommitted: bool,
+        preview: bool,
+    },
     Status {
         mnemonic: Option<&'a str>,
     },
+    Report {
+        period: Option<&'a str>,
+        datetime: Option<&'a str>,
+        mnemonic: Option<&'a str>,
+        profiles: Option<&'a str>,
+    },
+    Balance {
+        since: Option<&'a str>,
+    },
+    Export {
+        format: &'a str,
+        mnemonic: Option<&'a str>,
+    },
+    Import {
+        format: &'a str,
+        path: &'a str,
+    },
+    Undo,
+    Log {
+        mnemonic: Option<&'a str>,
+        count: Option<&'a str>,
+    },
+    Amend {
+        entry: &'a str,
+        datetime: Option<&'a str>,
+        forgotten: bool,
+        mnemonic: Option<&'a str>,
+        delete: bool,
+    },
+    ConfigGet {
+        key: Option<&'a str>,
+    },
+    ConfigSet {
+        key: &'a str,
+        value: &'a str,
+    },
+    ConfigUnset {
+        key: &'a str,
+    },
+    Doctor {
+        days: Option<&'a str>,
+    },
+    Repair,
+    ProfileList,
+    ProfileCreate {
+        name: &'a str,
+    },
+    ProfileUse {
+        name: &'a str,
+    },
 }
 
 pub struct ForgetableDateTimeInput<'a> {
@@ -104,10 +263,24 @@ pub enum GoalActionParseError {
 }
 
 pub enum CommandParseError {
-    DateTimeParseError(chrono::format::ParseError),
+    DateTimeParseError(DateTimeParseError),
     DurationParseError(DurationParseError),
     InvalidGoalPeriod(InvalidGoalPeriod),
+    InvalidAbsenceKind(InvalidAbsenceKind),
+    InvalidRoundingDirection(InvalidRoundingDirection),
+    InvalidRoundingScope(InvalidRoundingScope),
+    InvalidRoundingStep,
+    InvalidMoney(InvalidMoney),
+    InvalidInvoiceFormat(InvalidInvoiceFormat),
     GoalActionParseError(GoalActionParseError),
+    InvalidExchangeFormat(InvalidExchangeFormat),
+    InvalidEntrySelector(InvalidEntrySelector),
+    InvalidCount(ParseIntError),
+    InvalidDays(ParseIntError),
+    MissingAmendment,
+    MissingMnemonic,
+    InvalidReportPeriod,
+    InvalidDateRange,
 }
 
 //==============================================================================
@@ -119,7 +292,7 @@ pub enum CommandParseError {
 impl<'a> TryFrom<CommandInput<'a>> for Command<'a> {
     type Error = CommandParseError;
 
-    fn try_from(input: CommandInput<'a>) -> Result<Command, Self::Error> {
+    fn try_from(input: CommandInput<'a>) -> Result<Command<'a>, Self::Error> {
         Ok(match input {
             CommandInput::Enter { datetime } => Command::Enter {
                 datetime: ForgetableDateTime::try_from(datetime)?,
@@ -127,27 +300,100 @@ impl<'a> TryFrom<CommandInput<'a>> for Command<'a> {
             CommandInput::Exit { datetime } => Command::Exit {
                 datetime: ForgetableDateTime::try_from(datetime)?,
             },
+            CommandInput::Pause { datetime } => Command::Pause {
+                datetime: ForgetableDateTime::try_from(datetime)?,
+            },
+            CommandInput::Resume { datetime } => Command::Resume {
+                datetime: ForgetableDateTime::try_from(datetime)?,
+            },
+            CommandInput::Break { start, duration } => Command::Break {
+                start: parse_time(start)?,
+                duration: duration.map(parse_duration).transpose()?,
+            },
+            CommandInput::Breaks => Command::Breaks,
+            CommandInput::Absence {
+                start,
+                end,
+                kind,
+                half,
+                name,
+                erase,
+            } => Command::Absence {
+                date: parse_date(start, workday(Local::now()))?,
+                absence: if erase {
+                    None
+                } else {
+                    Some(parse_absence(start, end, kind, half, name)?)
+                },
+            },
+            CommandInput::Absences => Command::Absences,
+            CommandInput::ImportHolidays { path } => Command::ImportHolidays { path },
             CommandInput::Create { mnemonic, code } => Command::Create { mnemonic, code },
             CommandInput::Edit { mnemonic, code } => Command::Edit { mnemonic, code },
-            CommandInput::Delete { mnemonic } => Command::Delete { mnemonic },
-            CommandInput::Start { mnemonic, datetime } => Command::Start {
+            CommandInput::Delete {
                 mnemonic,
+                archive,
+                purge,
+            } => Command::Delete {
+                mnemonic,
+                mode: match (archive, purge) {
+                    (true, _) => DeleteMode::Archive,
+                    (_, true) => DeleteMode::Purge,
+                    _ => DeleteMode::Default,
+                },
+            },
+            CommandInput::Rename { old, new } => Command::Rename { old, new },
+            CommandInput::Merge { from, into } => Command::Merge { from, into },
+            CommandInput::Start {
+                mnemonic,
+                datetime,
+                note,
+            } => Command::Start {
+                mnemonic: mnemonic_or_default(mnemonic)?,
                 datetime: ForgetableDateTime::try_from(datetime)?,
+                note,
             },
             CommandInput::Stop {
                 mnemonic,
                 datetime,
+                note,
                 commit,
             } => Command::Stop {
                 mnemonic,
                 datetime: ForgetableDateTime::try_from(datetime)?,
+                note,
                 commit,
             },
-            CommandInput::Commit { mnemonic, datetime } => Command::Commit {
+            CommandInput::Interrupt {
                 mnemonic,
+                datetime,
+                note,
+            } => Command::Interrupt {
+                mnemonic,
+                datetime: ForgetableDateTime::try_from(datetime)?,
+                note,
+            },
+            CommandInput::Switch {
+                mnemonic,
+                datetime,
+                note,
+                commit,
+            } => Command::Switch {
+                mnemonic,
+                datetime: ForgetableDateTime::try_from(datetime)?,
+                note,
+                commit,
+            },
+            CommandInput::Commit {
+                mnemonic,
+                datetime,
+                note,
+            } => Command::Commit {
+                mnemonic: mnemonic_or_default(mnemonic)?,
                 datetime: parse_datetime_or_now(datetime)?,
+                note,
             },
-            CommandInput::Resolve { mnemonic } => Command::Resolve { mnemonic },
+            CommandInput::Resolve { mnemonic, all } => Command::Resolve { mnemonic, all },
             CommandInput::Goal {
                 action,
                 arg,
@@ -157,13 +403,144 @@ impl<'a> TryFrom<CommandInput<'a>> for Command<'a> {
                 mnemonic,
             },
             CommandInput::Goals { mnemonic } => Command::Goals { mnemonic },
+            CommandInput::Rounding {
+                mnemonic,
+                direction,
+                step,
+                per,
+                erase,
+            } => Command::Rounding {
+                mnemonic,
+                action: match (erase, direction) {
+                    (true, _) => RoundingAction::Erase,
+                    (false, Some(direction)) => {
+                        RoundingAction::Set(parse_rounding(direction, step, per)?)
+                    }
+                    (false, None) => RoundingAction::Show,
+                },
+            },
+            CommandInput::Rate {
+                mnemonic,
+                client,
+                rate,
+                erase,
+            } => Command::Rate {
+                target: match (mnemonic, client) {
+                    (Some(mnemonic), _) => RateTarget::Task(mnemonic),
+                    (None, Some(client)) => RateTarget::Client(client),
+                    (None, None) => RateTarget::Default,
+                },
+                action: match (erase, rate) {
+                    (true, _) => RateAction::Erase,
+                    (false, Some(rate)) => RateAction::Set(Money::from_str(rate)?),
+                    (false, None) => RateAction::Show,
+                },
+            },
+            CommandInput::Client { mnemonic, client } => Command::Client { mnemonic, client },
+            CommandInput::Invoice {
+                first,
+                last,
+                format,
+                client,
+                committed,
+                uncommitted,
+                preview,
+            } => {
+                let today = workday(Local::now());
+                let (first, last) = (parse_date(first, today)?, parse_date(last, today)?);
+
+                if last < first {
+                    return Err(CommandParseError::InvalidDateRange);
+                }
+
+                Command::Invoice {
+                    first,
+                    last,
+                    format: format
+                        .map(InvoiceFormat::from_str)
+                        .transpose()?
+                        .unwrap_or(InvoiceFormat::Markdown),
+                    client,
+                    sessions: match (committed, uncommitted) {
+                        (true, _) => SessionFilter::Committed,
+                        (_, true) => SessionFilter::Uncommitted,
+                        _ => SessionFilter::All,
+                    },
+                    preview,
+                }
+            }
             CommandInput::Status { mnemonic } => Command::Status { mnemonic },
+            CommandInput::Report {
+                period,
+                datetime,
+                mnemonic,
+                profiles,
+            } => Command::Report {
+                period: parse_report_period(period)?,
+                datetime: parse_datetime_or_now(datetime)?,
+                mnemonic,
+                profiles: profiles.map(parse_profile_selection),
+            },
+            CommandInput::Balance { since } => Command::Balance {
+                since: since
+                    .map(|since| parse_date(since, workday(Local::now())))
+                    .transpose()?,
+            },
+            CommandInput::Export { format, mnemonic } => Command::Export {
+                format: ExchangeFormat::from_str(format)?,
+                mnemonic,
+            },
+            CommandInput::Import { format, path } => Command::Import {
+                format: ExchangeFormat::from_str(format)?,
+                path,
+            },
+            CommandInput::Undo => Command::Undo,
+            CommandInput::Log { mnemonic, count } => Command::Log {
+                mnemonic,
+                count: count.map(str::parse).transpose()?.unwrap_or(20),
+            },
+            CommandInput::Amend {
+                entry,
+                datetime,
+                forgotten,
+                mnemonic,
+                delete,
+            } => Command::Amend {
+                entry: EntrySelector::parse(entry)?,
+                action: parse_amend_action(datetime, forgotten, mnemonic, delete)?,
+            },
+            CommandInput::ConfigGet { key } => Command::Config {
+                action: ConfigAction::Get(key),
+            },
+            CommandInput::ConfigSet { key, value } => Command::Config {
+                action: ConfigAction::Set(key, value),
+            },
+            CommandInput::ConfigUnset { key } => Command::Config {
+                action: ConfigAction::Unset(key),
+            },
+            CommandInput::Doctor { days } => Command::Doctor {
+                days: days
+                    .map(str::parse)
+                    .transpose()
+                    .map_err(CommandParseError::InvalidDays)?
+                    .unwrap_or(7),
+            },
+            CommandInput::Repair => Command::Repair,
+            CommandInput::ProfileList => Command::Profile {
+                action: ProfileAction::List,
+            },
+            CommandInput::ProfileCreate { name } => Command::Profile {
+                action: ProfileAction::Create(name),
+            },
+            CommandInput::ProfileUse { name } => Command::Profile {
+                action: ProfileAction::Use(name),
+            },
         })
     }
 }
 
 impl<'a> TryFrom<ForgetableDateTimeInput<'a>> for ForgetableDateTime {
-    type Error = chrono::format::ParseError;
+    type Error = DateTimeParseError;
 
     fn try_from(input: ForgetableDateTimeInput<'a>) -> Result<ForgetableDateTime, Self::Error> {
         Ok(ForgetableDateTime {
@@ -191,8 +568,8 @@ impl From<DurationParseError> for GoalActionParseError {
     }
 }
 
-impl From<chrono::format::ParseError> for CommandParseError {
-    fn from(error: chrono::format::ParseError) -> CommandParseError {
+impl From<DateTimeParseError> for CommandParseError {
+    fn from(error: DateTimeParseError) -> CommandParseError {
         CommandParseError::DateTimeParseError(error)
     }
 }
@@ -209,12 +586,60 @@ impl From<InvalidGoalPeriod> for CommandParseError {
     }
 }
 
+impl From<InvalidAbsenceKind> for CommandParseError {
+    fn from(error: InvalidAbsenceKind) -> CommandParseError {
+        CommandParseError::InvalidAbsenceKind(error)
+    }
+}
+
+impl From<InvalidRoundingDirection> for CommandParseError {
+    fn from(error: InvalidRoundingDirection) -> CommandParseError {
+        CommandParseError::InvalidRoundingDirection(error)
+    }
+}
+
+impl From<InvalidRoundingScope> for CommandParseError {
+    fn from(error: InvalidRoundingScope) -> CommandParseError {
+        CommandParseError::InvalidRoundingScope(error)
+    }
+}
+
+impl From<InvalidMoney> for CommandParseError {
+    fn from(error: InvalidMoney) -> CommandParseError {
+        CommandParseError::InvalidMoney(error)
+    }
+}
+
+impl From<InvalidInvoiceFormat> for CommandParseError {
+    fn from(error: InvalidInvoiceFormat) -> CommandParseError {
+        CommandParseError::InvalidInvoiceFormat(error)
+    }
+}
+
 impl From<GoalActionParseError> for CommandParseError {
     fn from(error: GoalActionParseError) -> CommandParseError {
         CommandParseError::GoalActionParseError(error)
     }
 }
 
+impl From<InvalidExchangeFormat> for CommandParseError {
+    fn from(error: InvalidExchangeFormat) -> CommandParseError {
+        CommandParseError::InvalidExchangeFormat(error)
+    }
+}
+
+impl From<InvalidEntrySelector> for CommandParseError {
+    fn from(error: InvalidEntrySelector) -> CommandParseError {
+        CommandParseError::InvalidEntrySelector(error)
+    }
+}
+
+impl From<ParseIntError> for CommandParseError {
+    fn from(error: ParseIntError) -> CommandParseError {
+        CommandParseError::InvalidCount(error)
+    }
+}
+
 //==============================================================================
 //
 //                           Display Implementations
@@ -236,6 +661,36 @@ impl Display for DurationParseError {
     }
 }
 
+impl Display for DateTimeParseError {
+    fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FormatError> {
+        match self {
+            DateTimeParseError::InvalidFormat => write!(
+                f,
+                "expected a date and/or a time, e.g. `{}`, or an RFC 3339 date/time",
+                match config::current().date_order() {
+                    DateOrder::DayMonthYear => "25/01/2019 14:15",
+                    DateOrder::MonthDayYear => "01/25/2019 14:15",
+                    DateOrder::YearMonthDay => "2019/01/25 14:15",
+                }
+            ),
+            DateTimeParseError::InvalidDate => write!(f, "no such date"),
+            DateTimeParseError::InvalidTime => write!(f, "no such time"),
+            DateTimeParseError::NonExistent(datetime) => write!(
+                f,
+                "{} doesn't exist in the local time zone, it's skipped by a DST change",
+                datetime.format("%Y-%m-%d %H:%M")
+            ),
+            DateTimeParseError::Ambiguous(first, second) => write!(
+                f,
+                "{} happens twice in the local time zone because of a DST change, provide the UTC offset, e.g. {} or {}",
+                first.format("%Y-%m-%d %H:%M"),
+                first.to_rfc3339(),
+                second.to_rfc3339()
+            ),
+        }
+    }
+}
+
 impl Display for GoalActionParseError {
     fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FormatError> {
         match self {
@@ -249,7 +704,7 @@ impl Display for GoalActionParseError {
 
 impl Display for CommandParseError {
     fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FormatError> {
-        write!(f, "{}: ", Color::Red.paint("error").to_string())?;
+        write!(f, "{}: ", config::current().paint(Color::Red, "error"))?;
 
         match self {
             CommandParseError::DateTimeParseError(error) => {
@@ -264,10 +719,72 @@ impl Display for CommandParseError {
                 writeln!(f, "could not parse the period argument.")?;
                 write!(f, "cause: {}", error)
             }
+            CommandParseError::InvalidAbsenceKind(error) => {
+                writeln!(f, "could not parse the type argument.")?;
+                write!(f, "cause: {}", error)
+            }
+            CommandParseError::InvalidRoundingDirection(error) => {
+                writeln!(f, "could not parse the direction argument.")?;
+                write!(f, "cause: {}", error)
+            }
+            CommandParseError::InvalidRoundingScope(error) => {
+                writeln!(f, "could not parse the per argument.")?;
+                write!(f, "cause: {}", error)
+            }
+            CommandParseError::InvalidRoundingStep => {
+                writeln!(f, "could not parse the step argument.")?;
+                write!(
+                    f,
+                    "cause: the step must be a number of minutes that divides an hour, e.g. 5, 6, 15 or 30."
+                )
+            }
+            CommandParseError::InvalidMoney(error) => {
+                writeln!(f, "could not parse the rate argument.")?;
+                write!(f, "cause: {}", error)
+            }
+            CommandParseError::InvalidInvoiceFormat(error) => {
+                writeln!(f, "could not parse the format argument.")?;
+                write!(f, "cause: {}", error)
+            }
             CommandParseError::GoalActionParseError(error) => {
                 writeln!(f, "could not parse the goal action.")?;
                 write!(f, "cause: {}", error)
             }
+            CommandParseError::InvalidExchangeFormat(error) => {
+                writeln!(f, "could not parse the format argument.")?;
+                write!(f, "cause: {}", error)
+            }
+            CommandParseError::InvalidEntrySelector(error) => {
+                writeln!(f, "could not parse the entry argument.")?;
+                write!(f, "cause: {}", error)
+            }
+            CommandParseError::InvalidCount(error) => {
+                writeln!(f, "could not parse the count argument.")?;
+                write!(f, "cause: {}", error)
+            }
+            CommandParseError::InvalidDays(error) => {
+                writeln!(f, "could not parse the days argument.")?;
+                write!(f, "cause: {}", error)
+            }
+            CommandParseError::InvalidReportPeriod => {
+                writeln!(f, "could not parse the period argument.")?;
+                write!(f, "cause: valid period values: month, week, day.")
+            }
+            CommandParseError::InvalidDateRange => {
+                write!(f, "the last day comes before the first one.")
+            }
+            CommandParseError::MissingMnemonic => {
+                write!(
+                    f,
+                    "no task was given and there's no default task (see `timelog config`)."
+                )
+            }
+            CommandParseError::MissingAmendment => {
+                write!(
+                    f,
+                    "nothing to amend: provide a date/time, a task, --forgot or --delete."
+                )
+            }
         }
     }
 }
@@ -278,10 +795,203 @@ impl Display for CommandParseError {
 //
 //==============================================================================
 
-fn parse_datetime_or_now(input: Option<&str>) -> Result<DateTime, chrono::format::ParseError> {
-    input
-        .map(|s| DateTime::from_str(s))
-        .unwrap_or_else(|| Ok(Local::now()))
+fn parse_datetime_or_now(input: Option<&str>) -> Result<DateTime, DateTimeParseError> {
+    let now = Local::now();
+    input.map_or(Ok(now), |input| parse_datetime(input, now))
+}
+
+/// Date/times are accepted in the RFC 3339 format, e.g. `2019-02-22T14:15:00-03:00`, or as a
+/// date and a time separated by a space or a dash, e.g. `22/02 14:15` (see `parse_date` and
+/// `parse_time`). A date alone means the start of the day, and a time alone the most recent fit
+/// until now. Days start at the configured time, so with days starting at 04:00, `22/02 01:30`
+/// means the night after the 22nd.
+pub fn parse_datetime(input: &str, now: DateTime) -> Result<DateTime, DateTimeParseError> {
+    if let Ok(datetime) = DateTime::from_str(input) {
+        return Ok(datetime);
+    }
+
+    let input = input.trim();
+    let today = now.date().naive_local();
+    let day_start = config::current().day_start();
+    let is_time = |part: &str| part.contains(':') || part.ends_with('h');
+
+    match input.rfind(|c: char| c.is_whitespace() || c == '-') {
+        Some(index) if is_time(&input[index + 1..]) => {
+            let date = parse_date(input[..index].trim_end(), workday(now))?;
+            let time = parse_time(&input[index + 1..])?;
+
+            // The time before the start of the day belongs to the previous day.
+            if time < day_start {
+                local(date.succ().and_time(time))
+            } else {
+                local(date.and_time(time))
+            }
+        }
+        _ if is_time(input) => {
+            let time = parse_time(input)?;
+            let datetime = local(today.and_time(time))?;
+
+            if datetime > now {
+                local(today.pred().and_time(time))
+            } else {
+                Ok(datetime)
+            }
+        }
+        _ => local(parse_date(input, workday(now))?.and_time(day_start)),
+    }
+}
+
+/// Dates are accepted in the ISO 8601 format, e.g. `2019-02-22`, or abbreviated in the
+/// configured order, e.g. `22`, `22/02` or `22/02/2019` by default. Without the month or the
+/// year, the date is the most recent fit until today.
+fn parse_date(input: &str, today: NaiveDate) -> Result<NaiveDate, DateTimeParseError> {
+    if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        return Ok(date);
+    }
+
+    let parts = input
+        .split('/')
+        .map(str::parse::<u32>)
+        .collect::<Result<Vec<u32>, _>>()
+        .map_err(|_| DateTimeParseError::InvalidFormat)?;
+
+    let (year, month, day) = match (config::current().date_order(), parts.as_slice()) {
+        (_, &[day]) => (None, None, day),
+        (DateOrder::DayMonthYear, &[day, month]) => (None, Some(month), day),
+        (DateOrder::DayMonthYear, &[day, month, year]) => (Some(year), Some(month), day),
+        (DateOrder::MonthDayYear, &[month, day]) | (DateOrder::YearMonthDay, &[month, day]) => {
+            (None, Some(month), day)
+        }
+        (DateOrder::MonthDayYear, &[month, day, year])
+        | (DateOrder::YearMonthDay, &[year, month, day]) => (Some(year), Some(month), day),
+        _ => return Err(DateTimeParseError::InvalidFormat),
+    };
+
+    if day == 0 || day > 31 || month.is_some_and(|month| month == 0 || month > 12) {
+        return Err(DateTimeParseError::InvalidDate);
+    }
+
+    // Two-digit years belong to the current century.
+    let year = year.map(|year| {
+        if year < 100 {
+            year as i32 + 2000
+        } else {
+            year as i32
+        }
+    });
+
+    let candidates: Vec<(i32, u32)> = match (year, month) {
+        (Some(year), Some(month)) => vec![(year, month)],
+        (None, Some(month)) => (0..8).map(|back| (today.year() - back, month)).collect(),
+        _ => (0..12)
+            .map(|back| {
+                let months = today.year() * 12 + today.month0() as i32 - back;
+                (months.div_euclid(12), months.rem_euclid(12) as u32 + 1)
+            })
+            .collect(),
+    };
+
+    candidates
+        .into_iter()
+        .filter_map(|(year, month)| NaiveDate::from_ymd_opt(year, month, day))
+        .find(|&date| year.is_some() || date <= today)
+        .ok_or(DateTimeParseError::InvalidDate)
+}
+
+/// Times are accepted as `14h`, `14:15` or `14:15:54`.
+fn parse_time(input: &str) -> Result<NaiveTime, DateTimeParseError> {
+    lazy_static! {
+        static ref TIME_REGEX: Regex =
+            Regex::new(r"^(\d{1,2})(?:h|:(\d{2})(?::(\d{2}))?)$").unwrap();
+    }
+
+    let captures = TIME_REGEX
+        .captures(input)
+        .ok_or(DateTimeParseError::InvalidFormat)?;
+
+    let number = |index| {
+        captures
+            .get(index)
+            .map_or(0, |number| number.as_str().parse().unwrap_or(u32::MAX))
+    };
+
+    NaiveTime::from_hms_opt(number(1), number(2), number(3)).ok_or(DateTimeParseError::InvalidTime)
+}
+
+/// Converts a date/time to the local time zone. Date/times skipped or repeated by a DST change
+/// are refused, since there's no way to tell which moment is meant without a UTC offset.
+fn local(naive: NaiveDateTime) -> Result<DateTime, DateTimeParseError> {
+    match local_moments(naive).as_slice() {
+        [] => Err(DateTimeParseError::NonExistent(naive)),
+        &[datetime] => Ok(datetime),
+        &[first, second, ..] => Err(DateTimeParseError::Ambiguous(first, second)),
+    }
+}
+
+/// The given mnemonic, or the default task if there's one.
+fn mnemonic_or_default(mnemonic: Option<&str>) -> Result<&str, CommandParseError> {
+    mnemonic
+        .or_else(|| config::current().default_task.as_deref())
+        .ok_or(CommandParseError::MissingMnemonic)
+}
+
+/// An absence lasts only its first day unless the last one is provided. Vacation is the default
+/// type.
+fn parse_absence(
+    start: &str,
+    end: Option<&str>,
+    kind: Option<&str>,
+    half: bool,
+    name: Option<&str>,
+) -> Result<Absence, CommandParseError> {
+    let today = workday(Local::now());
+    let start = parse_date(start, today)?;
+
+    let end = end
+        .map(|end| parse_date(end, today))
+        .transpose()?
+        .unwrap_or(start);
+
+    if end < start {
+        return Err(CommandParseError::InvalidDateRange);
+    }
+
+    Ok(Absence {
+        kind: kind
+            .map(AbsenceKind::from_str)
+            .transpose()?
+            .unwrap_or(AbsenceKind::Vacation),
+        start,
+        end,
+        half,
+        name: name.map(String::from),
+    })
+}
+
+/// Rounding steps must fit a whole number of times in an hour. Without a step, the time is
+/// rounded to 15 minutes, and without a scope, only the total of each commit is rounded.
+fn parse_rounding(
+    direction: &str,
+    step: Option<&str>,
+    per: Option<&str>,
+) -> Result<Rounding, CommandParseError> {
+    let step = match step.map(str::parse::<u32>).transpose() {
+        Ok(step) => step.unwrap_or(15),
+        Err(_) => return Err(CommandParseError::InvalidRoundingStep),
+    };
+
+    if step == 0 || 60 % step != 0 {
+        return Err(CommandParseError::InvalidRoundingStep);
+    }
+
+    Ok(Rounding {
+        direction: RoundingDirection::from_str(direction)?,
+        step,
+        per: per
+            .map(RoundingScope::from_str)
+            .transpose()?
+            .unwrap_or_default(),
+    })
 }
 
 fn parse_goal_action<'a>(
@@ -301,3 +1011,113 @@ fn parse_goal_action<'a>(
         )),
     }
 }
+
+/// Reports cover a whole month, week or day. The week is the default.
+fn parse_report_period(input: Option<&str>) -> Result<GoalPeriod, CommandParseError> {
+    match input.map(GoalPeriod::from_str).transpose() {
+        Ok(None) => Ok(GoalPeriod::Week),
+        Ok(Some(GoalPeriod::Weekday(_))) | Err(_) => Err(CommandParseError::InvalidReportPeriod),
+        Ok(Some(period)) => Ok(period),
+    }
+}
+
+/// Either `all` or a comma-separated list of profile names.
+fn parse_profile_selection(input: &str) -> ProfileSelection<'_> {
+    if input == "all" {
+        ProfileSelection::All
+    } else {
+        ProfileSelection::Named(
+            input
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .collect(),
+        )
+    }
+}
+
+fn parse_amend_action<'a>(
+    datetime: Option<&'a str>,
+    forgotten: bool,
+    mnemonic: Option<&'a str>,
+    delete: bool,
+) -> Result<AmendAction<'a>, CommandParseError> {
+    if delete {
+        return Ok(AmendAction::Delete);
+    }
+
+    if datetime.is_none() && !forgotten && mnemonic.is_none() {
+        return Err(CommandParseError::MissingAmendment);
+    }
+
+    Ok(AmendAction::Change {
+        datetime: datetime
+            .map(|datetime| parse_datetime(datetime, Local::now()))
+            .transpose()?,
+        forgotten,
+        mnemonic,
+    })
+}
+
+//==============================================================================
+//
+//                                   Tests
+//
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::use_berlin_time;
+
+    use chrono::Duration;
+
+    fn now() -> DateTime {
+        Local.ymd(2026, 11, 2).and_hms(12, 0, 0)
+    }
+
+    #[test]
+    fn refuses_a_skipped_time() {
+        use_berlin_time();
+        let result = parse_datetime("2026-03-29 2:30", now());
+
+        assert!(matches!(result, Err(DateTimeParseError::NonExistent(_))));
+    }
+
+    #[test]
+    fn refuses_a_repeated_time() {
+        use_berlin_time();
+
+        match parse_datetime("2026-10-25 2:30", now()) {
+            Err(DateTimeParseError::Ambiguous(first, second)) => {
+                assert_eq!(second - first, Duration::hours(1))
+            }
+            _ => panic!("a repeated time must be ambiguous"),
+        }
+    }
+
+    #[test]
+    fn accepts_a_repeated_time_with_its_offset() {
+        use_berlin_time();
+        let first = parse_datetime("2026-10-25T02:30:00+02:00", now())
+            .ok()
+            .unwrap();
+        let second = parse_datetime("2026-10-25T02:30:00+01:00", now())
+            .ok()
+            .unwrap();
+
+        assert_eq!(second - first, Duration::hours(1));
+    }
+
+    #[test]
+    fn a_time_alone_is_the_most_recent_fit() {
+        use_berlin_time();
+        let now = Local.ymd(2026, 6, 10).and_hms(9, 0, 0);
+
+        let earlier = parse_datetime("8:30", now).ok().unwrap();
+        let later = parse_datetime("9:30", now).ok().unwrap();
+
+        assert_eq!(earlier, Local.ymd(2026, 6, 10).and_hms(8, 30, 0));
+        assert_eq!(later, Local.ymd(2026, 6, 9).and_hms(9, 30, 0));
+    }
+}
@@ -0,0 +1,546 @@
+use chrono::prelude::*;
+use chrono::Duration;
+
+use super::data::Data;
+use super::history::sessions;
+use super::history::HistoryEntry;
+use super::report::Period;
+use super::DateTime;
+use super::InvoiceFormat;
+use super::Money;
+use super::SessionFilter;
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+//==============================================================================
+//
+//                              Type Definitions
+//
+//==============================================================================
+
+pub struct Invoice {
+    /// The invoice number, or `None` for a preview.
+    pub number: Option<u32>,
+    pub issued: NaiveDate,
+    pub first: NaiveDate,
+    pub last: NaiveDate,
+    pub client: Option<String>,
+    pub lines: Vec<InvoiceLine>,
+}
+
+/// The time worked on the tasks with the same code and hourly rate.
+pub struct InvoiceLine {
+    /// The task code, or the mnemonic of a task without a code.
+    pub code: String,
+    /// The titles of the tasks.
+    pub description: String,
+    pub time: Duration,
+    pub rate: Option<Money>,
+}
+
+//==============================================================================
+//
+//                                   Lines
+//
+//==============================================================================
+
+/// The time worked from the first day to the last one, grouped by task code and hourly rate,
+/// optionally only on the tasks of a client. Sessions count as committed if a commit of their
+/// task follows them. Open sessions last until `now`.
+pub fn lines(
+    data: &Data,
+    entries: &[&HistoryEntry],
+    period: &Period,
+    client: Option<&str>,
+    filter: SessionFilter,
+    now: DateTime,
+) -> Vec<InvoiceLine> {
+    let mut last_stop: HashMap<&str, DateTime> = HashMap::new();
+    let mut committed: HashMap<&str, DateTime> = HashMap::new();
+
+    for entry in entries {
+        match entry {
+            HistoryEntry::Stop {
+                mnemonic, datetime, ..
+            } => {
+                last_stop.insert(mnemonic, datetime.datetime);
+            }
+            HistoryEntry::Commit { mnemonic, .. } => {
+                if let Some(&stop) = last_stop.get(mnemonic.as_str()) {
+                    committed.insert(mnemonic, stop);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut lines: Vec<InvoiceLine> = Vec::new();
+
+    for session in sessions(entries.iter().cloned()) {
+        let task = data.task(&session.mnemonic);
+
+        if client.is_some_and(|client| task.and_then(|task| task.client.as_deref()) != Some(client))
+        {
+            continue;
+        }
+
+        let is_committed = session.stop.is_some_and(|stop| {
+            committed
+                .get(session.mnemonic.as_str())
+                .is_some_and(|&commit| stop.datetime <= commit)
+        });
+
+        match filter {
+            SessionFilter::Committed if !is_committed => continue,
+            SessionFilter::Uncommitted if is_committed => continue,
+            _ => {}
+        }
+
+        let stop = session.stop.map_or(now, |stop| stop.datetime);
+        let time = period.overlap(session.start.datetime, stop);
+
+        if time <= Duration::zero() {
+            continue;
+        }
+
+        let code = task
+            .and_then(|task| task.code.clone())
+            .unwrap_or_else(|| session.mnemonic.clone());
+
+        let title = task.map_or(session.mnemonic.as_str(), |task| task.title.as_str());
+        let rate = data.rate(&session.mnemonic).cloned();
+
+        match lines
+            .iter_mut()
+            .find(|line| line.code == code && line.rate == rate)
+        {
+            Some(line) => {
+                line.time = line.time + time;
+
+                if !line.description.split("; ").any(|other| other == title) {
+                    line.description.push_str("; ");
+                    line.description.push_str(title);
+                }
+            }
+            None => lines.push(InvoiceLine {
+                code,
+                description: title.to_string(),
+                time,
+                rate,
+            }),
+        }
+    }
+
+    lines
+}
+
+impl InvoiceLine {
+    /// The amount due for the line, if it has a rate.
+    pub fn amount(&self) -> Option<Money> {
+        self.rate.as_ref().map(|rate| rate.for_time(self.time))
+    }
+}
+
+impl Invoice {
+    /// The total amount due in each currency, in order of appearance.
+    pub fn totals(&self) -> Vec<Money> {
+        let mut totals: Vec<Money> = Vec::new();
+
+        for amount in self.lines.iter().filter_map(InvoiceLine::amount) {
+            match totals
+                .iter_mut()
+                .find(|total| total.currency == amount.currency)
+            {
+                Some(total) => total.cents += amount.cents,
+                None => totals.push(amount),
+            }
+        }
+
+        totals
+    }
+
+    /// Writes the invoice in the given format.
+    pub fn render(&self, format: InvoiceFormat) -> String {
+        match format {
+            InvoiceFormat::Markdown => self.markdown(),
+            InvoiceFormat::Html => self.html(),
+            InvoiceFormat::Text => self.text(),
+        }
+    }
+
+    fn title(&self) -> String {
+        match self.number {
+            Some(number) => format!("Invoice {}", number),
+            None => String::from("Invoice (preview)"),
+        }
+    }
+
+    /// The issue date, the client and the period, as label and value pairs.
+    fn details(&self) -> Vec<(&str, String)> {
+        let mut details = vec![("Issued", self.issued.format("%Y-%m-%d").to_string())];
+
+        if let Some(client) = &self.client {
+            details.push(("Client", client.clone()));
+        }
+
+        details.push((
+            "Period",
+            format!(
+                "{} to {}",
+                self.first.format("%Y-%m-%d"),
+                self.last.format("%Y-%m-%d")
+            ),
+        ));
+
+        details
+    }
+
+    /// The cells of each line: code, description, hours, rate and amount.
+    fn cells(&self) -> Vec<[String; 5]> {
+        self.lines
+            .iter()
+            .map(|line| {
+                [
+                    line.code.clone(),
+                    line.description.clone(),
+                    format_hours(line.time),
+                    line.rate
+                        .as_ref()
+                        .map_or_else(String::new, |rate| format!("{}/h", rate)),
+                    line.amount()
+                        .map_or_else(|| String::from("(no rate)"), |amount| amount.to_string()),
+                ]
+            })
+            .collect()
+    }
+
+    fn markdown(&self) -> String {
+        let mut output = String::new();
+
+        writeln!(output, "# {}", self.title()).unwrap();
+        writeln!(output).unwrap();
+
+        for (label, value) in self.details() {
+            writeln!(output, "- {}: {}", label, value).unwrap();
+        }
+
+        writeln!(output).unwrap();
+        writeln!(output, "| Code | Description | Hours | Rate | Amount |").unwrap();
+        writeln!(output, "|------|-------------|------:|-----:|-------:|").unwrap();
+
+        for cells in self.cells() {
+            let cells: Vec<String> = cells.iter().map(|cell| cell.replace('|', "\\|")).collect();
+            writeln!(output, "| {} |", cells.join(" | ")).unwrap();
+        }
+
+        writeln!(output).unwrap();
+
+        for total in self.totals() {
+            writeln!(output, "**Total: {}**", total).unwrap();
+        }
+
+        output
+    }
+
+    fn html(&self) -> String {
+        let mut output = String::new();
+        let title = escape_html(&self.title());
+
+        writeln!(output, "<!DOCTYPE html>").unwrap();
+        writeln!(output, "<html>").unwrap();
+        writeln!(
+            output,
+            "<head><meta charset=\"utf-8\"><title>{}</title></head>",
+            title
+        )
+        .unwrap();
+        writeln!(output, "<body>").unwrap();
+        writeln!(output, "<h1>{}</h1>", title).unwrap();
+        writeln!(output, "<dl>").unwrap();
+
+        for (label, value) in self.details() {
+            writeln!(output, "<dt>{}</dt><dd>{}</dd>", label, escape_html(&value)).unwrap();
+        }
+
+        writeln!(output, "</dl>").unwrap();
+        writeln!(output, "<table>").unwrap();
+        writeln!(
+            output,
+            "<tr><th>Code</th><th>Description</th><th>Hours</th><th>Rate</th><th>Amount</th></tr>"
+        )
+        .unwrap();
+
+        for cells in self.cells() {
+            let cells: Vec<String> = cells
+                .iter()
+                .map(|cell| format!("<td>{}</td>", escape_html(cell)))
+                .collect();
+
+            writeln!(output, "<tr>{}</tr>", cells.concat()).unwrap();
+        }
+
+        writeln!(output, "</table>").unwrap();
+
+        for total in self.totals() {
+            writeln!(
+                output,
+                "<p><strong>Total: {}</strong></p>",
+                escape_html(&total.to_string())
+            )
+            .unwrap();
+        }
+
+        writeln!(output, "</body>").unwrap();
+        writeln!(output, "</html>").unwrap();
+        output
+    }
+
+    fn text(&self) -> String {
+        const HEADERS: [&str; 5] = ["Code", "Description", "Hours", "Rate", "Amount"];
+
+        let mut output = String::new();
+        let cells = self.cells();
+
+        writeln!(output, "{}", self.title()).unwrap();
+        writeln!(output).unwrap();
+
+        for (label, value) in self.details() {
+            writeln!(output, "{}: {}", label, value).unwrap();
+        }
+
+        let widths: Vec<usize> = (0..HEADERS.len())
+            .map(|column| {
+                cells
+                    .iter()
+                    .map(|line| line[column].chars().count())
+                    .chain(Some(HEADERS[column].len()))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        let row = |cells: &[&str]| {
+            cells
+                .iter()
+                .zip(&widths)
+                .enumerate()
+                .map(|(column, (cell, &width))| {
+                    // Codes and descriptions are aligned to the left, numbers to the right.
+                    if column < 2 {
+                        format!("{:<width$}", cell, width = width)
+                    } else {
+                        format!("{:>width$}", cell, width = width)
+                    }
+                })
+                .collect::<Vec<String>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        };
+
+        writeln!(output).unwrap();
+        writeln!(output, "{}", row(&HEADERS)).unwrap();
+
+        for line in &cells {
+            let line: Vec<&str> = line.iter().map(String::as_str).collect();
+            writeln!(output, "{}", row(&line)).unwrap();
+        }
+
+        writeln!(output).unwrap();
+
+        for total in self.totals() {
+            writeln!(output, "Total: {}", total).unwrap();
+        }
+
+        output
+    }
+}
+
+/// Formats a duration as a decimal number of hours, e.g. `1.50`.
+fn format_hours(time: Duration) -> String {
+    let minutes = time.num_minutes();
+    format!("{}.{:02}", minutes / 60, (minutes % 60) * 100 / 60)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+//==============================================================================
+//
+//                                   Tests
+//
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Task;
+    use crate::tests::use_berlin_time;
+
+    use core::str::FromStr;
+
+    fn money(money: &str) -> Money {
+        Money::from_str(money).ok().unwrap()
+    }
+
+    fn date(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    /// Two tasks of a client sharing a code, a task with a rate of its own and a task without
+    /// a client, with a default rate, a rate for each client and a session on each task.
+    fn data_and_entries() -> (Data, Vec<HistoryEntry>) {
+        let mut data = Data::default();
+
+        let mut task = |mnemonic: &str, title: &str, code: Option<&str>, client: Option<&str>| {
+            let mut task = Task::new(title.to_string(), code.map(str::to_string));
+            task.client = client.map(str::to_string);
+            data.add_task(mnemonic, task);
+        };
+
+        task("api", "API", Some("P-1"), Some("acme"));
+        task("ui", "UI", Some("P-1"), Some("acme"));
+        task("ops", "Ops", None, Some("other"));
+        task("misc", "Misc", None, None);
+
+        data.task_mut("ops").unwrap().rate = Some(money("100 EUR"));
+        data.general.rates.default = Some(money("50 EUR"));
+        data.general
+            .rates
+            .clients
+            .insert(String::from("acme"), money("80 EUR"));
+        data.general
+            .rates
+            .clients
+            .insert(String::from("other"), money("90 EUR"));
+
+        let entries = [
+            "start api 2026-06-10 9:00 +02:00",
+            "stop api 2026-06-10 10:00 +02:00",
+            "commit api",
+            "start ui 2026-06-10 10:00 +02:00",
+            "stop ui 2026-06-10 10:30 +02:00",
+            "start ops 2026-06-10 11:00 +02:00",
+            "stop ops 2026-06-10 12:00 +02:00",
+            "start misc 2026-06-10 13:00 +02:00",
+            "stop misc 2026-06-10 13:15 +02:00",
+        ]
+        .iter()
+        .map(|line| HistoryEntry::from_str(line).ok().unwrap())
+        .collect();
+
+        (data, entries)
+    }
+
+    fn invoice_lines(client: Option<&str>, filter: SessionFilter) -> Vec<InvoiceLine> {
+        let (data, entries) = data_and_entries();
+        let entries: Vec<&HistoryEntry> = entries.iter().collect();
+        let period = Period::days(date("2026-06-01"), date("2026-06-30"));
+        let now = Local.ymd(2026, 7, 1).and_hms(12, 0, 0);
+
+        lines(&data, &entries, &period, client, filter, now)
+    }
+
+    fn cells(lines: &[InvoiceLine]) -> Vec<(String, String, i64, String)> {
+        lines
+            .iter()
+            .map(|line| {
+                (
+                    line.code.clone(),
+                    line.description.clone(),
+                    line.time.num_minutes(),
+                    line.amount()
+                        .map_or_else(String::new, |amount| amount.to_string()),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rates_of_tasks_override_the_ones_of_clients() {
+        use_berlin_time();
+        let (data, _) = data_and_entries();
+        let rate = |mnemonic| data.rate(mnemonic).map(Money::to_string);
+
+        assert_eq!(rate("api"), Some(String::from("80.00 EUR")));
+        assert_eq!(rate("ops"), Some(String::from("100.00 EUR")));
+        assert_eq!(rate("misc"), Some(String::from("50.00 EUR")));
+    }
+
+    #[test]
+    fn lines_group_the_tasks_by_code() {
+        use_berlin_time();
+        let line = |code: &str, description: &str, minutes, amount: &str| {
+            (
+                code.to_string(),
+                description.to_string(),
+                minutes,
+                amount.to_string(),
+            )
+        };
+
+        assert_eq!(
+            cells(&invoice_lines(None, SessionFilter::All)),
+            vec![
+                line("P-1", "API; UI", 90, "120.00 EUR"),
+                line("ops", "Ops", 60, "100.00 EUR"),
+                line("misc", "Misc", 15, "12.50 EUR"),
+            ]
+        );
+
+        assert_eq!(
+            cells(&invoice_lines(Some("acme"), SessionFilter::Committed)),
+            vec![line("P-1", "API", 60, "80.00 EUR")]
+        );
+
+        assert_eq!(
+            cells(&invoice_lines(Some("acme"), SessionFilter::Uncommitted)),
+            vec![line("P-1", "UI", 30, "40.00 EUR")]
+        );
+    }
+
+    #[test]
+    fn render_writes_each_format() {
+        use_berlin_time();
+        let invoice = Invoice {
+            number: Some(7),
+            issued: date("2026-06-30"),
+            first: date("2026-06-01"),
+            last: date("2026-06-30"),
+            client: Some(String::from("acme")),
+            lines: invoice_lines(Some("acme"), SessionFilter::All),
+        };
+
+        assert_eq!(
+            invoice.render(InvoiceFormat::Text),
+            "Invoice 7\n\
+             \n\
+             Issued: 2026-06-30\n\
+             Client: acme\n\
+             Period: 2026-06-01 to 2026-06-30\n\
+             \n\
+             Code  Description  Hours         Rate      Amount\n\
+             P-1   API; UI       1.50  80.00 EUR/h  120.00 EUR\n\
+             \n\
+             Total: 120.00 EUR\n"
+        );
+
+        let markdown = invoice.render(InvoiceFormat::Markdown);
+        assert!(markdown.starts_with("# Invoice 7\n"));
+        assert!(markdown.contains("\n| P-1 | API; UI | 1.50 | 80.00 EUR/h | 120.00 EUR |\n"));
+        assert!(markdown.ends_with("\n**Total: 120.00 EUR**\n"));
+
+        let html = invoice.render(InvoiceFormat::Html);
+        assert!(html.contains("<h1>Invoice 7</h1>"));
+        assert!(html.contains(
+            "<tr><td>P-1</td><td>API; UI</td><td>1.50</td>\
+             <td>80.00 EUR/h</td><td>120.00 EUR</td></tr>"
+        ));
+        assert!(html.contains("<p><strong>Total: 120.00 EUR</strong></p>"));
+    }
+}
@@ -0,0 +1,451 @@
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::data::Data;
+use super::data::General;
+use super::data::Task;
+use super::wal;
+use super::wal::Rewrite;
+
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::fmt::Error as FormatError;
+use std::fmt::Formatter;
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
+use std::path::Path;
+use std::path::PathBuf;
+
+//==============================================================================
+//
+//                              Type Definitions
+//
+//==============================================================================
+
+/// A change made to the history.log or data.json files, with enough information to revert it.
+/// Task changes store both versions of the task, so it's possible to check whether the task
+/// was changed again afterwards.
+#[derive(Serialize, Deserialize)]
+pub enum Change {
+    HistoryInsert {
+        offset: u64,
+        line: String,
+    },
+    HistoryReplace {
+        offset: u64,
+        previous: Vec<String>,
+        current: Vec<String>,
+    },
+    Task {
+        mnemonic: String,
+        index: Option<usize>,
+        previous: Option<Task>,
+        current: Option<Task>,
+    },
+    General {
+        previous: General,
+        current: General,
+    },
+}
+
+/// All the changes made by the last command that changed anything.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Transaction {
+    pub changes: Vec<Change>,
+}
+
+/// Provides access to the undo journal file, which only keeps the last transaction.
+/// The first change recorded by a new `Journal` object replaces the previous transaction.
+pub struct Journal {
+    path: PathBuf,
+    transaction: RefCell<Option<Transaction>>,
+}
+
+//==============================================================================
+//
+//                                Error Types
+//
+//==============================================================================
+
+pub enum JournalError {
+    Io(io::Error),
+    Json(serde_json::Error),
+}
+
+//==============================================================================
+//
+//                              Change Handling
+//
+//==============================================================================
+
+/// The changes needed to turn `previous` into `current`. Only tasks that differ are included.
+pub fn data_changes(previous: &Data, current: &Data) -> Vec<Change> {
+    let mut changes = Vec::new();
+
+    let mnemonics = previous.tasks.keys().chain(
+        current
+            .tasks
+            .keys()
+            .filter(|key| !previous.tasks.contains_key(*key)),
+    );
+
+    for mnemonic in mnemonics {
+        let old = previous.tasks.get_full(mnemonic);
+        let new = current.task(mnemonic);
+
+        if old.map(|(_, _, task)| task) != new {
+            changes.push(Change::Task {
+                mnemonic: mnemonic.clone(),
+                index: old.map(|(index, _, _)| index),
+                previous: old.map(|(_, _, task)| task.clone()),
+                current: new.cloned(),
+            });
+        }
+    }
+
+    if previous.general != current.general {
+        changes.push(Change::General {
+            previous: previous.general.clone(),
+            current: current.general.clone(),
+        });
+    }
+
+    changes
+}
+
+/// The names of the general settings that differ between both versions.
+pub fn general_fields(previous: &General, current: &General) -> Vec<&'static str> {
+    let mut fields = Vec::new();
+
+    if previous.goals != current.goals {
+        fields.push("goals");
+    }
+
+    if previous.rounding != current.rounding {
+        fields.push("rounding");
+    }
+
+    if previous.rates != current.rates {
+        fields.push("rates");
+    }
+
+    if previous.last_invoice != current.last_invoice {
+        fields.push("invoice number");
+    }
+
+    if previous.breaks != current.breaks {
+        fields.push("planned breaks");
+    }
+
+    if previous.balance_start != current.balance_start {
+        fields.push("balance start date");
+    }
+
+    if previous.absences != current.absences {
+        fields.push("absences");
+    }
+
+    if previous.interruptions != current.interruptions {
+        fields.push("interruptions");
+    }
+
+    if previous.paused != current.paused {
+        fields.push("paused task");
+    }
+
+    fields
+}
+
+//==============================================================================
+//
+//                                File Handling
+//
+//==============================================================================
+
+impl Journal {
+    pub fn new<P: AsRef<Path>>(path: P) -> Journal {
+        Journal {
+            path: path.as_ref().to_path_buf(),
+            transaction: RefCell::new(None),
+        }
+    }
+
+    /// Adds changes to the current transaction, returning the rewrite saving it. The rewrite
+    /// is meant to be applied along with the changes themselves, so a crash can't leave the
+    /// journal describing other changes than the ones made. See `wal::rewrite_tails`.
+    pub fn rewrite<I>(&self, changes: I) -> Result<Rewrite, JournalError>
+    where
+        I: IntoIterator<Item = Change>,
+    {
+        let mut transaction = self.transaction.borrow_mut();
+        let transaction = transaction.get_or_insert_with(Transaction::default);
+
+        transaction.changes.extend(changes);
+
+        Ok(Rewrite {
+            path: self.path.clone(),
+            offset: 0,
+            tail: serde_json::to_vec(transaction)?,
+        })
+    }
+
+    /// Loads the last transaction, if any.
+    pub fn load(&self) -> Result<Option<Transaction>, JournalError> {
+        match File::open(&self.path) {
+            Ok(file) => Ok(Some(serde_json::from_reader(BufReader::new(file))?)),
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// See `wal::recover`.
+    pub fn recover(&self) -> Result<bool, JournalError> {
+        Ok(!wal::recover(&self.path)?.is_empty())
+    }
+
+    pub fn is_pending(&self) -> bool {
+        wal::is_pending(&self.path)
+    }
+
+    /// The rewrite emptying the journal, so the last transaction can't be reverted anymore.
+    /// It's meant to be applied along with the reverting changes.
+    pub fn rewrite_empty(&self) -> Result<Rewrite, JournalError> {
+        *self.transaction.borrow_mut() = Some(Transaction::default());
+
+        Ok(Rewrite {
+            path: self.path.clone(),
+            offset: 0,
+            tail: serde_json::to_vec(&Transaction::default())?,
+        })
+    }
+}
+
+//==============================================================================
+//
+//                         Conversions Between Error Types
+//
+//==============================================================================
+
+impl From<io::Error> for JournalError {
+    fn from(error: io::Error) -> JournalError {
+        JournalError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for JournalError {
+    fn from(error: serde_json::Error) -> JournalError {
+        JournalError::Json(error)
+    }
+}
+
+//==============================================================================
+//
+//                           Display Implementations
+//
+//==============================================================================
+
+/// Describes what reverting the change will do.
+impl Display for Change {
+    fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FormatError> {
+        match self {
+            Change::HistoryInsert { line, .. } => write!(f, "remove history entry: {}", line),
+            Change::HistoryReplace {
+                previous, current, ..
+            } => {
+                if current.is_empty() {
+                    write!(f, "restore history entries:")?;
+                } else {
+                    write!(f, "remove history entries:")?;
+
+                    for line in current {
+                        write!(f, "\n    {}", line)?;
+                    }
+
+                    if !previous.is_empty() {
+                        write!(f, "\n  and restore:")?;
+                    }
+                }
+
+                for line in previous {
+                    write!(f, "\n    {}", line)?;
+                }
+
+                Ok(())
+            }
+            Change::Task {
+                mnemonic,
+                previous: None,
+                ..
+            } => write!(f, "remove task {}", mnemonic),
+            Change::Task {
+                mnemonic,
+                current: None,
+                ..
+            } => write!(f, "restore task {}", mnemonic),
+            Change::Task {
+                mnemonic,
+                previous: Some(previous),
+                current: Some(current),
+                ..
+            } => {
+                let mut fields = Vec::new();
+
+                if previous.title != current.title {
+                    fields.push(format!("title \"{}\"", previous.title));
+                }
+
+                if previous.code != current.code {
+                    fields.push(format!(
+                        "code {}",
+                        previous.code.as_ref().map_or("(none)", String::as_str)
+                    ));
+                }
+
+                if previous.client != current.client {
+                    fields.push(format!(
+                        "client {}",
+                        previous.client.as_ref().map_or("(none)", String::as_str)
+                    ));
+                }
+
+                if previous.rate != current.rate {
+                    fields.push(String::from("previous rate"));
+                }
+
+                if previous.active != current.active {
+                    fields.push(String::from(if previous.active {
+                        "active"
+                    } else {
+                        "inactive"
+                    }));
+                }
+
+                if previous.goals != current.goals {
+                    fields.push(String::from("previous goals"));
+                }
+
+                if previous.rounding != current.rounding {
+                    fields.push(String::from("previous rounding"));
+                }
+
+                write!(f, "revert task {} to {}", mnemonic, fields.join(", "))
+            }
+            Change::General { previous, current } => write!(
+                f,
+                "revert the general {}",
+                general_fields(previous, current).join(", ")
+            ),
+        }
+    }
+}
+
+impl Display for JournalError {
+    fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FormatError> {
+        match self {
+            JournalError::Io(error) => write!(f, "{}", error),
+            JournalError::Json(error) => write!(f, "invalid undo journal: {}", error),
+        }
+    }
+}
+
+//==============================================================================
+//
+//                                   Tests
+//
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::temp_dir;
+    use crate::tests::use_berlin_time;
+    use crate::GoalPeriod;
+
+    use chrono::Duration;
+
+    fn data(tasks: &[(&str, &str)]) -> Data {
+        let mut data = Data::default();
+
+        for (mnemonic, title) in tasks {
+            data.add_task(mnemonic, Task::new(title.to_string(), None));
+        }
+
+        data
+    }
+
+    fn describe(changes: &[Change]) -> Vec<String> {
+        changes.iter().map(Change::to_string).collect()
+    }
+
+    #[test]
+    fn data_changes_include_the_changed_tasks_only() {
+        use_berlin_time();
+        let previous = data(&[("dev", "Development"), ("ops", "Support"), ("old", "Old")]);
+        let mut current = data(&[
+            ("dev", "Development"),
+            ("ops", "Operations"),
+            ("new", "New"),
+        ]);
+
+        current.task_mut("dev").unwrap().active = true;
+        current
+            .general
+            .goals
+            .set(GoalPeriod::Week, Duration::hours(40));
+
+        assert_eq!(
+            describe(&data_changes(&previous, &current)),
+            vec![
+                "revert task ops to title \"Support\"",
+                "restore task old",
+                "remove task new",
+                "revert the general goals",
+            ]
+        );
+
+        assert!(matches!(
+            data_changes(&previous, &current)[1],
+            Change::Task {
+                index: Some(2),
+                current: None,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn a_new_journal_starts_a_new_transaction() {
+        use_berlin_time();
+        let path = temp_dir("journal-transaction").join("undo.json");
+        let line = |line: &str| Change::HistoryInsert {
+            offset: 0,
+            line: line.to_string(),
+        };
+
+        let record = |journal: &Journal, change: Change| {
+            let rewrite = journal.rewrite(Some(change)).ok().unwrap();
+            wal::rewrite_tails(&[rewrite]).unwrap();
+        };
+
+        let journal = Journal::new(&path);
+        record(&journal, line("start dev 2026-06-10 9:00 +02:00"));
+        record(&journal, line("stop dev 2026-06-10 12:00 +02:00"));
+
+        let transaction = journal.load().ok().unwrap().unwrap();
+        assert_eq!(transaction.changes.len(), 2);
+
+        let journal = Journal::new(&path);
+        record(&journal, line("commit dev"));
+
+        let transaction = journal.load().ok().unwrap().unwrap();
+        assert_eq!(
+            describe(&transaction.changes),
+            vec!["remove history entry: commit dev"]
+        );
+
+        let empty = journal.rewrite_empty().ok().unwrap();
+        wal::rewrite_tails(&[empty]).unwrap();
+        assert!(journal.load().ok().unwrap().unwrap().changes.is_empty());
+    }
+}
@@ -1,6 +1,19 @@
-#![feature(try_from)]
-
+pub mod config;
+pub mod data;
+pub mod doctor;
+pub mod execution;
+pub mod history;
+pub mod ical;
+pub mod index;
 pub mod input;
+pub mod invoice;
+pub mod journal;
+pub mod org;
+pub mod profile;
+pub mod prompt;
+pub mod repair;
+pub mod report;
+pub mod wal;
 
 use chrono::prelude::*;
 use chrono::Duration;
@@ -11,6 +24,9 @@ use lazy_static::lazy_static;
 
 use regex::Regex;
 
+use serde::Deserialize;
+use serde::Serialize;
+
 use std::fmt::Display;
 use std::fmt::Error as FormatError;
 use std::fmt::Formatter;
@@ -31,6 +47,28 @@ pub enum Command<'a> {
     Exit {
         datetime: ForgetableDateTime,
     },
+    Pause {
+        datetime: ForgetableDateTime,
+    },
+    Resume {
+        datetime: ForgetableDateTime,
+    },
+    /// Plans a break for every day. Without a duration, the planned break is erased.
+    Break {
+        start: NaiveTime,
+        duration: Option<Duration>,
+    },
+    Breaks,
+    /// Registers an absence. Without one, the absence covering the date is erased.
+    Absence {
+        date: NaiveDate,
+        absence: Option<data::Absence>,
+    },
+    Absences,
+    /// Registers the all-day events of an iCalendar file as holidays.
+    ImportHolidays {
+        path: &'a str,
+    },
     Create {
         mnemonic: &'a str,
         code: Option<&'a str>,
@@ -41,22 +79,48 @@ pub enum Command<'a> {
     },
     Delete {
         mnemonic: &'a str,
+        mode: DeleteMode,
+    },
+    Rename {
+        old: &'a str,
+        new: &'a str,
+    },
+    Merge {
+        from: &'a str,
+        into: &'a str,
     },
     Start {
         mnemonic: &'a str,
         datetime: ForgetableDateTime,
+        note: Option<&'a str>,
     },
     Stop {
         mnemonic: Option<&'a str>,
         datetime: ForgetableDateTime,
+        note: Option<&'a str>,
+        commit: bool,
+    },
+    Interrupt {
+        mnemonic: &'a str,
+        datetime: ForgetableDateTime,
+        note: Option<&'a str>,
+    },
+    Switch {
+        mnemonic: &'a str,
+        datetime: ForgetableDateTime,
+        note: Option<&'a str>,
         commit: bool,
     },
     Commit {
         mnemonic: &'a str,
         datetime: DateTime,
+        note: Option<&'a str>,
     },
+    /// Asks for estimates of the uncertain entries of a task, of the work in general without
+    /// one, or of everything with `all`.
     Resolve {
         mnemonic: Option<&'a str>,
+        all: bool,
     },
     Goal {
         action: GoalAction,
@@ -65,16 +129,84 @@ pub enum Command<'a> {
     Goals {
         mnemonic: Option<&'a str>,
     },
+    /// Changes or displays how the time logged by commits of a task, or of every task
+    /// without a policy of its own, is rounded.
+    Rounding {
+        mnemonic: Option<&'a str>,
+        action: RoundingAction,
+    },
+    /// Changes or displays the hourly rate of a task, of a client or the default one.
+    Rate {
+        target: RateTarget<'a>,
+        action: RateAction,
+    },
+    /// Assigns a task to a client. Without a client, the task is unassigned.
+    Client {
+        mnemonic: &'a str,
+        client: Option<&'a str>,
+    },
+    /// Writes an invoice for the time worked from the first day to the last one.
+    Invoice {
+        first: NaiveDate,
+        last: NaiveDate,
+        format: InvoiceFormat,
+        client: Option<&'a str>,
+        sessions: SessionFilter,
+        preview: bool,
+    },
     Status {
         mnemonic: Option<&'a str>,
     },
+    Report {
+        period: GoalPeriod,
+        datetime: DateTime,
+        mnemonic: Option<&'a str>,
+        /// The profiles to combine, instead of the current one.
+        profiles: Option<ProfileSelection<'a>>,
+    },
+    /// Displays the flex-time balance, after changing its start date if one is provided.
+    Balance {
+        since: Option<NaiveDate>,
+    },
+    Export {
+        format: ExchangeFormat,
+        mnemonic: Option<&'a str>,
+    },
+    Import {
+        format: ExchangeFormat,
+        path: &'a str,
+    },
+    Undo,
+    Log {
+        mnemonic: Option<&'a str>,
+        count: usize,
+    },
+    Amend {
+        entry: EntrySelector<'a>,
+        action: AmendAction<'a>,
+    },
+    Config {
+        action: ConfigAction<'a>,
+    },
+    /// Checks the history and data files for problems. Uncertain entries are reported once
+    /// they're older than the given number of days.
+    Doctor {
+        days: u32,
+    },
+    /// Fixes the common problems of the history, after the user confirms the changes.
+    Repair,
+    Profile {
+        action: ProfileAction<'a>,
+    },
 }
 
+#[derive(Clone, Copy)]
 pub struct ForgetableDateTime {
     pub datetime: DateTime,
     pub forgotten: bool,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum GoalPeriod {
     Month,
     Week,
@@ -82,6 +214,96 @@ pub enum GoalPeriod {
     Weekday(Weekday),
 }
 
+/// Reasons for being away from work on a day, so it's not expected to be worked.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum AbsenceKind {
+    Vacation,
+    Sick,
+    Holiday,
+    Other,
+}
+
+pub struct InvalidAbsenceKind;
+
+/// How the time to be logged by a commit is rounded. What's left over by the rounding is carried
+/// over to the next commit.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Rounding {
+    pub direction: RoundingDirection,
+    /// Size of the step in minutes. Always a divisor of 60.
+    pub step: u32,
+    #[serde(default)]
+    pub per: RoundingScope,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum RoundingDirection {
+    Up,
+    Down,
+    Nearest,
+}
+
+/// Whether each session is rounded on its own or only the total of the commit.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RoundingScope {
+    Session,
+    #[default]
+    Commit,
+}
+
+pub struct InvalidRoundingDirection;
+
+pub struct InvalidRoundingScope;
+
+pub enum RoundingAction {
+    Set(Rounding),
+    Erase,
+    Show,
+}
+
+/// An amount of money in cents, with its currency code, e.g. `85.50 EUR`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Money {
+    pub cents: i64,
+    pub currency: String,
+}
+
+pub struct InvalidMoney;
+
+/// Whose hourly rate a `rate` command refers to.
+pub enum RateTarget<'a> {
+    Task(&'a str),
+    Client(&'a str),
+    Default,
+}
+
+pub enum RateAction {
+    Set(Money),
+    Erase,
+    Show,
+}
+
+/// Which sessions an invoice includes, according to whether they were committed.
+#[derive(Clone, Copy, PartialEq)]
+pub enum SessionFilter {
+    All,
+    Committed,
+    Uncommitted,
+}
+
+/// File formats supported by the `invoice` command.
+#[derive(Clone, Copy)]
+pub enum InvoiceFormat {
+    Markdown,
+    Html,
+    Text,
+}
+
+pub struct InvalidInvoiceFormat;
+
 pub enum GoalAction {
     Set(GoalPeriod, Duration),
     Erase(GoalPeriod),
@@ -90,6 +312,64 @@ pub enum GoalAction {
 
 pub struct InvalidGoalPeriod;
 
+/// Identifies a history entry, either by its position in the `log` listing (1 being the last
+/// entry) or by its ID, which doesn't change when other entries are added.
+pub enum EntrySelector<'a> {
+    Index(usize),
+    Id(&'a str),
+}
+
+/// What to do with the history of a task being deleted.
+pub enum DeleteMode {
+    /// Only tasks without history can be deleted.
+    Default,
+    /// The task is deactivated instead, so its history is kept.
+    Archive,
+    /// The history entries of the task are removed along with it.
+    Purge,
+}
+
+pub enum AmendAction<'a> {
+    Change {
+        datetime: Option<DateTime>,
+        forgotten: bool,
+        mnemonic: Option<&'a str>,
+    },
+    Delete,
+}
+
+pub struct InvalidEntrySelector;
+
+/// Reads or changes the settings of the config.toml file.
+pub enum ConfigAction<'a> {
+    /// Displays a setting, or every setting.
+    Get(Option<&'a str>),
+    Set(&'a str, &'a str),
+    Unset(&'a str),
+}
+
+/// Lists, creates or selects profiles.
+pub enum ProfileAction<'a> {
+    List,
+    Create(&'a str),
+    /// Makes a profile the one used by default.
+    Use(&'a str),
+}
+
+/// The profiles a report combines.
+pub enum ProfileSelection<'a> {
+    All,
+    Named(Vec<&'a str>),
+}
+
+/// File formats supported by the `export` and `import` commands.
+#[derive(Clone, Copy)]
+pub enum ExchangeFormat {
+    Org,
+}
+
+pub struct InvalidExchangeFormat;
+
 pub enum DurationParseError {
     InvalidFormat,
     InvalidHourNumber,
@@ -97,6 +377,16 @@ pub enum DurationParseError {
     EmptyDuration,
 }
 
+pub enum DateTimeParseError {
+    InvalidFormat,
+    InvalidDate,
+    InvalidTime,
+    /// The local date/time is skipped by a DST change.
+    NonExistent(NaiveDateTime),
+    /// The local date/time is repeated by a DST change, so it's both moments.
+    Ambiguous(DateTime, DateTime),
+}
+
 //==============================================================================
 //
 //                              Type Conversions
@@ -123,6 +413,27 @@ impl FromStr for GoalPeriod {
     }
 }
 
+impl Display for GoalPeriod {
+    fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FormatError> {
+        write!(
+            f,
+            "{}",
+            match self {
+                GoalPeriod::Month => "month",
+                GoalPeriod::Week => "week",
+                GoalPeriod::Day => "day",
+                GoalPeriod::Weekday(Weekday::Sun) => "sunday",
+                GoalPeriod::Weekday(Weekday::Mon) => "monday",
+                GoalPeriod::Weekday(Weekday::Tue) => "tuesday",
+                GoalPeriod::Weekday(Weekday::Wed) => "wednesday",
+                GoalPeriod::Weekday(Weekday::Thu) => "thursday",
+                GoalPeriod::Weekday(Weekday::Fri) => "friday",
+                GoalPeriod::Weekday(Weekday::Sat) => "saturday",
+            }
+        )
+    }
+}
+
 impl Display for InvalidGoalPeriod {
     fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FormatError> {
         writeln!(f, "invalid goal period")?;
@@ -130,6 +441,298 @@ impl Display for InvalidGoalPeriod {
     }
 }
 
+impl FromStr for AbsenceKind {
+    type Err = InvalidAbsenceKind;
+
+    fn from_str(string: &str) -> Result<Self, InvalidAbsenceKind> {
+        match string {
+            "vacation" => Ok(AbsenceKind::Vacation),
+            "sick" => Ok(AbsenceKind::Sick),
+            "holiday" => Ok(AbsenceKind::Holiday),
+            "other" => Ok(AbsenceKind::Other),
+            _ => Err(InvalidAbsenceKind),
+        }
+    }
+}
+
+impl Display for AbsenceKind {
+    fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FormatError> {
+        write!(
+            f,
+            "{}",
+            match self {
+                AbsenceKind::Vacation => "vacation",
+                AbsenceKind::Sick => "sick",
+                AbsenceKind::Holiday => "holiday",
+                AbsenceKind::Other => "other",
+            }
+        )
+    }
+}
+
+impl Display for InvalidAbsenceKind {
+    fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FormatError> {
+        writeln!(f, "invalid absence type")?;
+        write!(f, "valid type values: vacation, sick, holiday, other.")
+    }
+}
+
+impl Rounding {
+    /// Rounds a duration to a whole number of steps. Nothing is logged for a negative duration.
+    pub fn round(&self, duration: Duration) -> Duration {
+        let step = i64::from(self.step) * 60;
+        let seconds = duration.num_seconds();
+
+        if seconds <= 0 {
+            return Duration::zero();
+        }
+
+        let steps = match self.direction {
+            RoundingDirection::Up => (seconds + step - 1) / step,
+            RoundingDirection::Down => seconds / step,
+            RoundingDirection::Nearest => (seconds + step / 2) / step,
+        };
+
+        Duration::seconds(steps * step)
+    }
+}
+
+impl FromStr for RoundingDirection {
+    type Err = InvalidRoundingDirection;
+
+    fn from_str(string: &str) -> Result<Self, InvalidRoundingDirection> {
+        match string {
+            "up" => Ok(RoundingDirection::Up),
+            "down" => Ok(RoundingDirection::Down),
+            "nearest" => Ok(RoundingDirection::Nearest),
+            _ => Err(InvalidRoundingDirection),
+        }
+    }
+}
+
+impl FromStr for RoundingScope {
+    type Err = InvalidRoundingScope;
+
+    fn from_str(string: &str) -> Result<Self, InvalidRoundingScope> {
+        match string {
+            "session" => Ok(RoundingScope::Session),
+            "commit" => Ok(RoundingScope::Commit),
+            _ => Err(InvalidRoundingScope),
+        }
+    }
+}
+
+/// Describes the policy, e.g. `up to 15-minute steps, per commit`.
+impl Display for Rounding {
+    fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FormatError> {
+        match self.direction {
+            RoundingDirection::Up => write!(f, "up to {}-minute steps", self.step)?,
+            RoundingDirection::Down => write!(f, "down to {}-minute steps", self.step)?,
+            RoundingDirection::Nearest => write!(f, "to the nearest {}-minute step", self.step)?,
+        }
+
+        match self.per {
+            RoundingScope::Session => write!(f, ", per session"),
+            RoundingScope::Commit => write!(f, ", per commit"),
+        }
+    }
+}
+
+impl Display for InvalidRoundingDirection {
+    fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FormatError> {
+        writeln!(f, "invalid rounding direction")?;
+        write!(f, "valid direction values: up, down, nearest.")
+    }
+}
+
+impl Display for InvalidRoundingScope {
+    fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FormatError> {
+        writeln!(f, "invalid rounding scope")?;
+        write!(f, "valid scope values: session, commit.")
+    }
+}
+
+impl Money {
+    /// The amount due for the given time at this hourly rate, rounded to the nearest cent.
+    pub fn for_time(&self, time: Duration) -> Money {
+        let cents = i128::from(self.cents) * i128::from(time.num_seconds());
+
+        Money {
+            cents: ((cents + 1800) / 3600) as i64,
+            currency: self.currency.clone(),
+        }
+    }
+}
+
+/// Amounts are accepted with up to two decimal places and a three-letter currency code,
+/// e.g. `85 EUR`, `85.5 USD` or `85,50BRL`.
+impl FromStr for Money {
+    type Err = InvalidMoney;
+
+    fn from_str(string: &str) -> Result<Self, InvalidMoney> {
+        lazy_static! {
+            static ref MONEY_REGEX: Regex =
+                Regex::new(r"^(\d+)(?:[.,](\d{1,2}))?\s*([A-Za-z]{3})$").unwrap();
+        }
+
+        let captures = MONEY_REGEX.captures(string.trim()).ok_or(InvalidMoney)?;
+        let units: i64 = captures[1].parse().map_err(|_| InvalidMoney)?;
+
+        let cents = match captures.get(2).map(|cents| cents.as_str()) {
+            Some(cents) if cents.len() == 1 => cents.parse::<i64>().map_err(|_| InvalidMoney)? * 10,
+            Some(cents) => cents.parse().map_err(|_| InvalidMoney)?,
+            None => 0,
+        };
+
+        Ok(Money {
+            cents: units
+                .checked_mul(100)
+                .and_then(|units| units.checked_add(cents))
+                .ok_or(InvalidMoney)?,
+            currency: captures[3].to_uppercase(),
+        })
+    }
+}
+
+impl Display for Money {
+    fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FormatError> {
+        let sign = if self.cents < 0 { "-" } else { "" };
+        let cents = self.cents.abs();
+
+        write!(
+            f,
+            "{}{}.{:02} {}",
+            sign,
+            cents / 100,
+            cents % 100,
+            self.currency
+        )
+    }
+}
+
+impl Display for InvalidMoney {
+    fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FormatError> {
+        writeln!(f, "invalid amount")?;
+        write!(
+            f,
+            "amounts must have a currency code, e.g. 85 EUR or 85.50 USD."
+        )
+    }
+}
+
+impl FromStr for InvoiceFormat {
+    type Err = InvalidInvoiceFormat;
+
+    fn from_str(string: &str) -> Result<Self, InvalidInvoiceFormat> {
+        match string {
+            "markdown" | "md" => Ok(InvoiceFormat::Markdown),
+            "html" => Ok(InvoiceFormat::Html),
+            "text" | "txt" => Ok(InvoiceFormat::Text),
+            _ => Err(InvalidInvoiceFormat),
+        }
+    }
+}
+
+impl Display for InvalidInvoiceFormat {
+    fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FormatError> {
+        writeln!(f, "invalid invoice format")?;
+        write!(f, "valid format values: markdown, html, text.")
+    }
+}
+
+impl<'a> EntrySelector<'a> {
+    /// Entry IDs are 8 hexadecimal digits. Anything else is expected to be a listing index.
+    pub fn parse(string: &'a str) -> Result<Self, InvalidEntrySelector> {
+        if string.len() == 8 && string.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Ok(EntrySelector::Id(string));
+        }
+
+        match string.parse::<usize>() {
+            Ok(index) if index > 0 => Ok(EntrySelector::Index(index)),
+            _ => Err(InvalidEntrySelector),
+        }
+    }
+}
+
+impl Display for InvalidEntrySelector {
+    fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FormatError> {
+        writeln!(f, "invalid entry")?;
+        write!(
+            f,
+            "entries are identified by their index in the log listing or by their 8-digit ID."
+        )
+    }
+}
+
+impl FromStr for ExchangeFormat {
+    type Err = InvalidExchangeFormat;
+
+    fn from_str(string: &str) -> Result<Self, InvalidExchangeFormat> {
+        match string {
+            "org" => Ok(ExchangeFormat::Org),
+            _ => Err(InvalidExchangeFormat),
+        }
+    }
+}
+
+impl Display for InvalidExchangeFormat {
+    fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FormatError> {
+        writeln!(f, "invalid file format")?;
+        write!(f, "valid format values: org.")
+    }
+}
+
+/// Task mnemonics are restricted to letters, numbers and dashes, with slashes separating
+/// namespaces, e.g. `project/feature`.
+pub fn is_valid_mnemonic(mnemonic: &str) -> bool {
+    lazy_static! {
+        static ref MNEMONIC_REGEX: Regex = Regex::new(r"^[A-Za-z0-9-]+(/[A-Za-z0-9-]+)*$").unwrap();
+    }
+
+    MNEMONIC_REGEX.is_match(mnemonic)
+}
+
+/// Whether a mnemonic is the given namespace itself or belongs to it, e.g. both `project`
+/// and `project/feature` are within `project`, but `project-x` isn't.
+pub fn is_within(mnemonic: &str, namespace: &str) -> bool {
+    mnemonic
+        .strip_prefix(namespace)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// The moments a local date/time refers to, in chronological order: none if a DST change skips
+/// it, or two if a DST change repeats it. `Local::from_local_datetime` always finds a single
+/// moment, so the offsets in effect around the date/time are tried instead.
+pub fn local_moments(naive: NaiveDateTime) -> Vec<DateTime> {
+    let mut moments: Vec<DateTime> = [-1, 0, 1]
+        .iter()
+        .map(|&days| Local.offset_from_utc_datetime(&(naive + Duration::days(days))))
+        .map(|offset| {
+            Local.from_utc_datetime(
+                &(naive - Duration::seconds(i64::from(offset.local_minus_utc()))),
+            )
+        })
+        .filter(|moment| moment.naive_local() == naive)
+        .collect();
+
+    moments.sort();
+    moments.dedup();
+    moments
+}
+
+/// Formats a duration the same way it's accepted by `parse_duration`, e.g. `2h 32m`.
+/// Seconds are truncated.
+pub fn format_duration(duration: Duration) -> String {
+    let sign = if duration < Duration::zero() { "-" } else { "" };
+    let minutes = duration.num_minutes().abs();
+
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("{}{}m", sign, minutes),
+        (hours, 0) => format!("{}{}h", sign, hours),
+        (hours, minutes) => format!("{}{}h {}m", sign, hours, minutes),
+    }
+}
+
 pub fn parse_duration(input: &str) -> Result<Duration, DurationParseError> {
     lazy_static! {
         static ref DURATION_REGEX: Regex = Regex::new(r"^(?:(\d+)h)? *(?:(\d+)m)?$").unwrap();
@@ -168,3 +771,123 @@ pub fn parse_duration(input: &str) -> Result<Duration, DurationParseError> {
             }
         })
 }
+
+//==============================================================================
+//
+//                                   Tests
+//
+//==============================================================================
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+    use std::sync::Once;
+
+    /// The local time zone is read only once per process, so every test relying on it goes
+    /// through here first. Berlin skips 2:00-3:00 on 2026-03-29 and repeats it on 2026-10-25.
+    pub fn use_berlin_time() {
+        static ONCE: Once = Once::new();
+        ONCE.call_once(|| env::set_var("TZ", "Europe/Berlin"));
+    }
+
+    pub fn naive(datetime: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(datetime, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    /// An empty directory of its own for the files of a test.
+    pub fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("timelog-test-{}-{}", process::id(), name));
+
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn entry_selectors() {
+        assert!(matches!(
+            EntrySelector::parse("0a1b2c3d"),
+            Ok(EntrySelector::Id("0a1b2c3d"))
+        ));
+        assert!(matches!(
+            EntrySelector::parse("12345678"),
+            Ok(EntrySelector::Id(_))
+        ));
+        assert!(matches!(
+            EntrySelector::parse("12"),
+            Ok(EntrySelector::Index(12))
+        ));
+        assert!(EntrySelector::parse("0").is_err());
+        assert!(EntrySelector::parse("0a1b2c3").is_err());
+    }
+
+    #[test]
+    fn namespaces() {
+        assert!(is_valid_mnemonic("project/feature-1"));
+        assert!(!is_valid_mnemonic("project/"));
+        assert!(!is_valid_mnemonic("/feature"));
+        assert!(!is_valid_mnemonic("project//feature"));
+
+        assert!(is_within("project", "project"));
+        assert!(is_within("project/feature", "project"));
+        assert!(!is_within("project-x", "project"));
+        assert!(!is_within("project", "project/feature"));
+    }
+
+    #[test]
+    fn local_moments_of_a_regular_time() {
+        use_berlin_time();
+        let moments = local_moments(naive("2026-06-10 9:30"));
+
+        assert_eq!(moments.len(), 1);
+        assert_eq!(moments[0].offset().local_minus_utc(), 2 * 3600);
+    }
+
+    #[test]
+    fn local_moments_of_a_skipped_time() {
+        use_berlin_time();
+        assert!(local_moments(naive("2026-03-29 2:30")).is_empty());
+    }
+
+    #[test]
+    fn local_moments_of_a_repeated_time() {
+        use_berlin_time();
+        let moments = local_moments(naive("2026-10-25 2:30"));
+
+        assert_eq!(moments.len(), 2);
+        assert_eq!(moments[1] - moments[0], Duration::hours(1));
+        assert_eq!(moments[0].offset().local_minus_utc(), 2 * 3600);
+    }
+
+    #[test]
+    fn rounding_directions() {
+        let rounding = |direction| Rounding {
+            direction,
+            step: 15,
+            per: RoundingScope::Commit,
+        };
+
+        let time = Duration::minutes(22);
+        assert_eq!(
+            rounding(RoundingDirection::Up).round(time),
+            Duration::minutes(30)
+        );
+        assert_eq!(
+            rounding(RoundingDirection::Down).round(time),
+            Duration::minutes(15)
+        );
+        assert_eq!(
+            rounding(RoundingDirection::Nearest).round(time),
+            Duration::minutes(15)
+        );
+        assert_eq!(
+            rounding(RoundingDirection::Up).round(-time),
+            Duration::zero()
+        );
+    }
+}
@@ -1,7 +1,6 @@
-#![feature(try_from)]
-
 #[cfg(windows)]
 use ansi_term::enable_ansi_support;
+use ansi_term::Color;
 
 use clap::clap_app;
 use clap::crate_authors;
@@ -11,21 +10,28 @@ use clap::ArgMatches;
 
 use std::convert::TryFrom;
 
+use timelog::config;
+use timelog::config::Config;
 use timelog::input::CommandInput;
 use timelog::input::ForgetableDateTimeInput;
 use timelog::input::GoalActionInput;
 use timelog::input::GoalArgInput;
+use timelog::profile::Profiles;
 use timelog::Command;
 
 fn main() {
     const MNEMONIC_DESCRIPTION: &str = "Primary reference to the task";
     const TASK_CODE_DESCRIPTION: &str = "Reference to the task used in an external tool";
     const FORGOT_DESCRIPTION: &str = "Marks date/time as uncertain";
+    const FORMAT_DESCRIPTION: &str = "File format (org)";
+    const NOTE_DESCRIPTION: &str = "Free-text note about the work done";
 
     let matches = clap_app!(timelog =>
         (version: crate_version!())
         (author: crate_authors!())
         (about: crate_description!())
+        (@arg data_dir: +takes_value --("data-dir") "Directory of the history.log and data.json files")
+        (@arg profile: +takes_value --profile -P "Profile to use instead of the configured one")
         (@subcommand enter =>
             (about: "Registers the time the user arrived at the workplace")
             (@arg datetime: "Date/time the user arrived")
@@ -36,6 +42,45 @@ fn main() {
             (@arg datetime: "Date/time the user left")
             (@arg forgot: --forgot -f FORGOT_DESCRIPTION)
         )
+        (@subcommand pause =>
+            (about: "Registers the time the user started a break, stopping the current task")
+            (@arg datetime: "Date/time the break started")
+            (@arg forgot: --forgot -f FORGOT_DESCRIPTION)
+        )
+        (@subcommand resume =>
+            (about: "Registers the time the user ended a break, restarting the task stopped by it")
+            (@arg datetime: "Date/time the break ended")
+            (@arg forgot: --forgot -f FORGOT_DESCRIPTION)
+        )
+        (@subcommand break =>
+            (about: "Plans a break for every day, so it's skipped when forecasting goals")
+            (@arg start: +required "Time the break starts (e.g. 12:30)")
+            (@arg duration: required_unless[erase] "Duration of the break (e.g. 1h)")
+            (@arg erase: --erase -e conflicts_with[duration] "Erase the break planned for the given time")
+        )
+        (@subcommand breaks =>
+            (about: "Displays the breaks planned for every day")
+        )
+        (@subcommand absence =>
+            (about: "Registers days away from work, so they're not expected to be worked")
+            (@arg start: +required "First day of the absence (e.g. 2019-07-01)")
+            (@arg end: "Last day of the absence (default: the first day)")
+            (@arg kind: +takes_value --type -t "Type of the absence (vacation, sick, holiday or other, default: vacation)")
+            (@arg half: --half "Only half of each day is spent away")
+            (@arg name: +takes_value --name -n "Description of the absence")
+            (@arg erase: --erase -e conflicts_with[end kind half name] "Erase the absence covering the given day")
+        )
+        (@subcommand absences =>
+            (about: "Displays the registered absences")
+        )
+        (@subcommand holidays =>
+            (about: "Manages the public holidays")
+            (@setting SubcommandRequiredElseHelp)
+            (@subcommand import =>
+                (about: "Registers the all-day events of an iCalendar file as holiday absences")
+                (@arg file: +required "Path of the .ics file to be imported")
+            )
+        )
         (@subcommand create =>
             (visible_alias: "new")
             (about: "Creates a new task")
@@ -51,28 +96,59 @@ fn main() {
             (visible_alias: "del")
             (about: "Removes a task")
             (@arg mnemonic: +required MNEMONIC_DESCRIPTION)
+            (@arg archive: --archive conflicts_with[purge] "Deactivates the task instead, keeping its history")
+            (@arg purge: --purge "Also removes the history entries of the task")
+        )
+        (@subcommand rename =>
+            (about: "Changes the mnemonic of a task, rewriting its history entries")
+            (@arg old: +required "Current mnemonic of the task")
+            (@arg new: +required "New mnemonic of the task")
+        )
+        (@subcommand merge =>
+            (about: "Merges a task into another one, rewriting its history entries")
+            (@arg from: +required "Mnemonic of the task to be merged and removed")
+            (@arg into: +required "Mnemonic of the task that remains")
         )
         (@subcommand start =>
             (about: "Registers the time the user started working on a task")
-            (@arg mnemonic: +required MNEMONIC_DESCRIPTION)
+            (@arg mnemonic: "Primary reference to the task (default: the configured default task)")
             (@arg datetime: "Date/time the user started working")
             (@arg forgot: --forgot -f FORGOT_DESCRIPTION)
+            (@arg note: +takes_value --note -n NOTE_DESCRIPTION)
         )
         (@subcommand stop =>
             (about: "Registers the time the user stopped working on the current task")
             (@arg mnemonic: MNEMONIC_DESCRIPTION)
             (@arg datetime: "Date/time the user stopped working")
             (@arg forgot: --forgot -f FORGOT_DESCRIPTION)
+            (@arg note: +takes_value --note -n NOTE_DESCRIPTION)
             (@arg commit: --commit -c "Execute the commit subcommand after stop")
         )
+        (@subcommand interrupt =>
+            (about: "Suspends the current task and starts another one until it's stopped")
+            (@arg mnemonic: +required MNEMONIC_DESCRIPTION)
+            (@arg datetime: "Date/time the user was interrupted")
+            (@arg forgot: --forgot -f FORGOT_DESCRIPTION)
+            (@arg note: +takes_value --note -n NOTE_DESCRIPTION)
+        )
+        (@subcommand switch =>
+            (about: "Stops the current task and starts another one at the same time")
+            (@arg mnemonic: +required MNEMONIC_DESCRIPTION)
+            (@arg datetime: "Date/time the user switched tasks")
+            (@arg forgot: --forgot -f FORGOT_DESCRIPTION)
+            (@arg note: +takes_value --note -n "Free-text note about the work done on the stopped task")
+            (@arg commit: --commit -c "Execute the commit subcommand for the stopped task")
+        )
         (@subcommand commit =>
             (about: "Marks a time period worked on a task as logged in an external tool")
-            (@arg mnemonic: +required MNEMONIC_DESCRIPTION)
+            (@arg mnemonic: "Primary reference to the task (default: the configured default task)")
             (@arg datetime: "Date/time until which all time has been logged")
+            (@arg note: +takes_value --note -n NOTE_DESCRIPTION)
         )
         (@subcommand resolve =>
             (about: "Allows the user to provide a better estimate of date/time of the entries marked as forgot")
             (@arg mnemonic: MNEMONIC_DESCRIPTION)
+            (@arg all: --all -a conflicts_with[mnemonic] "Resolve the entries of all tasks and of the work in general")
         )
         (@subcommand goal =>
             (about: "Sets a time goal for a provided task or for the work in general")
@@ -90,10 +166,117 @@ fn main() {
             (about: "Displays the time goals for a provided task or for the work in general")
             (@arg mnemonic: MNEMONIC_DESCRIPTION)
         )
+        (@subcommand rounding =>
+            (about: "Sets how the time logged by commits is rounded, for a task or for every task")
+            (@arg mnemonic: MNEMONIC_DESCRIPTION)
+            (@arg direction: +takes_value --direction -d "Rounding direction (up, down or nearest)")
+            (@arg step: +takes_value --step -s requires[direction] "Step in minutes (e.g. 5, 6, 15 or 30, default: 15)")
+            (@arg per: +takes_value --per -p requires[direction] "What's rounded (session or commit, default: commit)")
+            (@arg erase: --erase -e conflicts_with[direction step per] "Erase the rounding policy")
+        )
+        (@subcommand rate =>
+            (about: "Sets the hourly rate of a task, of a client or the default one")
+            (@arg mnemonic: conflicts_with[client] MNEMONIC_DESCRIPTION)
+            (@arg client: +takes_value --client -c "Client whose rate is set")
+            (@arg rate: +takes_value --set -s "Hourly rate with its currency (e.g. 85.50 EUR)")
+            (@arg erase: --erase -e conflicts_with[rate] "Erase the rate")
+        )
+        (@subcommand client =>
+            (about: "Assigns a task to a client, for rates and invoices")
+            (@arg mnemonic: +required MNEMONIC_DESCRIPTION)
+            (@arg client: required_unless[erase] "Name of the client")
+            (@arg erase: --erase -e conflicts_with[client] "Unassign the task from its client")
+        )
+        (@subcommand invoice =>
+            (about: "Writes an invoice for the time worked in a period to the standard output")
+            (@arg first: +required +takes_value --from "First day of the period (e.g. 2019-02-01)")
+            (@arg last: +required +takes_value --to "Last day of the period (e.g. 2019-02-28)")
+            (@arg format: +takes_value --format -f "Invoice format (markdown, html or text, default: markdown)")
+            (@arg client: +takes_value --client -c "Only include the tasks of the client")
+            (@arg committed: --committed conflicts_with[uncommitted] "Only include committed sessions")
+            (@arg uncommitted: --uncommitted "Only include uncommitted sessions")
+            (@arg preview: --preview -p "Don't use up an invoice number")
+        )
         (@subcommand status =>
             (about: "Displays general information about the current status of the user's work")
             (@arg mnemonic: MNEMONIC_DESCRIPTION)
         )
+        (@subcommand report =>
+            (about: "Displays the time worked on each task in a period, with namespace totals")
+            (@arg period: "Period of the report (month, week or day, default: week)")
+            (@arg mnemonic: "Namespace or task to restrict the report to")
+            (@arg datetime: +takes_value --date -d "Date/time within the period (default: now)")
+            (@arg profiles: +takes_value --profiles "Profiles to combine, comma-separated, or all")
+        )
+        (@subcommand balance =>
+            (about: "Displays the flex-time balance week by week: time worked minus the daily goals")
+            (@arg since: +takes_value --since -s "First day counted by the balance (e.g. 2019-01-01)")
+        )
+        (@subcommand export =>
+            (about: "Writes the work sessions to the standard output in another format")
+            (@arg format: +required FORMAT_DESCRIPTION)
+            (@arg mnemonic: MNEMONIC_DESCRIPTION)
+        )
+        (@subcommand import =>
+            (about: "Adds the work sessions from a file in another format to the history")
+            (@arg format: +required FORMAT_DESCRIPTION)
+            (@arg file: +required "Path of the file to be imported")
+        )
+        (@subcommand undo =>
+            (about: "Reverts the changes made by the last command")
+        )
+        (@subcommand log =>
+            (about: "Lists the last history entries with their indices and IDs")
+            (@arg mnemonic: MNEMONIC_DESCRIPTION)
+            (@arg count: +takes_value --count -n "Number of entries to be listed (default: 20)")
+        )
+        (@subcommand amend =>
+            (about: "Changes or deletes an existing history entry")
+            (@arg entry: +required "Index of the entry in the log listing, or its ID")
+            (@arg datetime: +takes_value --datetime -d "New date/time of the entry")
+            (@arg forgot: --forgot -f FORGOT_DESCRIPTION)
+            (@arg task: +takes_value --task -t "Mnemonic of the task the entry should refer to")
+            (@arg delete: --delete conflicts_with[datetime forgot task] "Deletes the entry")
+        )
+        (@subcommand config =>
+            (about: "Displays or changes the settings of the configuration file")
+            (@setting SubcommandRequiredElseHelp)
+            (@subcommand get =>
+                (about: "Displays a setting, or every setting")
+                (@arg key: "Name of the setting")
+            )
+            (@subcommand set =>
+                (about: "Changes a setting")
+                (@arg key: +required "Name of the setting")
+                (@arg value: +required "New value of the setting")
+            )
+            (@subcommand unset =>
+                (about: "Erases a setting, so its default value is used")
+                (@arg key: +required "Name of the setting")
+            )
+        )
+        (@subcommand doctor =>
+            (about: "Checks the history and data files for problems, suggesting how to fix them")
+            (@arg days: +takes_value --days "Age in days of the uncertain entries to be reported (default: 7)")
+        )
+        (@subcommand repair =>
+            (about: "Fixes the common problems of the history, showing the changes before writing them")
+        )
+        (@subcommand profile =>
+            (about: "Lists, creates or selects the profiles, each one with its own history and tasks")
+            (@setting SubcommandRequiredElseHelp)
+            (@subcommand list =>
+                (about: "Lists the profiles, marking the current one")
+            )
+            (@subcommand create =>
+                (about: "Creates an empty profile")
+                (@arg name: +required "Name of the profile")
+            )
+            (@subcommand use =>
+                (about: "Makes a profile the one used by default")
+                (@arg name: +required "Name of the profile")
+            )
+        )
     ).get_matches();
 
     const REQUIRED_FIELD_EXPECTED: &str = "Required field not found!";
@@ -105,6 +288,32 @@ fn main() {
         ("exit", Some(submatches)) => CommandInput::Exit {
             datetime: parse_forgettable_datetime(submatches),
         },
+        ("pause", Some(submatches)) => CommandInput::Pause {
+            datetime: parse_forgettable_datetime(submatches),
+        },
+        ("resume", Some(submatches)) => CommandInput::Resume {
+            datetime: parse_forgettable_datetime(submatches),
+        },
+        ("break", Some(submatches)) => CommandInput::Break {
+            start: submatches.value_of("start").expect(REQUIRED_FIELD_EXPECTED),
+            duration: submatches.value_of("duration"),
+        },
+        ("breaks", Some(_)) => CommandInput::Breaks,
+        ("absence", Some(submatches)) => CommandInput::Absence {
+            start: submatches.value_of("start").expect(REQUIRED_FIELD_EXPECTED),
+            end: submatches.value_of("end"),
+            kind: submatches.value_of("kind"),
+            half: submatches.is_present("half"),
+            name: submatches.value_of("name"),
+            erase: submatches.is_present("erase"),
+        },
+        ("absences", Some(_)) => CommandInput::Absences,
+        ("holidays", Some(submatches)) => match submatches.subcommand() {
+            ("import", Some(submatches)) => CommandInput::ImportHolidays {
+                path: submatches.value_of("file").expect(REQUIRED_FIELD_EXPECTED),
+            },
+            _ => return,
+        },
         ("create", Some(submatches)) => CommandInput::Create {
             mnemonic: submatches
                 .value_of("mnemonic")
@@ -121,26 +330,51 @@ fn main() {
             mnemonic: submatches
                 .value_of("mnemonic")
                 .expect(REQUIRED_FIELD_EXPECTED),
+            archive: submatches.is_present("archive"),
+            purge: submatches.is_present("purge"),
+        },
+        ("rename", Some(submatches)) => CommandInput::Rename {
+            old: submatches.value_of("old").expect(REQUIRED_FIELD_EXPECTED),
+            new: submatches.value_of("new").expect(REQUIRED_FIELD_EXPECTED),
+        },
+        ("merge", Some(submatches)) => CommandInput::Merge {
+            from: submatches.value_of("from").expect(REQUIRED_FIELD_EXPECTED),
+            into: submatches.value_of("into").expect(REQUIRED_FIELD_EXPECTED),
         },
         ("start", Some(submatches)) => CommandInput::Start {
-            mnemonic: submatches
-                .value_of("mnemonic")
-                .expect(REQUIRED_FIELD_EXPECTED),
+            mnemonic: submatches.value_of("mnemonic"),
             datetime: parse_forgettable_datetime(submatches),
+            note: submatches.value_of("note"),
         },
         ("stop", Some(submatches)) => CommandInput::Stop {
             mnemonic: submatches.value_of("mnemonic"),
             datetime: parse_forgettable_datetime(submatches),
+            note: submatches.value_of("note"),
             commit: submatches.is_present("commit"),
         },
-        ("commit", Some(submatches)) => CommandInput::Commit {
+        ("interrupt", Some(submatches)) => CommandInput::Interrupt {
+            mnemonic: submatches
+                .value_of("mnemonic")
+                .expect(REQUIRED_FIELD_EXPECTED),
+            datetime: parse_forgettable_datetime(submatches),
+            note: submatches.value_of("note"),
+        },
+        ("switch", Some(submatches)) => CommandInput::Switch {
             mnemonic: submatches
                 .value_of("mnemonic")
                 .expect(REQUIRED_FIELD_EXPECTED),
+            datetime: parse_forgettable_datetime(submatches),
+            note: submatches.value_of("note"),
+            commit: submatches.is_present("commit"),
+        },
+        ("commit", Some(submatches)) => CommandInput::Commit {
+            mnemonic: submatches.value_of("mnemonic"),
             datetime: submatches.value_of("datetime"),
+            note: submatches.value_of("note"),
         },
         ("resolve", Some(submatches)) => CommandInput::Resolve {
             mnemonic: submatches.value_of("mnemonic"),
+            all: submatches.is_present("all"),
         },
         ("goal", Some(submatches)) => CommandInput::Goal {
             action: parse_goal_action(submatches).expect(REQUIRED_FIELD_EXPECTED),
@@ -150,15 +384,126 @@ fn main() {
         ("goals", Some(submatches)) => CommandInput::Goals {
             mnemonic: submatches.value_of("mnemonic"),
         },
+        ("rounding", Some(submatches)) => CommandInput::Rounding {
+            mnemonic: submatches.value_of("mnemonic"),
+            direction: submatches.value_of("direction"),
+            step: submatches.value_of("step"),
+            per: submatches.value_of("per"),
+            erase: submatches.is_present("erase"),
+        },
+        ("rate", Some(submatches)) => CommandInput::Rate {
+            mnemonic: submatches.value_of("mnemonic"),
+            client: submatches.value_of("client"),
+            rate: submatches.value_of("rate"),
+            erase: submatches.is_present("erase"),
+        },
+        ("client", Some(submatches)) => CommandInput::Client {
+            mnemonic: submatches
+                .value_of("mnemonic")
+                .expect(REQUIRED_FIELD_EXPECTED),
+            client: submatches.value_of("client"),
+        },
+        ("invoice", Some(submatches)) => CommandInput::Invoice {
+            first: submatches.value_of("first").expect(REQUIRED_FIELD_EXPECTED),
+            last: submatches.value_of("last").expect(REQUIRED_FIELD_EXPECTED),
+            format: submatches.value_of("format"),
+            client: submatches.value_of("client"),
+            committed: submatches.is_present("committed"),
+            uncommitted: submatches.is_present("uncommitted"),
+            preview: submatches.is_present("preview"),
+        },
         ("status", Some(submatches)) => CommandInput::Status {
             mnemonic: submatches.value_of("mnemonic"),
         },
+        ("report", Some(submatches)) => CommandInput::Report {
+            period: submatches.value_of("period"),
+            datetime: submatches.value_of("datetime"),
+            mnemonic: submatches.value_of("mnemonic"),
+            profiles: submatches.value_of("profiles"),
+        },
+        ("balance", Some(submatches)) => CommandInput::Balance {
+            since: submatches.value_of("since"),
+        },
+        ("export", Some(submatches)) => CommandInput::Export {
+            format: submatches
+                .value_of("format")
+                .expect(REQUIRED_FIELD_EXPECTED),
+            mnemonic: submatches.value_of("mnemonic"),
+        },
+        ("import", Some(submatches)) => CommandInput::Import {
+            format: submatches
+                .value_of("format")
+                .expect(REQUIRED_FIELD_EXPECTED),
+            path: submatches.value_of("file").expect(REQUIRED_FIELD_EXPECTED),
+        },
+        ("undo", Some(_)) => CommandInput::Undo,
+        ("log", Some(submatches)) => CommandInput::Log {
+            mnemonic: submatches.value_of("mnemonic"),
+            count: submatches.value_of("count"),
+        },
+        ("amend", Some(submatches)) => CommandInput::Amend {
+            entry: submatches.value_of("entry").expect(REQUIRED_FIELD_EXPECTED),
+            datetime: submatches.value_of("datetime"),
+            forgotten: submatches.is_present("forgot"),
+            mnemonic: submatches.value_of("task"),
+            delete: submatches.is_present("delete"),
+        },
+        ("config", Some(submatches)) => match submatches.subcommand() {
+            ("get", Some(submatches)) => CommandInput::ConfigGet {
+                key: submatches.value_of("key"),
+            },
+            ("set", Some(submatches)) => CommandInput::ConfigSet {
+                key: submatches.value_of("key").expect(REQUIRED_FIELD_EXPECTED),
+                value: submatches.value_of("value").expect(REQUIRED_FIELD_EXPECTED),
+            },
+            ("unset", Some(submatches)) => CommandInput::ConfigUnset {
+                key: submatches.value_of("key").expect(REQUIRED_FIELD_EXPECTED),
+            },
+            _ => return,
+        },
+        ("doctor", Some(submatches)) => CommandInput::Doctor {
+            days: submatches.value_of("days"),
+        },
+        ("repair", Some(_)) => CommandInput::Repair,
+        ("profile", Some(submatches)) => match submatches.subcommand() {
+            ("list", Some(_)) => CommandInput::ProfileList,
+            ("create", Some(submatches)) => CommandInput::ProfileCreate {
+                name: submatches.value_of("name").expect(REQUIRED_FIELD_EXPECTED),
+            },
+            ("use", Some(submatches)) => CommandInput::ProfileUse {
+                name: submatches.value_of("name").expect(REQUIRED_FIELD_EXPECTED),
+            },
+            _ => return,
+        },
         _ => return,
     };
 
     #[cfg(windows)]
     enable_ansi_support().ok();
 
+    // The configuration is needed to parse the command, e.g. for the date order. The config
+    // command reports the problems of the configuration file itself.
+    let config = config::path().map_or_else(|_| Ok(Config::default()), Config::load);
+    let is_config = matches!(
+        command_input,
+        CommandInput::ConfigGet { .. }
+            | CommandInput::ConfigSet { .. }
+            | CommandInput::ConfigUnset { .. }
+    );
+
+    match config {
+        Ok(config) => config::install(config),
+        Err(_) if is_config => {}
+        Err(error) => {
+            println!(
+                "{}: could not load the configuration file.",
+                Config::default().paint(Color::Red, "error")
+            );
+            println!("cause: {}", error);
+            return;
+        }
+    }
+
     let command = match Command::try_from(command_input) {
         Ok(command) => command,
         Err(error) => {
@@ -167,7 +512,34 @@ fn main() {
         }
     };
 
-    // TODO: Execute command
+    let dir = match config::current().data_dir(matches.value_of("data_dir")) {
+        Some(dir) => dir,
+        None => {
+            println!(
+                "{}: the data directory is unknown, set it with --data-dir.",
+                config::current().paint(Color::Red, "error")
+            );
+            return;
+        }
+    };
+
+    if !is_config {
+        if let Err(error) = std::fs::create_dir_all(&dir) {
+            println!(
+                "{}: could not create the data directory {}.",
+                config::current().paint(Color::Red, "error"),
+                dir.display()
+            );
+            println!("cause: {}", error);
+            return;
+        }
+    }
+
+    let profiles = Profiles::new(dir, config::current().profile(matches.value_of("profile")));
+
+    if let Err(error) = command.execute(&profiles.files(profiles.current()), &profiles) {
+        println!("{}", error);
+    }
 }
 
 fn parse_forgettable_datetime<'a>(matches: &'a ArgMatches<'a>) -> ForgetableDateTimeInput<'a> {
@@ -180,19 +552,15 @@ fn parse_forgettable_datetime<'a>(matches: &'a ArgMatches<'a>) -> ForgetableDate
 fn parse_goal_action<'a>(matches: &'a ArgMatches<'a>) -> Option<GoalActionInput<'a>> {
     if matches.is_present("erase_all") {
         Some(GoalActionInput::EraseAll)
-    } else if let Some(period) = matches.value_of("period") {
-        Some(GoalActionInput::Set(period))
     } else {
-        None
+        matches.value_of("period").map(GoalActionInput::Set)
     }
 }
 
 fn parse_goal_arg<'a>(matches: &'a ArgMatches<'a>) -> Option<GoalArgInput<'a>> {
     if matches.is_present("erase") {
         Some(GoalArgInput::Erase)
-    } else if let Some(time) = matches.value_of("time") {
-        Some(GoalArgInput::Time(time))
     } else {
-        None
+        matches.value_of("time").map(GoalArgInput::Time)
     }
 }
@@ -0,0 +1,448 @@
+use chrono::prelude::*;
+
+use lazy_static::lazy_static;
+
+use regex::Captures;
+use regex::Regex;
+
+use super::data::Data;
+use super::history::sessions;
+use super::history::HistoryEntry;
+use super::local_moments;
+use super::DateTime;
+
+use std::fmt::Display;
+use std::fmt::Error as FormatError;
+use std::fmt::Formatter;
+use std::fmt::Write;
+
+//==============================================================================
+//
+//                              Type Definitions
+//
+//==============================================================================
+
+/// A heading of an Org file, with the properties and CLOCK lines relevant to timelog.
+pub struct OrgTask {
+    pub title: String,
+    pub mnemonic: Option<String>,
+    pub code: Option<String>,
+    pub clocks: Vec<Clock>,
+}
+
+/// A CLOCK line. A clock without an end is still running.
+pub struct Clock {
+    pub start: DateTime,
+    pub stop: Option<DateTime>,
+    /// List items right after the CLOCK line.
+    pub notes: Vec<String>,
+}
+
+//==============================================================================
+//
+//                                Error Types
+//
+//==============================================================================
+
+pub enum OrgParseError {
+    InvalidClock { line: usize },
+    ClockOutsideHeading { line: usize },
+}
+
+//==============================================================================
+//
+//                                   Export
+//
+//==============================================================================
+
+/// Writes one heading per task, in the same order as in the data file, with the task code as
+/// a property and the sessions as CLOCK lines inside a LOGBOOK drawer, the most recent first.
+/// The notes of each session are written as list items after its CLOCK line.
+/// Tasks without sessions are omitted.
+pub fn export(data: &Data, entries: &[HistoryEntry], mnemonic: Option<&str>) -> String {
+    let sessions = sessions(entries);
+    let mut mnemonics: Vec<&str> = data.tasks.keys().map(String::as_str).collect();
+
+    for session in &sessions {
+        if !mnemonics.contains(&session.mnemonic.as_str()) {
+            mnemonics.push(&session.mnemonic);
+        }
+    }
+
+    let mut output = String::new();
+
+    for task in mnemonics {
+        if mnemonic.is_some_and(|mnemonic| mnemonic != task) {
+            continue;
+        }
+
+        let clocks: Vec<Clock> = sessions
+            .iter()
+            .rev()
+            .filter(|session| session.mnemonic == task)
+            .map(|session| Clock {
+                start: session.start.datetime,
+                stop: session.stop.map(|stop| stop.datetime),
+                notes: session.notes.clone(),
+            })
+            .collect();
+
+        if clocks.is_empty() {
+            continue;
+        }
+
+        let (title, code) = match data.task(task) {
+            Some(data) => (data.title.as_str(), data.code.as_ref()),
+            None => (task, None),
+        };
+
+        writeln!(output, "* {}", title).unwrap();
+        writeln!(output, ":PROPERTIES:").unwrap();
+        writeln!(output, ":MNEMONIC: {}", task).unwrap();
+
+        if let Some(code) = code {
+            writeln!(output, ":CODE:     {}", code).unwrap();
+        }
+
+        writeln!(output, ":END:").unwrap();
+        writeln!(output, ":LOGBOOK:").unwrap();
+
+        for clock in clocks {
+            writeln!(output, "{}", clock).unwrap();
+
+            for note in &clock.notes {
+                writeln!(output, "- {}", note.replace('\n', "\n  ")).unwrap();
+            }
+        }
+
+        writeln!(output, ":END:").unwrap();
+    }
+
+    output
+}
+
+//==============================================================================
+//
+//                                   Import
+//
+//==============================================================================
+
+/// Reads the headings of an Org file that contain CLOCK lines, either directly or inside a
+/// LOGBOOK drawer. Properties are only considered inside a PROPERTIES drawer. List items right
+/// after a CLOCK line are its notes, and indented lines after an item continue it.
+pub fn parse(text: &str) -> Result<Vec<OrgTask>, OrgParseError> {
+    lazy_static! {
+        static ref HEADING_REGEX: Regex = Regex::new(r"^\*+\s+(.*?)\s*$").unwrap();
+        static ref NOTE_REGEX: Regex = Regex::new(r"^\s*- (.*?)\s*$").unwrap();
+        static ref CONTINUATION_REGEX: Regex = Regex::new(r"^\s+(\S.*?)\s*$").unwrap();
+        static ref PROPERTY_REGEX: Regex = Regex::new(r"^\s*:([^:\s]+):\s*(.*?)\s*$").unwrap();
+        static ref CLOCK_REGEX: Regex =
+            Regex::new(r"^\s*CLOCK:\s*\[([^\]]*)\](?:--\[([^\]]*)\])?(?:\s*=>\s*-?\d+:\d{2})?\s*$")
+                .unwrap();
+    }
+
+    let mut tasks: Vec<OrgTask> = Vec::new();
+    let mut in_properties = false;
+    let mut after_clock = false;
+
+    for (index, line) in text.lines().enumerate() {
+        let number = index + 1;
+
+        if after_clock {
+            let clock = tasks.last_mut().and_then(|task| task.clocks.last_mut());
+
+            if let Some(clock) = clock {
+                if let Some(captures) = NOTE_REGEX.captures(line) {
+                    clock.notes.push(captures[1].to_string());
+                    continue;
+                }
+
+                let note = clock.notes.last_mut();
+
+                if let (Some(note), Some(captures)) = (note, CONTINUATION_REGEX.captures(line)) {
+                    if !line.trim().starts_with(':') && !line.trim().starts_with("CLOCK:") {
+                        note.push('\n');
+                        note.push_str(&captures[1]);
+                        continue;
+                    }
+                }
+            }
+
+            after_clock = false;
+        }
+
+        if let Some(captures) = HEADING_REGEX.captures(line) {
+            in_properties = false;
+            tasks.push(OrgTask {
+                title: captures[1].to_string(),
+                mnemonic: None,
+                code: None,
+                clocks: Vec::new(),
+            });
+        } else if line.trim().starts_with("CLOCK:") {
+            let captures = CLOCK_REGEX
+                .captures(line)
+                .ok_or(OrgParseError::InvalidClock { line: number })?;
+
+            let clock =
+                parse_clock(&captures).ok_or(OrgParseError::InvalidClock { line: number })?;
+
+            tasks
+                .last_mut()
+                .ok_or(OrgParseError::ClockOutsideHeading { line: number })?
+                .clocks
+                .push(clock);
+
+            after_clock = true;
+        } else if line.trim().eq_ignore_ascii_case(":PROPERTIES:") {
+            in_properties = true;
+        } else if line.trim().eq_ignore_ascii_case(":END:") {
+            in_properties = false;
+        } else if in_properties {
+            let captures = match PROPERTY_REGEX.captures(line) {
+                Some(captures) => captures,
+                None => continue,
+            };
+
+            let task = match tasks.last_mut() {
+                Some(task) => task,
+                None => continue,
+            };
+
+            let value = captures[2].to_string();
+
+            match captures[1].to_uppercase().as_str() {
+                "MNEMONIC" => task.mnemonic = Some(value),
+                "CODE" => task.code = Some(value),
+                _ => {}
+            }
+        }
+    }
+
+    tasks.retain(|task| !task.clocks.is_empty());
+    Ok(tasks)
+}
+
+fn parse_clock(captures: &Captures) -> Option<Clock> {
+    let start = parse_timestamp(&captures[1])?;
+
+    let stop = match captures.get(2) {
+        Some(stop) => Some(parse_timestamp(stop.as_str())?),
+        None => None,
+    };
+
+    Some(Clock {
+        start,
+        stop,
+        notes: Vec::new(),
+    })
+}
+
+/// Parses an inactive Org timestamp without the brackets, e.g. `2019-02-22 Fri 09:45`.
+/// The day name is ignored, since it depends on the language Org was configured with.
+fn parse_timestamp(timestamp: &str) -> Option<DateTime> {
+    lazy_static! {
+        static ref TIMESTAMP_REGEX: Regex =
+            Regex::new(r"^(\d{4}-\d{2}-\d{2})(?:\s+[^\s\d]+)?\s+(\d{1,2}:\d{2})$").unwrap();
+    }
+
+    let captures = TIMESTAMP_REGEX.captures(timestamp.trim())?;
+    let date = NaiveDate::parse_from_str(&captures[1], "%Y-%m-%d").ok()?;
+    let time = NaiveTime::parse_from_str(&captures[2], "%H:%M").ok()?;
+
+    local_moments(date.and_time(time)).first().cloned()
+}
+
+//==============================================================================
+//
+//                           Display Implementations
+//
+//==============================================================================
+
+impl Display for Clock {
+    fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FormatError> {
+        const TIMESTAMP_FORMAT: &str = "[%Y-%m-%d %a %H:%M]";
+
+        write!(f, "CLOCK: {}", self.start.format(TIMESTAMP_FORMAT))?;
+
+        if let Some(stop) = self.stop {
+            let minutes = (stop - self.start).num_minutes();
+
+            write!(
+                f,
+                "--{} => {:>2}:{:02}",
+                stop.format(TIMESTAMP_FORMAT),
+                minutes / 60,
+                minutes % 60
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for OrgParseError {
+    fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), FormatError> {
+        match self {
+            OrgParseError::InvalidClock { line } => {
+                write!(f, "invalid CLOCK line at line {}", line)
+            }
+            OrgParseError::ClockOutsideHeading { line } => {
+                write!(f, "CLOCK line outside of a heading at line {}", line)
+            }
+        }
+    }
+}
+
+//==============================================================================
+//
+//                                   Tests
+//
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Task;
+    use crate::tests::use_berlin_time;
+
+    use core::str::FromStr;
+
+    fn entries(lines: &[&str]) -> Vec<HistoryEntry> {
+        lines
+            .iter()
+            .map(|line| HistoryEntry::from_str(line).ok().unwrap())
+            .collect()
+    }
+
+    fn data() -> Data {
+        let mut data = Data::default();
+        data.add_task(
+            "dev",
+            Task::new("Development".to_string(), Some("DEV-1".to_string())),
+        );
+        data
+    }
+
+    #[test]
+    fn export_writes_a_heading_per_task() {
+        use_berlin_time();
+        let entries = entries(&[
+            "start dev 2026-06-10 9:00 +02:00",
+            "start ops 2026-06-10 10:00 +02:00",
+            "stop dev 2026-06-10 11:15 +02:00",
+            "stop ops 2026-06-10 11:30 +02:00",
+            "start dev 2026-06-11 9:00 +02:00",
+        ]);
+
+        assert_eq!(
+            export(&data(), &entries, None),
+            "* Development\n\
+             :PROPERTIES:\n\
+             :MNEMONIC: dev\n\
+             :CODE:     DEV-1\n\
+             :END:\n\
+             :LOGBOOK:\n\
+             CLOCK: [2026-06-11 Thu 09:00]\n\
+             CLOCK: [2026-06-10 Wed 09:00]--[2026-06-10 Wed 11:15] =>  2:15\n\
+             :END:\n\
+             * ops\n\
+             :PROPERTIES:\n\
+             :MNEMONIC: ops\n\
+             :END:\n\
+             :LOGBOOK:\n\
+             CLOCK: [2026-06-10 Wed 10:00]--[2026-06-10 Wed 11:30] =>  1:30\n\
+             :END:\n"
+        );
+
+        assert!(export(&data(), &entries, Some("ops")).starts_with("* ops\n"));
+    }
+
+    #[test]
+    fn export_and_parse_round_trip() {
+        use_berlin_time();
+        let entries = entries(&[
+            "start dev 2026-06-10 9:00 +02:00",
+            "stop dev 2026-06-10 11:15 +02:00",
+            "start dev 2026-06-11 9:00 +02:00",
+        ]);
+
+        let tasks = parse(&export(&data(), &entries, None)).ok().unwrap();
+
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].title, "Development");
+        assert_eq!(tasks[0].mnemonic.as_deref(), Some("dev"));
+        assert_eq!(tasks[0].code.as_deref(), Some("DEV-1"));
+
+        let clocks: Vec<String> = tasks[0].clocks.iter().map(Clock::to_string).collect();
+
+        assert_eq!(
+            clocks,
+            vec![
+                "CLOCK: [2026-06-11 Thu 09:00]",
+                "CLOCK: [2026-06-10 Wed 09:00]--[2026-06-10 Wed 11:15] =>  2:15",
+            ]
+        );
+
+        // The running clock has no end.
+        assert!(tasks[0].clocks[0].stop.is_none());
+    }
+
+    #[test]
+    fn parse_reads_clocks_outside_drawers() {
+        use_berlin_time();
+        let text = "#+TITLE: Work\n\
+                    * Support\n\
+                    :PROPERTIES:\n\
+                    :Code: OPS-2\n\
+                    :END:\n\
+                    CLOCK: [2026-06-10 mer. 14:00]--[2026-06-10 mer. 15:00] =>  1:00\n\
+                    ** Without clocks\n\
+                    :MNEMONIC: ignored\n";
+
+        let tasks = parse(text).ok().unwrap();
+
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].mnemonic, None);
+        assert_eq!(tasks[0].code.as_deref(), Some("OPS-2"));
+        assert_eq!(tasks[0].clocks.len(), 1);
+    }
+
+    #[test]
+    fn parse_refuses_invalid_clocks() {
+        use_berlin_time();
+        assert!(matches!(
+            parse("* Task\nCLOCK: [2026-06-10 Wed 9h]\n"),
+            Err(OrgParseError::InvalidClock { line: 2 })
+        ));
+        assert!(matches!(
+            parse("CLOCK: [2026-06-10 Wed 09:00]\n"),
+            Err(OrgParseError::ClockOutsideHeading { line: 1 })
+        ));
+    }
+
+    #[test]
+    fn notes_are_list_items_after_the_clock() {
+        use_berlin_time();
+        let entries = entries(&[
+            r#"start dev 2026-06-10 9:00 +02:00 "Review""#,
+            r#"stop dev 2026-06-10 11:15 +02:00 "Fixed the build\nand the tests""#,
+        ]);
+
+        let text = export(&data(), &entries, None);
+
+        assert!(text.contains(
+            "=>  2:15\n\
+             - Review\n\
+             - Fixed the build\n  \
+             and the tests\n\
+             :END:\n"
+        ));
+
+        let tasks = parse(&text).ok().unwrap();
+        assert_eq!(
+            tasks[0].clocks[0].notes,
+            vec!["Review", "Fixed the build\nand the tests"]
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use super::execution::Files;
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+//==============================================================================
+//
+//                              Type Definitions
+//
+//==============================================================================
+
+/// The profiles of a data directory, each one with its own history.log, data.json and undo.json
+/// files. The default profile keeps them in the data directory itself, the other profiles in
+/// `profiles/<name>` inside it.
+pub struct Profiles {
+    root: PathBuf,
+    /// The profile the commands operate on.
+    current: String,
+}
+
+/// The name of the profile stored in the data directory itself.
+pub const DEFAULT_PROFILE: &str = "default";
+
+//==============================================================================
+//
+//                              Profile Handling
+//
+//==============================================================================
+
+impl Profiles {
+    pub fn new<P: AsRef<Path>>(root: P, current: &str) -> Profiles {
+        Profiles {
+            root: root.as_ref().to_path_buf(),
+            current: current.to_string(),
+        }
+    }
+
+    pub fn current(&self) -> &str {
+        &self.current
+    }
+
+    /// The directory of a profile's files, which may not exist yet.
+    pub fn dir(&self, name: &str) -> PathBuf {
+        if name == DEFAULT_PROFILE {
+            self.root.clone()
+        } else {
+            self.root.join("profiles").join(name)
+        }
+    }
+
+    pub fn files(&self, name: &str) -> Files {
+        Files::new(self.dir(name))
+    }
+
+    pub fn exists(&self, name: &str) -> bool {
+        self.dir(name).is_dir()
+    }
+
+    /// The names of the profiles: the default one, then the others in alphabetical order.
+    pub fn list(&self) -> io::Result<Vec<String>> {
+        let mut names = Vec::new();
+
+        match fs::read_dir(self.root.join("profiles")) {
+            Ok(entries) => {
+                for entry in entries {
+                    let entry = entry?;
+
+                    if let Some(name) = entry.file_name().to_str() {
+                        if entry.file_type()?.is_dir() && is_valid_profile_name(name) {
+                            names.push(name.to_string());
+                        }
+                    }
+                }
+            }
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => {}
+            Err(error) => return Err(error),
+        }
+
+        names.sort();
+        names.insert(0, DEFAULT_PROFILE.to_string());
+        Ok(names)
+    }
+
+    /// Creates the directory of a new profile.
+    pub fn create(&self, name: &str) -> io::Result<()> {
+        fs::create_dir_all(self.dir(name))
+    }
+}
+
+/// Profile names are restricted to letters, numbers and dashes, like the parts of mnemonics.
+pub fn is_valid_profile_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+//==============================================================================
+//
+//                                   Tests
+//
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::temp_dir;
+
+    #[test]
+    fn profiles_are_listed_after_the_default_one() {
+        let root = temp_dir("profiles");
+        let profiles = Profiles::new(&root, DEFAULT_PROFILE);
+
+        assert_eq!(profiles.list().ok().unwrap(), vec![DEFAULT_PROFILE]);
+
+        profiles.create("work").unwrap();
+        profiles.create("side-project").unwrap();
+        fs::create_dir_all(root.join("profiles").join("not.a.profile")).unwrap();
+        fs::write(root.join("profiles").join("file"), "").unwrap();
+
+        assert_eq!(
+            profiles.list().ok().unwrap(),
+            vec![DEFAULT_PROFILE, "side-project", "work"]
+        );
+        assert_eq!(profiles.dir(DEFAULT_PROFILE), root);
+        assert_eq!(profiles.dir("work"), root.join("profiles").join("work"));
+        assert!(profiles.exists("work"));
+        assert!(!profiles.exists("home"));
+    }
+
+    #[test]
+    fn profile_names_are_checked() {
+        assert!(is_valid_profile_name("client-2"));
+        assert!(!is_valid_profile_name(""));
+        assert!(!is_valid_profile_name("a/b"));
+        assert!(!is_valid_profile_name(".."));
+    }
+}
@@ -0,0 +1,65 @@
+use std::io;
+use std::io::Write;
+
+//==============================================================================
+//
+//                              Prompt Functions
+//
+//==============================================================================
+
+/// Asks the user to type a line of text, returning it without the line break.
+pub fn ask(question: &str) -> io::Result<String> {
+    print!("{} ", question);
+    io::stdout().flush()?;
+
+    let answer = read_answer()?;
+
+    Ok(answer.trim_end_matches(['\n', '\r']).to_string())
+}
+
+/// Asks the user a yes/no question. Anything other than "y" or "yes" is considered a no.
+pub fn confirm(question: &str) -> io::Result<bool> {
+    let answer = ask(&format!("{} [y/N]", question))?;
+
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+#[cfg(not(test))]
+fn read_answer() -> io::Result<String> {
+    use std::io::BufRead;
+
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(answer)
+}
+
+/// Tests answer with what they passed to `tests::answer`, and fail on unexpected questions.
+#[cfg(test)]
+fn read_answer() -> io::Result<String> {
+    tests::ANSWERS
+        .with(|answers| answers.borrow_mut().pop_front())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "unexpected question"))
+}
+
+//==============================================================================
+//
+//                                   Tests
+//
+//==============================================================================
+
+#[cfg(test)]
+pub mod tests {
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+
+    thread_local! {
+        pub static ANSWERS: RefCell<VecDeque<String>> = const { RefCell::new(VecDeque::new()) };
+    }
+
+    /// Sets the answers to the next questions of the current test, in order.
+    pub fn answer(answers: &[&str]) {
+        ANSWERS.with(|queue| {
+            *queue.borrow_mut() = answers.iter().map(|answer| answer.to_string()).collect();
+        });
+    }
+}
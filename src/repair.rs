@@ -0,0 +1,588 @@
+use super::data::Data;
+use super::history::HistoryEntry;
+use super::history::HistoryLine;
+use super::DateTime;
+use super::ForgetableDateTime;
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt::Write;
+
+//==============================================================================
+//
+//                              Type Definitions
+//
+//==============================================================================
+
+/// The result of repairing the history: its entries once fixed, the tasks to be created and a
+/// description of each fix.
+pub struct Repair {
+    pub entries: Vec<HistoryEntry>,
+    pub tasks: Vec<String>,
+    pub fixes: Vec<String>,
+}
+
+/// A line of the history being repaired: the original text, unless the entry is a new one.
+struct Line {
+    text: String,
+    entry: HistoryEntry,
+}
+
+/// A step of a diff, with the index of the line in the old version, or in the new one for
+/// insertions.
+enum DiffOp {
+    Equal(usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+//==============================================================================
+//
+//                                   Repair
+//
+//==============================================================================
+
+/// Fixes the common problems of a history: exact duplicate entries are dropped, entries out of
+/// chronological order are moved to their place, and sessions left open are stopped at the
+/// next `exit`. A session that's started again while open is only stopped right before that if
+/// `ask` agrees. Tasks found only in the history are created. Blank lines aren't entries, so
+/// they're left to the caller, which keeps them where they are.
+pub fn repair<F, E>(history: &[HistoryLine], data: &Data, mut ask: F) -> Result<Repair, E>
+where
+    F: FnMut(&str) -> Result<bool, E>,
+{
+    let mut fixes = Vec::new();
+
+    let mut lines: Vec<Line> = history
+        .iter()
+        .map(|line| Line {
+            text: line.text.clone(),
+            entry: line.entry.clone(),
+        })
+        .collect();
+
+    drop_duplicates(&mut lines, &mut fixes);
+    sort(&mut lines, &mut fixes);
+    stop_open_sessions(&mut lines, &mut fixes, &mut ask)?;
+
+    let mut tasks: Vec<String> = Vec::new();
+
+    for line in &lines {
+        if let Some(mnemonic) = line.entry.mnemonic() {
+            if data.task(mnemonic).is_none() && !tasks.iter().any(|task| task == mnemonic) {
+                fixes.push(format!(
+                    "Create the task {}, found only in the history",
+                    mnemonic
+                ));
+                tasks.push(mnemonic.to_string());
+            }
+        }
+    }
+
+    Ok(Repair {
+        entries: lines.into_iter().map(|line| line.entry).collect(),
+        tasks,
+        fixes,
+    })
+}
+
+/// Drops the entries identical to an earlier one. Commits have no date/time, so identical
+/// commits are legitimate and kept.
+fn drop_duplicates(lines: &mut Vec<Line>, fixes: &mut Vec<String>) {
+    let mut seen: HashSet<String> = HashSet::new();
+
+    lines.retain(|line| {
+        if line.entry.datetime().is_none() || seen.insert(line.text.trim().to_string()) {
+            true
+        } else {
+            fixes.push(format!("Drop the duplicate entry: {}", line.text.trim()));
+            false
+        }
+    });
+}
+
+/// Sorts the entries chronologically, keeping entries with the same date/time in their order
+/// and commits right after the entry they follow.
+fn sort(lines: &mut Vec<Line>, fixes: &mut Vec<String>) {
+    let mut blocks: Vec<(Option<DateTime>, Vec<Line>)> = Vec::new();
+
+    for line in lines.drain(..) {
+        match (line.entry.datetime(), blocks.last_mut()) {
+            (None, Some((_, block))) => block.push(line),
+            (datetime, _) => blocks.push((datetime, vec![line])),
+        }
+    }
+
+    let mut latest: Option<DateTime> = None;
+
+    for (datetime, block) in &blocks {
+        match (datetime, latest) {
+            (Some(datetime), Some(previous)) if *datetime < previous => {
+                fixes.push(format!(
+                    "Move the entry to its chronological place: {}",
+                    block[0].text.trim()
+                ));
+            }
+            (Some(datetime), _) => latest = Some(*datetime),
+            _ => {}
+        }
+    }
+
+    blocks.sort_by_key(|(datetime, _)| *datetime);
+    lines.extend(blocks.into_iter().flat_map(|(_, block)| block));
+}
+
+/// Stops the sessions still open at an `exit`, unless they're stopped later without being
+/// started again. Sessions started again while open are stopped right before that if `ask`
+/// agrees.
+fn stop_open_sessions<F, E>(
+    lines: &mut Vec<Line>,
+    fixes: &mut Vec<String>,
+    ask: &mut F,
+) -> Result<(), E>
+where
+    F: FnMut(&str) -> Result<bool, E>,
+{
+    let mut open: HashMap<String, usize> = HashMap::new();
+    let mut index = 0;
+
+    while index < lines.len() {
+        match lines[index].entry.clone() {
+            HistoryEntry::Start {
+                mnemonic, datetime, ..
+            } => {
+                if let Some(&start) = open.get(&mnemonic) {
+                    let question = format!(
+                        "The session started by\n    {}\nis still open when it's started again by\n    {}\nStop it right before?",
+                        lines[start].text.trim(),
+                        lines[index].text.trim()
+                    );
+
+                    if ask(&question)? {
+                        insert_stop(lines, index, &mnemonic, datetime, fixes);
+                        index += 1;
+                    }
+                }
+
+                open.insert(mnemonic, index);
+            }
+            HistoryEntry::Stop { mnemonic, .. } => {
+                open.remove(&mnemonic);
+            }
+            HistoryEntry::Exit { datetime } => {
+                let mut dangling: Vec<String> = open
+                    .keys()
+                    .filter(|mnemonic| !is_stopped_later(&lines[index + 1..], mnemonic))
+                    .cloned()
+                    .collect();
+
+                dangling.sort();
+
+                for mnemonic in dangling {
+                    insert_stop(lines, index, &mnemonic, datetime, fixes);
+                    open.remove(&mnemonic);
+                    index += 1;
+                }
+            }
+            _ => {}
+        }
+
+        index += 1;
+    }
+
+    Ok(())
+}
+
+/// Whether the next `start` or `stop` entry of the task is a `stop`.
+fn is_stopped_later(lines: &[Line], mnemonic: &str) -> bool {
+    lines
+        .iter()
+        .find_map(|line| match &line.entry {
+            HistoryEntry::Start {
+                mnemonic: other, ..
+            } if other == mnemonic => Some(false),
+            HistoryEntry::Stop {
+                mnemonic: other, ..
+            } if other == mnemonic => Some(true),
+            _ => None,
+        })
+        .unwrap_or(false)
+}
+
+fn insert_stop(
+    lines: &mut Vec<Line>,
+    index: usize,
+    mnemonic: &str,
+    datetime: ForgetableDateTime,
+    fixes: &mut Vec<String>,
+) {
+    let entry = HistoryEntry::Stop {
+        mnemonic: mnemonic.to_string(),
+        datetime,
+        note: None,
+    };
+
+    fixes.push(format!(
+        "Stop {} right before: {}",
+        mnemonic,
+        lines[index].text.trim()
+    ));
+
+    lines.insert(
+        index,
+        Line {
+            text: entry.to_string(),
+            entry,
+        },
+    );
+}
+
+//==============================================================================
+//
+//                                Unified Diff
+//
+//==============================================================================
+
+/// The differences between two versions of a file in the unified format, with 3 lines of
+/// context. Empty if they're the same.
+pub fn unified_diff(name: &str, old: &[String], new: &[String]) -> String {
+    const CONTEXT: usize = 3;
+
+    let ops = diff(old, new);
+    let changes: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, op)| !matches!(op, DiffOp::Equal(..)))
+        .map(|(index, _)| index)
+        .collect();
+
+    let mut output = String::new();
+
+    if changes.is_empty() {
+        return output;
+    }
+
+    writeln!(output, "--- a/{}", name).unwrap();
+    writeln!(output, "+++ b/{}", name).unwrap();
+
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+
+    for &change in &changes {
+        let start = change.saturating_sub(CONTEXT);
+        let end = (change + CONTEXT + 1).min(ops.len());
+
+        match hunks.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    for (start, end) in hunks {
+        let is_old = |op: &&DiffOp| !matches!(op, DiffOp::Insert(_));
+        let is_new = |op: &&DiffOp| !matches!(op, DiffOp::Delete(_));
+
+        // Empty ranges start at the line before them, the others at their first line.
+        let range = |before: usize, count: usize| {
+            if count == 0 {
+                format!("{},0", before)
+            } else {
+                format!("{},{}", before + 1, count)
+            }
+        };
+
+        writeln!(
+            output,
+            "@@ -{} +{} @@",
+            range(
+                ops[..start].iter().filter(is_old).count(),
+                ops[start..end].iter().filter(is_old).count()
+            ),
+            range(
+                ops[..start].iter().filter(is_new).count(),
+                ops[start..end].iter().filter(is_new).count()
+            )
+        )
+        .unwrap();
+
+        for op in &ops[start..end] {
+            match op {
+                DiffOp::Equal(old_index) => writeln!(output, " {}", old[*old_index]),
+                DiffOp::Delete(old_index) => writeln!(output, "-{}", old[*old_index]),
+                DiffOp::Insert(new_index) => writeln!(output, "+{}", new[*new_index]),
+            }
+            .unwrap();
+        }
+    }
+
+    output
+}
+
+/// The steps turning `old` into `new`. The lines in common at the start and at the end are
+/// skipped, and the rest is compared by the longest common subsequence, unless it's too long,
+/// in which case it's all replaced.
+fn diff(old: &[String], new: &[String]) -> Vec<DiffOp> {
+    const MAX_CELLS: usize = 4_000_000;
+
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut ops: Vec<DiffOp> = (0..prefix).map(DiffOp::Equal).collect();
+
+    if old_middle.len() * new_middle.len() > MAX_CELLS {
+        ops.extend((0..old_middle.len()).map(|index| DiffOp::Delete(prefix + index)));
+        ops.extend((0..new_middle.len()).map(|index| DiffOp::Insert(prefix + index)));
+    } else {
+        let (n, m) = (old_middle.len(), new_middle.len());
+        let mut lengths = vec![vec![0usize; m + 1]; n + 1];
+
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lengths[i][j] = if old_middle[i] == new_middle[j] {
+                    lengths[i + 1][j + 1] + 1
+                } else {
+                    lengths[i + 1][j].max(lengths[i][j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+
+        while i < n || j < m {
+            if i < n && j < m && old_middle[i] == new_middle[j] {
+                ops.push(DiffOp::Equal(prefix + i));
+                i += 1;
+                j += 1;
+            } else if i < n && (j == m || lengths[i + 1][j] >= lengths[i][j + 1]) {
+                ops.push(DiffOp::Delete(prefix + i));
+                i += 1;
+            } else {
+                ops.push(DiffOp::Insert(prefix + j));
+                j += 1;
+            }
+        }
+    }
+
+    let old_end = old.len() - suffix;
+    ops.extend((0..suffix).map(|index| DiffOp::Equal(old_end + index)));
+    ops
+}
+
+//==============================================================================
+//
+//                                   Tests
+//
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::use_berlin_time;
+
+    use std::io;
+
+    fn history(lines: &[&str]) -> Vec<HistoryLine> {
+        let mut offset = 0;
+
+        lines
+            .iter()
+            .map(|&text| {
+                let line = HistoryLine {
+                    offset,
+                    text: text.to_string(),
+                    entry: text.parse().ok().unwrap(),
+                };
+
+                offset += text.len() as u64 + 1;
+                line
+            })
+            .collect()
+    }
+
+    fn data() -> Data {
+        serde_json::from_str(r#"{"tasks": {"dev": {"active": true, "title": "Development"}}}"#)
+            .unwrap()
+    }
+
+    fn lines(repair: &Repair) -> Vec<String> {
+        repair.entries.iter().map(HistoryEntry::to_string).collect()
+    }
+
+    fn never(_: &str) -> io::Result<bool> {
+        panic!("nothing to ask");
+    }
+
+    #[test]
+    fn a_sound_history_is_kept() {
+        use_berlin_time();
+        let history = history(&[
+            "enter 2026-06-10 8:30 +02:00",
+            "start dev 2026-06-10 9:00 +02:00",
+            "stop dev 2026-06-10 10:00 +02:00",
+            "commit dev",
+            "commit dev",
+            "exit 2026-06-10 17:00 +02:00",
+        ]);
+
+        let repair = repair(&history, &data(), never).ok().unwrap();
+
+        assert!(repair.fixes.is_empty());
+        assert!(repair.tasks.is_empty());
+        assert_eq!(repair.entries.len(), 6);
+    }
+
+    #[test]
+    fn duplicates_are_dropped() {
+        use_berlin_time();
+        let history = history(&[
+            "start dev 2026-06-10 9:00 +02:00",
+            "start dev 2026-06-10 9:00 +02:00",
+            "stop dev 2026-06-10 10:00 +02:00",
+        ]);
+
+        let repair = repair(&history, &data(), never).ok().unwrap();
+
+        assert_eq!(
+            lines(&repair),
+            vec![
+                "start dev 2026-06-10 9:00 +02:00",
+                "stop dev 2026-06-10 10:00 +02:00",
+            ]
+        );
+        assert_eq!(repair.fixes.len(), 1);
+    }
+
+    #[test]
+    fn entries_are_sorted_with_their_commits() {
+        use_berlin_time();
+        let history = history(&[
+            "start dev 2026-06-10 11:00 +02:00",
+            "stop dev 2026-06-10 12:00 +02:00",
+            "start dev 2026-06-10 9:00 +02:00",
+            "stop dev 2026-06-10 10:00 +02:00",
+            "commit dev",
+        ]);
+
+        let repair = repair(&history, &data(), never).ok().unwrap();
+
+        assert_eq!(
+            lines(&repair),
+            vec![
+                "start dev 2026-06-10 9:00 +02:00",
+                "stop dev 2026-06-10 10:00 +02:00",
+                "commit dev",
+                "start dev 2026-06-10 11:00 +02:00",
+                "stop dev 2026-06-10 12:00 +02:00",
+            ]
+        );
+    }
+
+    #[test]
+    fn open_sessions_are_stopped_at_exit() {
+        use_berlin_time();
+        let history = history(&[
+            "enter 2026-06-10 8:30 +02:00",
+            "start dev 2026-06-10 9:00 +02:00",
+            "exit 2026-06-10 17:00 +02:00",
+            "enter 2026-06-11 8:30 +02:00",
+            "start ops 2026-06-11 9:00 +02:00",
+            "exit 2026-06-11 12:00 +02:00",
+            "stop ops 2026-06-11 13:00 +02:00",
+        ]);
+
+        let repair = repair(&history, &data(), never).ok().unwrap();
+
+        assert_eq!(
+            lines(&repair),
+            vec![
+                "enter 2026-06-10 8:30 +02:00",
+                "start dev 2026-06-10 9:00 +02:00",
+                "stop dev 2026-06-10 17:00 +02:00",
+                "exit 2026-06-10 17:00 +02:00",
+                "enter 2026-06-11 8:30 +02:00",
+                "start ops 2026-06-11 9:00 +02:00",
+                "exit 2026-06-11 12:00 +02:00",
+                "stop ops 2026-06-11 13:00 +02:00",
+            ]
+        );
+        assert_eq!(repair.tasks, vec!["ops"]);
+    }
+
+    #[test]
+    fn sessions_started_again_are_stopped_if_agreed() {
+        use_berlin_time();
+        let history = history(&[
+            "start dev 2026-06-10 9:00 +02:00",
+            "start dev 2026-06-10 10:00 +02:00",
+            "stop dev 2026-06-10 11:00 +02:00",
+        ]);
+
+        let mut questions = 0;
+        let agreed = repair(&history, &data(), |_| -> io::Result<bool> {
+            questions += 1;
+            Ok(true)
+        })
+        .ok()
+        .unwrap();
+
+        assert_eq!(questions, 1);
+        assert_eq!(
+            lines(&agreed),
+            vec![
+                "start dev 2026-06-10 9:00 +02:00",
+                "stop dev 2026-06-10 10:00 +02:00",
+                "start dev 2026-06-10 10:00 +02:00",
+                "stop dev 2026-06-10 11:00 +02:00",
+            ]
+        );
+
+        let refused = repair(&history, &data(), |_| io::Result::Ok(false))
+            .ok()
+            .unwrap();
+
+        assert!(refused.fixes.is_empty());
+        assert_eq!(refused.entries.len(), 3);
+    }
+
+    #[test]
+    fn unified_diff_of_a_change() {
+        let lines =
+            |lines: &[&str]| -> Vec<String> { lines.iter().map(|line| line.to_string()).collect() };
+
+        let old = lines(&["a", "b", "c", "d", "e", "f", "g", "h", "i"]);
+        let new = lines(&["a", "b", "c", "d", "x", "f", "g", "h", "i", "j"]);
+
+        assert_eq!(
+            unified_diff("history.log", &old, &new),
+            "--- a/history.log\n\
+             +++ b/history.log\n\
+             @@ -2,8 +2,9 @@\n \
+             b\n c\n d\n-e\n+x\n f\n g\n h\n i\n+j\n"
+        );
+        assert_eq!(unified_diff("history.log", &old, &old), "");
+    }
+
+    #[test]
+    fn unified_diff_counts_blank_lines() {
+        let lines =
+            |lines: &[&str]| -> Vec<String> { lines.iter().map(|line| line.to_string()).collect() };
+
+        let old = lines(&["a", "a", "", "b", "c", "d"]);
+        let new = lines(&["a", "", "b", "c", "d"]);
+
+        assert_eq!(
+            unified_diff("history.log", &old, &new),
+            "--- a/history.log\n\
+             +++ b/history.log\n\
+             @@ -1,5 +1,4 @@\n \
+             a\n-a\n \n b\n c\n"
+        );
+    }
+}
@@ -0,0 +1,939 @@
+use chrono::prelude::*;
+use chrono::Duration;
+
+use super::config;
+use super::config::Config;
+use super::data::General;
+use super::data::Goals;
+use super::data::PlannedBreaks;
+use super::history::sessions;
+use super::history::Break;
+use super::history::HistoryEntry;
+use super::history::Presence;
+use super::history::Session;
+use super::is_within;
+use super::local_moments;
+use super::DateTime;
+use super::GoalPeriod;
+use super::Rounding;
+use super::RoundingScope;
+
+use std::collections::BTreeMap;
+
+//==============================================================================
+//
+//                              Type Definitions
+//
+//==============================================================================
+
+/// An interval of time, including its start and excluding its end.
+#[derive(Clone, Copy)]
+pub struct Period {
+    pub start: DateTime,
+    pub end: DateTime,
+}
+
+/// A line of a report: the time worked on a task or namespace, including everything
+/// within it, and the time worked on it alone.
+pub struct ReportLine {
+    pub mnemonic: String,
+    /// Number of namespaces above the mnemonic.
+    pub depth: usize,
+    pub own: Duration,
+    pub total: Duration,
+    /// Notes of the sessions of the task itself within the period.
+    pub notes: Vec<String>,
+}
+
+/// The time worked on a task since its previous commit and the time to be logged for it.
+pub struct Billing {
+    pub worked: Duration,
+    pub logged: Duration,
+    /// Time worked but not logged yet, or logged in advance if negative.
+    pub carry: Duration,
+}
+
+/// The time worked and expected to be worked in a week, for the flex-time balance.
+pub struct BalanceWeek {
+    /// The first day of the week, or the first day counted if it's later.
+    pub start: NaiveDate,
+    pub worked: Duration,
+    pub expected: Duration,
+}
+
+//==============================================================================
+//
+//                                  Periods
+//
+//==============================================================================
+
+impl Period {
+    /// The day, week or month containing the given date/time. Weeks and months start on the
+    /// configured days, e.g. from Monday and from the 1st by default, and a month starting on
+    /// the 26th ends on the 25th of the next calendar month. A weekday period is just the day.
+    pub fn containing(kind: GoalPeriod, datetime: DateTime) -> Period {
+        Period::containing_with(kind, datetime, config::current())
+    }
+
+    /// The period containing the given date/time, according to the given configuration.
+    fn containing_with(kind: GoalPeriod, datetime: DateTime, config: &Config) -> Period {
+        let date = day_of(datetime, config.day_start());
+
+        let (start, end) = match kind {
+            GoalPeriod::Day | GoalPeriod::Weekday(_) => (date, date.succ()),
+            GoalPeriod::Week => {
+                let start = date - Duration::days(days_into_week(date, config.week_start()));
+                (start, start + Duration::days(7))
+            }
+            GoalPeriod::Month => {
+                let day = config.month_start();
+                let start = NaiveDate::from_ymd(date.year(), date.month(), day);
+
+                let start = if date.day() < day {
+                    shift_month(start, -1)
+                } else {
+                    start
+                };
+
+                (start, shift_month(start, 1))
+            }
+        };
+
+        Period {
+            start: start_of_day(start),
+            end: start_of_day(end),
+        }
+    }
+
+    /// The days from `first` to `last`, both included.
+    pub fn days(first: NaiveDate, last: NaiveDate) -> Period {
+        Period {
+            start: start_of_day(first),
+            end: start_of_day(last.succ()),
+        }
+    }
+
+    /// A period containing every date/time.
+    pub fn all() -> Period {
+        Period {
+            start: Local.from_utc_datetime(&chrono::naive::MIN_DATE.succ().and_hms(0, 0, 0)),
+            end: Local.from_utc_datetime(&chrono::naive::MAX_DATE.pred().and_hms(0, 0, 0)),
+        }
+    }
+
+    /// How much of the given interval falls within the period.
+    pub fn overlap(&self, start: DateTime, stop: DateTime) -> Duration {
+        let start = start.max(self.start);
+        let stop = stop.min(self.end);
+
+        if stop > start {
+            stop - start
+        } else {
+            Duration::zero()
+        }
+    }
+}
+
+/// The same day in another month, which must exist in every month.
+fn shift_month(date: NaiveDate, months: i32) -> NaiveDate {
+    let index = date.year() * 12 + date.month0() as i32 + months;
+    NaiveDate::from_ymd(
+        index.div_euclid(12),
+        index.rem_euclid(12) as u32 + 1,
+        date.day(),
+    )
+}
+
+/// How many days of its week come before the date, for weeks starting on the given day.
+fn days_into_week(date: NaiveDate, week_start: Weekday) -> i64 {
+    let start = week_start.num_days_from_monday();
+    i64::from((date.weekday().num_days_from_monday() + 7 - start) % 7)
+}
+
+/// The day a date/time belongs to: its date, or the previous one before the configured time
+/// days start at, e.g. 01:30 belongs to the previous day if days start at 04:00.
+pub fn workday(datetime: DateTime) -> NaiveDate {
+    day_of(datetime, config::current().day_start())
+}
+
+/// The day a date/time belongs to, for days starting at the given time. The local time is
+/// compared, so a DST change doesn't move the start of the days.
+fn day_of(datetime: DateTime, day_start: NaiveTime) -> NaiveDate {
+    (datetime.naive_local() - (day_start - NaiveTime::from_hms(0, 0, 0))).date()
+}
+
+/// The first moment of a day, at the configured time days start at. If that time doesn't exist
+/// in the local time zone, because of a DST change, it's taken as UTC instead.
+pub fn start_of_day(date: NaiveDate) -> DateTime {
+    local(date.and_time(config::current().day_start()))
+}
+
+/// Converts a date/time to the local time zone. A date/time skipped by a DST change is taken
+/// as UTC instead, and the first moment of a repeated one is taken.
+fn local(naive: NaiveDateTime) -> DateTime {
+    local_moments(naive)
+        .first()
+        .cloned()
+        .unwrap_or_else(|| Local.from_utc_datetime(&naive))
+}
+
+//==============================================================================
+//
+//                                   Totals
+//
+//==============================================================================
+
+/// Time worked on a task within the period, including all the tasks in its namespace.
+/// Open sessions last until `now`.
+pub fn task_time(
+    sessions: &[Session],
+    namespace: &str,
+    period: &Period,
+    now: DateTime,
+) -> Duration {
+    sessions
+        .iter()
+        .filter(|session| is_within(&session.mnemonic, namespace))
+        .fold(Duration::zero(), |total, session| {
+            total + session_time(session, period, now)
+        })
+}
+
+/// Time spent at the workplace within the period. Open periods last until `now`.
+pub fn presence_time(presences: &[Presence], period: &Period, now: DateTime) -> Duration {
+    presences.iter().fold(Duration::zero(), |total, presence| {
+        let exit = presence.exit.map_or(now, |exit| exit.datetime);
+        total + period.overlap(presence.enter.datetime, exit)
+    })
+}
+
+/// Time spent on breaks within the period. Open breaks last until `now`.
+pub fn break_time(breaks: &[Break], period: &Period, now: DateTime) -> Duration {
+    breaks.iter().fold(Duration::zero(), |total, pause| {
+        let resume = pause.resume.map_or(now, |resume| resume.datetime);
+        total + period.overlap(pause.pause.datetime, resume)
+    })
+}
+
+/// The time to be logged by a commit placed right after the given entries, rounded according to
+/// the policy. The time left over by the rounding of each commit is carried over to the next
+/// one, starting from the first commit of the task.
+pub fn bill<'a, I>(entries: I, mnemonic: &str, rounding: &Rounding, now: DateTime) -> Billing
+where
+    I: IntoIterator<Item = &'a HistoryEntry>,
+{
+    let mut group = Vec::new();
+    let mut carry = Duration::zero();
+
+    for entry in entries {
+        match entry {
+            HistoryEntry::Commit {
+                mnemonic: other, ..
+            } if other == mnemonic => {
+                carry = bill_group(&group, mnemonic, rounding, carry, now).carry;
+                group.clear();
+            }
+            entry => group.push(entry),
+        }
+    }
+
+    bill_group(&group, mnemonic, rounding, carry, now)
+}
+
+fn bill_group(
+    entries: &[&HistoryEntry],
+    mnemonic: &str,
+    rounding: &Rounding,
+    carry: Duration,
+    now: DateTime,
+) -> Billing {
+    let times: Vec<Duration> = sessions(entries.iter().cloned())
+        .iter()
+        .filter(|session| session.mnemonic == mnemonic)
+        .map(|session| session.duration(now))
+        .collect();
+
+    let worked = times
+        .iter()
+        .fold(Duration::zero(), |total, time| total + *time);
+
+    let logged = match rounding.per {
+        RoundingScope::Commit => rounding.round(worked + carry),
+        RoundingScope::Session => {
+            let mut carry = carry;
+            let mut logged = Duration::zero();
+
+            for &time in &times {
+                let rounded = rounding.round(time + carry);
+                carry = carry + time - rounded;
+                logged = logged + rounded;
+            }
+
+            logged
+        }
+    };
+
+    Billing {
+        worked,
+        logged,
+        carry: carry + worked - logged,
+    }
+}
+
+/// The moment the given amount of work will be done if it starts `now`, skipping the planned
+/// breaks that haven't started yet.
+pub fn forecast(planned: &PlannedBreaks, now: DateTime, work: Duration) -> DateTime {
+    // Planned breaks covering whole days would never let the work end.
+    const MAX_DAYS: usize = 366;
+
+    let mut time = now;
+    let mut work = work;
+    let mut date = now.date().naive_local();
+
+    if planned.is_empty() {
+        return now + work;
+    }
+
+    for _ in 0..MAX_DAYS {
+        for &(start, duration) in planned.iter() {
+            let start = local(date.and_time(start));
+
+            if start < time {
+                continue;
+            }
+
+            if time + work <= start {
+                return time + work;
+            }
+
+            work = work - (start - time);
+            time = start + duration;
+        }
+
+        date = date.succ();
+    }
+
+    time + work
+}
+
+fn session_time(session: &Session, period: &Period, now: DateTime) -> Duration {
+    let stop = session.stop.map_or(now, |stop| stop.datetime);
+    period.overlap(session.start.datetime, stop)
+}
+
+/// The goal for the period containing the given date/time. A goal for a specific day of the
+/// week takes precedence over the goal for every day.
+pub fn goal_for(goals: &Goals, kind: GoalPeriod, datetime: DateTime) -> Option<Duration> {
+    match kind {
+        GoalPeriod::Day => goals
+            .get(GoalPeriod::Weekday(workday(datetime).weekday()))
+            .or_else(|| goals.get(GoalPeriod::Day)),
+        GoalPeriod::Weekday(weekday) if weekday != workday(datetime).weekday() => None,
+        kind => goals.get(kind),
+    }
+}
+
+/// Time expected to be worked in general on the given day: its goal, if any, or half of it on a
+/// half day away, or nothing on a day away.
+pub fn expected_time(general: &General, date: NaiveDate) -> Duration {
+    let goal = goal_for(&general.goals, GoalPeriod::Day, start_of_day(date))
+        .unwrap_or_else(Duration::zero);
+
+    match general.absence_on(date) {
+        Some(absence) if absence.half => goal / 2,
+        Some(_) => Duration::zero(),
+        None => goal,
+    }
+}
+
+/// The general goal for the period containing the given date/time, taking the absences into
+/// account: the goal of a day away is reduced to its expected time, and week and month goals
+/// are reduced by the daily goals that aren't expected on the days away within them.
+pub fn general_goal_for(
+    general: &General,
+    kind: GoalPeriod,
+    datetime: DateTime,
+) -> Option<Duration> {
+    let goal = goal_for(&general.goals, kind, datetime)?;
+
+    if let GoalPeriod::Day | GoalPeriod::Weekday(_) = kind {
+        return Some(expected_time(general, workday(datetime)));
+    }
+
+    let period = Period::containing(kind, datetime);
+    let mut date = workday(period.start);
+    let mut goal = goal;
+
+    while date < workday(period.end) {
+        if general.absence_on(date).is_some() {
+            let day = goal_for(&general.goals, GoalPeriod::Day, start_of_day(date))
+                .unwrap_or_else(Duration::zero);
+
+            goal = goal - (day - expected_time(general, date));
+        }
+
+        date = date.succ();
+    }
+
+    Some(goal.max(Duration::zero()))
+}
+
+//==============================================================================
+//
+//                                  Balance
+//
+//==============================================================================
+
+/// The time worked in general, given by `worked` for each day, and the time expected by the
+/// daily goals in each week, counting the days from `first` until the day before `end`. Weeks
+/// start on the configured day.
+pub fn balance<F>(
+    general: &General,
+    worked: F,
+    first: NaiveDate,
+    end: NaiveDate,
+) -> Vec<BalanceWeek>
+where
+    F: Fn(&Period) -> Duration,
+{
+    let mut weeks: Vec<BalanceWeek> = Vec::new();
+    let mut date = first;
+
+    while date < end {
+        let period = Period {
+            start: start_of_day(date),
+            end: start_of_day(date.succ()),
+        };
+
+        let worked = worked(&period);
+        let expected = expected_time(general, date);
+
+        match weeks.last_mut() {
+            Some(week) if days_into_week(date, config::current().week_start()) != 0 => {
+                week.worked = week.worked + worked;
+                week.expected = week.expected + expected;
+            }
+            _ => weeks.push(BalanceWeek {
+                start: date,
+                worked,
+                expected,
+            }),
+        }
+
+        date = date.succ();
+    }
+
+    weeks
+}
+
+//==============================================================================
+//
+//                                  Reports
+//
+//==============================================================================
+
+/// The time worked on each task within the period, optionally only the ones within a
+/// namespace. Namespaces are included even if they aren't tasks themselves, and every
+/// namespace comes right before the tasks within it.
+pub fn report(
+    sessions: &[Session],
+    namespace: Option<&str>,
+    period: &Period,
+    now: DateTime,
+) -> Vec<ReportLine> {
+    let mut own: BTreeMap<Vec<&str>, (Duration, Vec<String>)> = BTreeMap::new();
+
+    for session in sessions {
+        if namespace.is_some_and(|namespace| !is_within(&session.mnemonic, namespace)) {
+            continue;
+        }
+
+        let time = session_time(session, period, now);
+
+        if time <= Duration::zero() {
+            continue;
+        }
+
+        let path: Vec<&str> = session.mnemonic.split('/').collect();
+
+        for depth in 1..path.len() {
+            let ancestor = path[..depth].to_vec();
+
+            if namespace.is_none_or(|namespace| is_within(&ancestor.join("/"), namespace)) {
+                own.entry(ancestor)
+                    .or_insert_with(|| (Duration::zero(), Vec::new()));
+            }
+        }
+
+        let entry = own
+            .entry(path)
+            .or_insert_with(|| (Duration::zero(), Vec::new()));
+
+        entry.0 = entry.0 + time;
+        entry.1.extend(session.notes.iter().cloned());
+    }
+
+    let top = namespace.map_or(1, |namespace| namespace.split('/').count());
+
+    own.iter()
+        .map(|(path, (time, notes))| ReportLine {
+            mnemonic: path.join("/"),
+            depth: path.len() - top,
+            own: *time,
+            total: own
+                .iter()
+                .filter(|(other, _)| other.starts_with(path))
+                .fold(Duration::zero(), |total, (_, (time, _))| total + *time),
+            notes: notes.clone(),
+        })
+        .collect()
+}
+
+//==============================================================================
+//
+//                                   Tests
+//
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Absence;
+    use crate::history::breaks;
+    use crate::history::presences;
+    use crate::history::sessions;
+    use crate::history::HistoryEntry;
+    use crate::tests::use_berlin_time;
+    use crate::AbsenceKind;
+    use crate::RoundingDirection;
+
+    use core::str::FromStr;
+
+    fn at(datetime: &str) -> DateTime {
+        HistoryEntry::from_str(&format!("enter {}", datetime))
+            .ok()
+            .and_then(|entry| entry.datetime())
+            .unwrap()
+    }
+
+    fn day_sessions() -> Vec<Session> {
+        let entries: Vec<HistoryEntry> = [
+            "start project 2026-06-10 8:00 +02:00",
+            "stop project 2026-06-10 8:30 +02:00",
+            "start project/api 2026-06-10 9:00 +02:00",
+            "stop project/api 2026-06-10 11:00 +02:00",
+            "start project/ui/forms 2026-06-10 11:00 +02:00",
+            "stop project/ui/forms 2026-06-10 12:00 +02:00",
+            "start project-x 2026-06-10 13:00 +02:00",
+            "stop project-x 2026-06-10 14:00 +02:00",
+            "start ops 2026-06-10 23:00 +02:00",
+        ]
+        .iter()
+        .map(|line| HistoryEntry::from_str(line).ok().unwrap())
+        .collect();
+
+        sessions(&entries)
+    }
+
+    fn lines(report: &[ReportLine]) -> Vec<(String, usize, i64, i64)> {
+        report
+            .iter()
+            .map(|line| {
+                (
+                    line.mnemonic.clone(),
+                    line.depth,
+                    line.own.num_minutes(),
+                    line.total.num_minutes(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn report_rolls_up_namespaces() {
+        use_berlin_time();
+        let now = at("2026-06-11 1:00 +02:00");
+        let day = Period::containing(GoalPeriod::Day, at("2026-06-10 12:00 +02:00"));
+
+        assert_eq!(
+            lines(&report(&day_sessions(), None, &day, now)),
+            vec![
+                ("ops".to_string(), 0, 60, 60),
+                ("project".to_string(), 0, 30, 210),
+                ("project/api".to_string(), 1, 120, 120),
+                ("project/ui".to_string(), 1, 0, 60),
+                ("project/ui/forms".to_string(), 2, 60, 60),
+                ("project-x".to_string(), 0, 60, 60),
+            ]
+        );
+
+        assert_eq!(
+            lines(&report(&day_sessions(), Some("project/ui"), &day, now)),
+            vec![
+                ("project/ui".to_string(), 0, 0, 60),
+                ("project/ui/forms".to_string(), 1, 60, 60),
+            ]
+        );
+
+        assert_eq!(
+            task_time(&day_sessions(), "project", &day, now),
+            Duration::minutes(210)
+        );
+    }
+
+    #[test]
+    fn periods_contain_the_date() {
+        use_berlin_time();
+        let datetime = at("2026-06-10 12:00 +02:00");
+
+        let week = Period::containing(GoalPeriod::Week, datetime);
+        assert_eq!(week.start, at("2026-06-08 0:00 +02:00"));
+        assert_eq!(week.end, at("2026-06-15 0:00 +02:00"));
+
+        let month = Period::containing(GoalPeriod::Month, at("2026-12-31 23:00 +01:00"));
+        assert_eq!(month.start, at("2026-12-01 0:00 +01:00"));
+        assert_eq!(month.end, at("2027-01-01 0:00 +01:00"));
+
+        assert_eq!(
+            week.overlap(at("2026-06-14 23:00 +02:00"), at("2026-06-15 2:00 +02:00")),
+            Duration::hours(1)
+        );
+    }
+
+    #[test]
+    fn forecast_skips_the_planned_breaks() {
+        use_berlin_time();
+        let mut planned = PlannedBreaks::default();
+        let time = |time: &str| NaiveTime::parse_from_str(time, "%H:%M").unwrap();
+
+        planned.set(time("12:00"), Duration::minutes(45));
+        planned.set(time("15:00"), Duration::minutes(15));
+
+        let now = at("2026-06-10 11:00 +02:00");
+
+        assert_eq!(
+            forecast(&planned, now, Duration::minutes(30)),
+            at("2026-06-10 11:30 +02:00")
+        );
+        assert_eq!(
+            forecast(&planned, now, Duration::hours(2)),
+            at("2026-06-10 13:45 +02:00")
+        );
+        assert_eq!(
+            forecast(&planned, now, Duration::hours(5)),
+            at("2026-06-10 17:00 +02:00")
+        );
+
+        // A break that already started is over by then.
+        assert_eq!(
+            forecast(&planned, at("2026-06-10 12:30 +02:00"), Duration::hours(1)),
+            at("2026-06-10 13:30 +02:00")
+        );
+    }
+
+    /// A daily goal of 8 hours from Monday to Friday.
+    fn week_goals() -> General {
+        let mut general = General::default();
+        general.goals.set(GoalPeriod::Day, Duration::hours(8));
+        general
+            .goals
+            .set(GoalPeriod::Weekday(Weekday::Sat), Duration::zero());
+        general
+            .goals
+            .set(GoalPeriod::Weekday(Weekday::Sun), Duration::zero());
+        general
+    }
+
+    fn date(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn balance_counts_whole_days_by_week() {
+        use_berlin_time();
+        let entries: Vec<HistoryEntry> = [
+            "enter 2026-06-05 8:00 +02:00",
+            "pause 2026-06-05 12:00 +02:00",
+            "resume 2026-06-05 13:00 +02:00",
+            "exit 2026-06-05 17:00 +02:00",
+            "enter 2026-06-08 9:00 +02:00",
+            "exit 2026-06-08 16:00 +02:00",
+            "enter 2026-06-09 9:00 +02:00",
+        ]
+        .iter()
+        .map(|line| HistoryEntry::from_str(line).ok().unwrap())
+        .collect();
+
+        let general = week_goals();
+
+        let now = at("2026-06-09 12:00 +02:00");
+
+        let presences = presences(entries.iter());
+        let breaks = breaks(entries.iter());
+
+        let weeks: Vec<(NaiveDate, i64, i64)> = balance(
+            &general,
+            |period| presence_time(&presences, period, now) - break_time(&breaks, period, now),
+            date("2026-06-05"),
+            date("2026-06-09"),
+        )
+        .iter()
+        .map(|week| {
+            (
+                week.start,
+                week.worked.num_hours(),
+                week.expected.num_hours(),
+            )
+        })
+        .collect();
+
+        // The week that is already running when the balance starts is counted from its first
+        // day, and today isn't counted at all.
+        assert_eq!(
+            weeks,
+            vec![(date("2026-06-05"), 8, 8), (date("2026-06-08"), 7, 8)]
+        );
+    }
+
+    #[test]
+    fn absences_reduce_the_general_goals() {
+        use_berlin_time();
+        let mut general = week_goals();
+        general.goals.set(GoalPeriod::Week, Duration::hours(40));
+        general.goals.set(GoalPeriod::Month, Duration::hours(160));
+
+        let absence = |start: &str, end: &str, half: bool| Absence {
+            kind: AbsenceKind::Vacation,
+            start: date(start),
+            end: date(end),
+            half,
+            name: None,
+        };
+
+        // Wednesday to the following Monday, then a half day on Tuesday.
+        assert!(general
+            .add_absence(absence("2026-06-10", "2026-06-15", false))
+            .is_ok());
+        assert!(general
+            .add_absence(absence("2026-06-16", "2026-06-16", true))
+            .is_ok());
+        assert!(general
+            .add_absence(absence("2026-06-15", "2026-06-17", false))
+            .is_err());
+
+        let goal = |kind: GoalPeriod, datetime: &str| {
+            general_goal_for(&general, kind, at(datetime)).map(|goal| goal.num_hours())
+        };
+
+        assert_eq!(goal(GoalPeriod::Day, "2026-06-09 12:00 +02:00"), Some(8));
+        assert_eq!(goal(GoalPeriod::Day, "2026-06-10 12:00 +02:00"), Some(0));
+        assert_eq!(goal(GoalPeriod::Day, "2026-06-16 12:00 +02:00"), Some(4));
+
+        // Three days away in the first week, the weekend doesn't count.
+        assert_eq!(goal(GoalPeriod::Week, "2026-06-10 12:00 +02:00"), Some(16));
+        // One day and a half away in the second one.
+        assert_eq!(goal(GoalPeriod::Week, "2026-06-16 12:00 +02:00"), Some(28));
+        assert_eq!(
+            goal(GoalPeriod::Month, "2026-06-16 12:00 +02:00"),
+            Some(124)
+        );
+    }
+
+    fn entries_of(lines: &[&str]) -> Vec<HistoryEntry> {
+        lines
+            .iter()
+            .map(|line| HistoryEntry::from_str(line).ok().unwrap())
+            .collect()
+    }
+
+    fn rounding(direction: RoundingDirection, per: RoundingScope) -> Rounding {
+        Rounding {
+            direction,
+            step: 15,
+            per,
+        }
+    }
+
+    #[test]
+    fn bill_carries_the_rounding_over_commits() {
+        use_berlin_time();
+        let history = entries_of(&[
+            "start dev 2026-06-10 9:00 +02:00",
+            "stop dev 2026-06-10 9:10 +02:00",
+            "commit dev",
+            "start dev 2026-06-10 10:00 +02:00",
+            "stop dev 2026-06-10 10:10 +02:00",
+        ]);
+
+        let first = bill(
+            &history[..2],
+            "dev",
+            &rounding(RoundingDirection::Up, RoundingScope::Commit),
+            at("2026-06-11 12:00 +02:00"),
+        );
+        assert_eq!(first.worked, Duration::minutes(10));
+        assert_eq!(first.logged, Duration::minutes(15));
+        assert_eq!(first.carry, Duration::minutes(-5));
+
+        // The 5 minutes logged in advance are taken off the next commit, whose 5 minutes left
+        // are rounded up again.
+        let up = bill(
+            &history,
+            "dev",
+            &rounding(RoundingDirection::Up, RoundingScope::Commit),
+            at("2026-06-11 12:00 +02:00"),
+        );
+        assert_eq!(up.worked, Duration::minutes(10));
+        assert_eq!(up.logged, Duration::minutes(15));
+        assert_eq!(up.carry, Duration::minutes(-10));
+
+        let down = bill(
+            &history,
+            "dev",
+            &rounding(RoundingDirection::Down, RoundingScope::Commit),
+            at("2026-06-11 12:00 +02:00"),
+        );
+        assert_eq!(down.logged, Duration::minutes(15));
+        assert_eq!(down.carry, Duration::minutes(5));
+    }
+
+    #[test]
+    fn bill_ignores_other_tasks() {
+        use_berlin_time();
+        let history = entries_of(&[
+            "start dev 2026-06-10 9:00 +02:00",
+            "stop dev 2026-06-10 9:20 +02:00",
+            "start ops 2026-06-10 9:20 +02:00",
+            "stop ops 2026-06-10 9:40 +02:00",
+            "commit ops",
+        ]);
+
+        let billing = bill(
+            &history,
+            "dev",
+            &rounding(RoundingDirection::Nearest, RoundingScope::Commit),
+            at("2026-06-11 12:00 +02:00"),
+        );
+        assert_eq!(billing.worked, Duration::minutes(20));
+        assert_eq!(billing.logged, Duration::minutes(15));
+        assert_eq!(billing.carry, Duration::minutes(5));
+    }
+
+    #[test]
+    fn bill_rounds_each_session_with_the_carry() {
+        use_berlin_time();
+        let history = entries_of(&[
+            "start dev 2026-06-10 9:00 +02:00",
+            "stop dev 2026-06-10 9:10 +02:00",
+            "start dev 2026-06-10 10:00 +02:00",
+            "stop dev 2026-06-10 10:10 +02:00",
+            "start dev 2026-06-10 11:00 +02:00",
+            "stop dev 2026-06-10 11:10 +02:00",
+        ]);
+
+        let session = bill(
+            &history,
+            "dev",
+            &rounding(RoundingDirection::Nearest, RoundingScope::Session),
+            at("2026-06-11 12:00 +02:00"),
+        );
+        // 10m rounds to 15m, then 10m - 5m to 0m, then 10m + 5m to 15m.
+        assert_eq!(session.logged, Duration::minutes(30));
+        assert_eq!(session.carry, Duration::zero());
+
+        let commit = bill(
+            &history,
+            "dev",
+            &rounding(RoundingDirection::Nearest, RoundingScope::Commit),
+            at("2026-06-11 12:00 +02:00"),
+        );
+        assert_eq!(commit.logged, Duration::minutes(30));
+        assert_eq!(commit.carry, Duration::zero());
+    }
+
+    #[test]
+    fn an_open_session_lasts_until_now() {
+        use_berlin_time();
+        let history = entries_of(&["start dev 2026-06-11 11:20 +02:00"]);
+
+        let billing = bill(
+            &history,
+            "dev",
+            &rounding(RoundingDirection::Up, RoundingScope::Commit),
+            at("2026-06-11 12:00 +02:00"),
+        );
+        assert_eq!(billing.worked, Duration::minutes(40));
+        assert_eq!(billing.logged, Duration::minutes(45));
+    }
+
+    #[test]
+    fn periods_start_on_the_configured_days() {
+        use_berlin_time();
+        let mut config = Config::default();
+        config.set("month_start", Some("26")).ok().unwrap();
+        config.set("week_start", Some("sunday")).ok().unwrap();
+
+        let period = |kind, datetime| Period::containing_with(kind, at(datetime), &config);
+
+        let month = period(GoalPeriod::Month, "2026-06-10 12:00 +02:00");
+        assert_eq!(month.start, at("2026-05-26 0:00 +02:00"));
+        assert_eq!(month.end, at("2026-06-26 0:00 +02:00"));
+
+        let month = period(GoalPeriod::Month, "2026-12-26 0:00 +01:00");
+        assert_eq!(month.start, at("2026-12-26 0:00 +01:00"));
+        assert_eq!(month.end, at("2027-01-26 0:00 +01:00"));
+
+        let month = period(GoalPeriod::Month, "2027-01-25 23:59 +01:00");
+        assert_eq!(month.start, at("2026-12-26 0:00 +01:00"));
+
+        // 2026-06-14 is a Sunday.
+        let week = period(GoalPeriod::Week, "2026-06-13 12:00 +02:00");
+        assert_eq!(week.start, at("2026-06-07 0:00 +02:00"));
+        assert_eq!(week.end, at("2026-06-14 0:00 +02:00"));
+
+        let week = period(GoalPeriod::Week, "2026-06-14 0:00 +02:00");
+        assert_eq!(week.start, at("2026-06-14 0:00 +02:00"));
+    }
+
+    #[test]
+    fn workdays_start_at_the_given_time() {
+        use_berlin_time();
+        let four = NaiveTime::from_hms(4, 0, 0);
+
+        assert_eq!(
+            day_of(at("2026-06-11 1:30 +02:00"), four),
+            date("2026-06-10")
+        );
+        assert_eq!(
+            day_of(at("2026-06-11 4:00 +02:00"), four),
+            date("2026-06-11")
+        );
+        assert_eq!(
+            day_of(at("2026-06-11 1:30 +02:00"), NaiveTime::from_hms(0, 0, 0)),
+            date("2026-06-11")
+        );
+
+        // The local time counts across DST changes: 3:30 is still the night before when the
+        // clocks are put back at 3:00, and the day starts at 4:00 when they're put forward.
+        assert_eq!(
+            day_of(at("2026-10-25 2:30 +01:00"), four),
+            date("2026-10-24")
+        );
+        assert_eq!(
+            day_of(at("2026-10-25 3:30 +01:00"), four),
+            date("2026-10-24")
+        );
+        assert_eq!(
+            day_of(at("2026-10-25 4:00 +01:00"), four),
+            date("2026-10-25")
+        );
+        assert_eq!(
+            day_of(at("2026-03-29 3:30 +02:00"), four),
+            date("2026-03-28")
+        );
+        assert_eq!(
+            day_of(at("2026-03-29 4:00 +02:00"), four),
+            date("2026-03-29")
+        );
+    }
+}
@@ -0,0 +1,398 @@
+use std::ffi::OsString;
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+//==============================================================================
+//
+//                              Write-Ahead Log
+//
+//==============================================================================
+
+// Rewriting the end of a file means truncating it and appending the new tail, which leaves
+// the file incomplete if the process dies in between. So the tail is first written to a side
+// file, `<file>.wal`, which is removed once the file itself is written. A side file left
+// behind by a crash is replayed by `recover`.
+//
+// The side file contains a record per rewritten file: a header line, `<offset> <length>
+// <checksum>`, followed by the tail. Files rewritten along with the one the side file is
+// named after have their name at the end of the header, and the side file then starts with a
+// line containing the number of records. The files themselves are only truncated after the
+// side file is complete and synced, so an incomplete side file means no file was touched, and
+// it's discarded.
+
+/// The end of a file to be replaced, from `offset` on.
+pub struct Rewrite {
+    pub path: PathBuf,
+    pub offset: u64,
+    pub tail: Vec<u8>,
+}
+
+/// Replaces everything from `offset` to the end of the file with `tail`, creating the file if
+/// needed.
+pub fn rewrite_tail(path: &Path, offset: u64, tail: &[u8]) -> io::Result<()> {
+    rewrite_tails(&[Rewrite {
+        path: path.to_path_buf(),
+        offset,
+        tail: tail.to_vec(),
+    }])
+}
+
+/// Applies rewrites of files in the same directory as a whole: they're all recorded in the
+/// side file of the first one, so a crash can't leave some of them done and others not.
+pub fn rewrite_tails(rewrites: &[Rewrite]) -> io::Result<()> {
+    let wal = match rewrites.first() {
+        Some(first) => write(&first.path, rewrites)?,
+        None => return Ok(()),
+    };
+
+    for rewrite in rewrites {
+        apply(&rewrite.path, rewrite.offset, &rewrite.tail)?;
+    }
+
+    fs::remove_file(&wal)
+}
+
+/// Whether a side file was left behind by an interrupted rewrite.
+pub fn is_pending(path: &Path) -> bool {
+    wal_path(path).exists()
+}
+
+/// Completes the rewrites interrupted by a crash, if the side file was written completely,
+/// and removes the side file. Returns the files rewritten, which are none if the side file is
+/// missing or incomplete.
+pub fn recover(path: &Path) -> io::Result<Vec<PathBuf>> {
+    let wal = wal_path(path);
+
+    let mut contents = Vec::new();
+
+    match File::open(&wal) {
+        Ok(mut file) => file.read_to_end(&mut contents)?,
+        Err(ref error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error),
+    };
+
+    let rewrites = parse(path, &contents).unwrap_or_default();
+
+    for rewrite in &rewrites {
+        apply(&rewrite.path, rewrite.offset, &rewrite.tail)?;
+    }
+
+    fs::remove_file(&wal)?;
+    Ok(rewrites.into_iter().map(|rewrite| rewrite.path).collect())
+}
+
+/// Writes and syncs the side file of the rewrites, named after the given file, returning its
+/// path.
+fn write(path: &Path, rewrites: &[Rewrite]) -> io::Result<PathBuf> {
+    let wal = wal_path(path);
+
+    {
+        let mut file = File::create(&wal)?;
+
+        if rewrites.len() > 1 {
+            writeln!(file, "{}", rewrites.len())?;
+        }
+
+        for rewrite in rewrites {
+            let tail = &rewrite.tail;
+            write!(
+                file,
+                "{} {} {:016x}",
+                rewrite.offset,
+                tail.len(),
+                checksum(tail)
+            )?;
+
+            if rewrite.path != path {
+                let name = rewrite.path.file_name().unwrap_or_default();
+                write!(file, " {}", name.to_string_lossy())?;
+            }
+
+            writeln!(file)?;
+            file.write_all(tail)?;
+        }
+
+        file.sync_all()?;
+    }
+
+    sync_dir(&wal)?;
+    Ok(wal)
+}
+
+fn apply(path: &Path, offset: u64, tail: &[u8]) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?;
+
+    file.set_len(offset)?;
+    file.seek(SeekFrom::End(0))?;
+    file.write_all(tail)?;
+    file.sync_all()
+}
+
+/// The rewrites recorded in the side file of the given file, if it's complete.
+fn parse(path: &Path, mut contents: &[u8]) -> Option<Vec<Rewrite>> {
+    let line = |contents: &[u8]| -> Option<(String, usize)> {
+        let end = contents.iter().position(|&byte| byte == b'\n')?;
+        let line = std::str::from_utf8(&contents[..end]).ok()?;
+        Some((line.to_string(), end + 1))
+    };
+
+    let (first, length) = line(contents)?;
+    let count = match first.parse::<usize>() {
+        Ok(count) => {
+            contents = &contents[length..];
+            count
+        }
+        Err(_) => 1,
+    };
+
+    let mut rewrites = Vec::new();
+
+    while !contents.is_empty() {
+        let (header, end) = line(contents)?;
+
+        let mut fields = header.splitn(4, ' ');
+        let offset = fields.next()?.parse().ok()?;
+        let length: usize = fields.next()?.parse().ok()?;
+        let sum = u64::from_str_radix(fields.next()?, 16).ok()?;
+        let path = fields
+            .next()
+            .map_or_else(|| path.to_path_buf(), |name| path.with_file_name(name));
+
+        let tail = contents.get(end..end + length)?;
+
+        if checksum(tail) != sum {
+            return None;
+        }
+
+        rewrites.push(Rewrite {
+            path,
+            offset,
+            tail: tail.to_vec(),
+        });
+
+        contents = &contents[end + length..];
+    }
+
+    Some(rewrites).filter(|rewrites| rewrites.len() == count)
+}
+
+fn wal_path(path: &Path) -> PathBuf {
+    let mut name = path
+        .file_name()
+        .map_or_else(OsString::new, |name| name.to_os_string());
+
+    name.push(".wal");
+    path.with_file_name(name)
+}
+
+/// 64-bit FNV-1a hash.
+pub fn checksum(bytes: &[u8]) -> u64 {
+    extend_checksum(0xcbf2_9ce4_8422_2325, bytes)
+}
+
+/// Continues a checksum over more bytes, i.e. `extend_checksum(checksum(a), b)` is the checksum
+/// of `a` followed by `b`.
+pub fn extend_checksum(checksum: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(checksum, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Makes the creation of the file durable, which needs the directory to be synced on Unix.
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> io::Result<()> {
+    match path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        Some(dir) => File::open(dir)?.sync_all(),
+        None => File::open(".")?.sync_all(),
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+//==============================================================================
+//
+//                                   Tests
+//
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::temp_dir;
+
+    fn rewrite(path: &Path, offset: u64, tail: &[u8]) -> Rewrite {
+        Rewrite {
+            path: path.to_path_buf(),
+            offset,
+            tail: tail.to_vec(),
+        }
+    }
+
+    #[test]
+    fn rewrite_tail_replaces_the_end() {
+        let path = temp_dir("wal-rewrite").join("file");
+        fs::write(&path, "first\nsecond\n").unwrap();
+
+        rewrite_tail(&path, 6, b"other\nthird\n").unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"first\nother\nthird\n");
+        assert!(!is_pending(&path));
+    }
+
+    #[test]
+    fn rewrite_tail_creates_the_file() {
+        let path = temp_dir("wal-create").join("file");
+
+        rewrite_tail(&path, 0, b"line\n").unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"line\n");
+    }
+
+    #[test]
+    fn replay_is_idempotent() {
+        let path = temp_dir("wal-replay").join("file");
+        fs::write(&path, "first\nsecond\n").unwrap();
+
+        write(&path, &[rewrite(&path, 6, b"other\n")]).unwrap();
+        assert!(is_pending(&path));
+
+        assert_eq!(recover(&path).unwrap(), vec![path.clone()]);
+        assert_eq!(fs::read(&path).unwrap(), b"first\nother\n");
+        assert!(!is_pending(&path));
+
+        assert!(recover(&path).unwrap().is_empty());
+        assert_eq!(fs::read(&path).unwrap(), b"first\nother\n");
+    }
+
+    #[test]
+    fn replay_completes_a_partial_rewrite() {
+        let path = temp_dir("wal-partial").join("file");
+        fs::write(&path, "first\nsecond\n").unwrap();
+
+        // The crash came after truncating the file and writing part of the tail.
+        write(&path, &[rewrite(&path, 6, b"other\nthird\n")]).unwrap();
+        fs::write(&path, "first\noth").unwrap();
+
+        assert_eq!(recover(&path).unwrap(), vec![path.clone()]);
+        assert_eq!(fs::read(&path).unwrap(), b"first\nother\nthird\n");
+
+        // The same happens if the replay itself is interrupted and replayed again.
+        write(&path, &[rewrite(&path, 6, b"other\nthird\n")]).unwrap();
+        assert_eq!(recover(&path).unwrap(), vec![path.clone()]);
+        assert_eq!(fs::read(&path).unwrap(), b"first\nother\nthird\n");
+    }
+
+    #[test]
+    fn files_rewritten_together_are_replayed_together() {
+        let dir = temp_dir("wal-together");
+        let (first, second) = (dir.join("first"), dir.join("second"));
+        fs::write(&first, "a\nb\n").unwrap();
+        fs::write(&second, "{}\n").unwrap();
+
+        rewrite_tails(&[rewrite(&first, 2, b"c\n"), rewrite(&second, 0, b"{1}\n")]).unwrap();
+
+        assert_eq!(fs::read(&first).unwrap(), b"a\nc\n");
+        assert_eq!(fs::read(&second).unwrap(), b"{1}\n");
+
+        // A crash after the first file was rewritten.
+        write(
+            &first,
+            &[rewrite(&first, 0, b"d\n"), rewrite(&second, 0, b"{2}\n")],
+        )
+        .unwrap();
+        fs::write(&first, "d\n").unwrap();
+
+        assert_eq!(
+            recover(&first).unwrap(),
+            vec![first.clone(), second.clone()]
+        );
+        assert_eq!(fs::read(&first).unwrap(), b"d\n");
+        assert_eq!(fs::read(&second).unwrap(), b"{2}\n");
+        assert!(!is_pending(&first));
+        assert!(!is_pending(&second));
+    }
+
+    #[test]
+    fn a_side_file_missing_a_record_is_discarded() {
+        let dir = temp_dir("wal-missing-record");
+        let (first, second) = (dir.join("first"), dir.join("second"));
+        fs::write(&first, "a\n").unwrap();
+        fs::write(&second, "{}\n").unwrap();
+
+        write(
+            &first,
+            &[rewrite(&first, 0, b"b\n"), rewrite(&second, 0, b"{1}\n")],
+        )
+        .unwrap();
+        let wal = wal_path(&first);
+        let contents = fs::read(&wal).unwrap();
+        let record = contents.len() - "{1}\n".len();
+        let header = record
+            - contents[..record - 1]
+                .iter()
+                .rev()
+                .position(|&byte| byte == b'\n')
+                .unwrap()
+            - 1;
+        fs::write(&wal, &contents[..header]).unwrap();
+
+        assert!(recover(&first).unwrap().is_empty());
+        assert_eq!(fs::read(&first).unwrap(), b"a\n");
+        assert_eq!(fs::read(&second).unwrap(), b"{}\n");
+    }
+
+    #[test]
+    fn an_incomplete_side_file_is_discarded() {
+        let path = temp_dir("wal-torn").join("file");
+        fs::write(&path, "first\nsecond\n").unwrap();
+
+        write(&path, &[rewrite(&path, 6, b"other\nthird\n")]).unwrap();
+        let wal = wal_path(&path);
+        let contents = fs::read(&wal).unwrap();
+        fs::write(&wal, &contents[..contents.len() - 3]).unwrap();
+
+        assert!(recover(&path).unwrap().is_empty());
+        assert_eq!(fs::read(&path).unwrap(), b"first\nsecond\n");
+        assert!(!is_pending(&path));
+    }
+
+    #[test]
+    fn a_corrupted_side_file_is_discarded() {
+        let path = temp_dir("wal-corrupted").join("file");
+        fs::write(&path, "first\nsecond\n").unwrap();
+
+        write(&path, &[rewrite(&path, 6, b"other\n")]).unwrap();
+        let wal = wal_path(&path);
+        let mut contents = fs::read(&wal).unwrap();
+        let last = contents.len() - 2;
+        contents[last] = b'X';
+        fs::write(&wal, &contents).unwrap();
+
+        assert!(recover(&path).unwrap().is_empty());
+        assert_eq!(fs::read(&path).unwrap(), b"first\nsecond\n");
+    }
+
+    #[test]
+    fn checksums_extend() {
+        assert_eq!(
+            extend_checksum(checksum(b"first "), b"second"),
+            checksum(b"first second")
+        );
+        assert_ne!(checksum(b"first"), checksum(b"firts"));
+    }
+}